@@ -0,0 +1,1471 @@
+use anyhow::{bail, Context, Result};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{command, crate_authors, crate_description, crate_version, Arg, ArgAction, ArgGroup};
+use tac_k_lib::regex::bytes::RegexBuilder;
+#[cfg(target_os = "linux")]
+use tac_k_lib::SpillFile;
+use tac_k_lib::{
+    Characters, Engine, Fragment, InputKind, MmapAdvice, OutputFormat, ReverseOptions, SpillPolicy, Summary,
+    Throughput, TrailingSeparator,
+};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::output::{AtomicFile, Writer};
+use crate::walk::{self, Filter};
+
+/// Duplicates everything written into `writer` into `file` as well, like `tee`.
+struct Tee<W> {
+    writer: W,
+    file: BufWriter<File>,
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write_all(buf)?;
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.file.flush()
+    }
+}
+
+const HELP_TEMPLATE: &str = "\
+{name} ({version}) {author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}";
+
+pub fn main() -> Result<()> {
+    match run() {
+        // Whatever was reading the output went away, there is nothing left to do.
+        Err(e)
+            if e.chain()
+                .any(|e| e.downcast_ref().is_some_and(tac_k_lib::is_broken_pipe)) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn run() -> Result<()> {
+    #[allow(non_upper_case_globals)]
+    let command = command!()
+        .name("tac")
+        .about(crate_description!())
+        .author(crate_authors!("\n"))
+        .version(crate_version!())
+        .help_template(HELP_TEMPLATE)
+        .arg(
+            Arg::new("separator")
+                .value_name("SEP")
+                .long("separator")
+                .short('s')
+                .help(
+                    "Use SEP as the separator instead of newline, escapes like \\0, \\t or \\xNN included.\n\
+                     Only a single byte or character is supported, unless --regex is given.",
+                ),
+        )
+        .arg(
+            Arg::new("zero_terminated")
+                .long("zero-terminated")
+                .short('z')
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["separator", "regex"])
+                .help("Use NUL as the separator instead of newline, like --separator='\\0'"),
+        )
+        .arg(
+            Arg::new("crlf")
+                .value_name("WHEN")
+                .long("crlf")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("always")
+                .value_parser(["always", "auto"])
+                .conflicts_with_all(["separator", "zero_terminated", "regex", "characters"])
+                .help(
+                    "Use \\r\\n as the separator instead of newline, always or only in the inputs whose\n\
+                     first line ends with it (auto)",
+                ),
+        )
+        .arg(
+            Arg::new("regex")
+                .long("regex")
+                .short('r')
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Interpret the separator as a regular expression, in which ^ and $ also match\n\
+                     at the start and end of every line",
+                ),
+        )
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .short('b')
+                .action(ArgAction::SetTrue)
+                .help("Attach the separator to the start of the line following it instead of the end"),
+        )
+        .arg(
+            Arg::new("force_flush")
+                .long("line-buffered")
+                .action(ArgAction::SetTrue)
+                .help("Always flush output after each line"),
+        )
+        .arg(
+            Arg::new("output")
+                .value_name("FILE")
+                .long("output")
+                .short('o')
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write the output to FILE instead of stdout"),
+        )
+        .arg(
+            Arg::new("atomic")
+                .long("atomic")
+                .action(ArgAction::SetTrue)
+                .requires("output")
+                .conflicts_with("follow")
+                .help(
+                    "Write the output to a temporary file next to FILE and rename it over FILE once\n\
+                     complete, which is implied if FILE is also an input",
+                ),
+        )
+        .arg(
+            Arg::new("in_place")
+                .value_name("SUFFIX")
+                .long("in-place")
+                .short('i')
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("")
+                .conflicts_with_all(["output", "tee", "follow", "dry_run", "check_utf8"])
+                .help(
+                    "Replace each FILE with its reversed content, atomically, keeping a backup of\n\
+                     the original named FILE followed by SUFFIX if given. Asks first if stdin is a\n\
+                     terminal",
+                ),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .requires("in_place")
+                .help("Replace files with --in-place without asking first"),
+        )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .action(ArgAction::SetTrue)
+                .requires("in_place")
+                .conflicts_with("force")
+                .help("Ask before replacing each file with --in-place, like rm -i"),
+        )
+        .arg(
+            Arg::new("tee")
+                .value_name("FILE")
+                .long("tee")
+                .help("Also write the output to FILE"),
+        )
+        .arg(
+            Arg::new("output_format")
+                .value_name("FORMAT")
+                .long("output-format")
+                .value_parser(["raw", "json", "ndjson", "framed", "base64", "escaped", "offsets"])
+                .default_value("raw")
+                .help(
+                    "Write lines as they are (raw), as a JSON array (json) or as one JSON object\n\
+                     per line (ndjson), each object holding a line's byte offset and content,\n\
+                     or without separator and prefixed with their length as a big-endian u32 (framed)\n\
+                     or base64-encoded, one per line (base64)\n\
+                     or with C-style backslash escapes, one per line (escaped)\n\
+                     or as their byte offset and length separated by a tab, one per line (offsets)",
+                ),
+        )
+        .arg(
+            Arg::new("base64")
+                .long("base64")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("output_format")
+                .help("Base64-encode each line, same as --output-format base64"),
+        )
+        .arg(
+            Arg::new("escape")
+                .long("escape")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["output_format", "base64"])
+                .help("Escape each line C-style, same as --output-format escaped"),
+        )
+        .arg(
+            Arg::new("offsets")
+                .long("offsets")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["output_format", "base64", "escape"])
+                .help("Print the byte offset and length of each line instead, same as --output-format offsets"),
+        )
+        .arg(
+            Arg::new("label")
+                .value_name("SEP")
+                .long("label")
+                .short('H')
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(":")
+                .help("Prefix each line with the name of its file followed by SEP [default: :]"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .action(ArgAction::Count)
+                .overrides_with("quiet")
+                .help(
+                    "Precede the lines of each file with a header like ==> FILE <==, as head and tail do; \
+                     twice, also trace execution like --debug",
+                ),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .visible_alias("silent")
+                .short('q')
+                .action(ArgAction::SetTrue)
+                .overrides_with("verbose")
+                .help("Never write headers, which is the default"),
+        )
+        .arg(
+            Arg::new("fragment")
+                .value_name("WHERE")
+                .long("fragment")
+                .value_parser(["first", "last", "drop"])
+                .default_value("first")
+                .help(
+                    "Write the unterminated last line of an input, if any, first (running into\n\
+                     the next one), last, or not at all (drop)",
+                ),
+        )
+        .arg(
+            Arg::new("leading_fragment")
+                .value_name("WHERE")
+                .long("leading-fragment")
+                .value_parser(["first", "last", "drop"])
+                .default_value("last")
+                .requires("before")
+                .help(
+                    "With --before, write the part of an input preceding its first separator, if\n\
+                     any, first, last, or not at all (drop)",
+                ),
+        )
+        .arg(
+            Arg::new("ensure_trailing_separator")
+                .long("ensure-trailing-separator")
+                .action(ArgAction::SetTrue)
+                .help("Terminate the unterminated last line of an input, if any, with the separator"),
+        )
+        .arg(
+            Arg::new("strip_trailing_separator")
+                .long("strip-trailing-separator")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("ensure_trailing_separator")
+                .help("Leave the separator off the last line written of each input"),
+        )
+        .arg(
+            Arg::new("number")
+                .long("number")
+                .short('N')
+                .action(ArgAction::SetTrue)
+                .help("Precede every line with its line number in the input"),
+        )
+        .arg(
+            Arg::new("lines")
+                .value_name("N")
+                .long("lines")
+                .short('n')
+                .value_parser(clap::value_parser!(u64))
+                .help("Only write the last N lines of each input"),
+        )
+        .arg(
+            Arg::new("skip")
+                .value_name("N")
+                .long("skip")
+                .value_parser(clap::value_parser!(u64))
+                .help("Skip the last N lines of each input, e.g. to page backwards with --lines"),
+        )
+        .arg(
+            Arg::new("until_match")
+                .value_name("PATTERN")
+                .long("until-match")
+                .help("Stop after the first (i.e. last in the input) line containing PATTERN"),
+        )
+        .arg(
+            Arg::new("from_match")
+                .value_name("PATTERN")
+                .long("from-match")
+                .help("Start at the first (i.e. last in the input) line containing PATTERN"),
+        )
+        .group(
+            ArgGroup::new("match")
+                .args(["until_match", "from_match"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::new("exclusive")
+                .long("exclusive")
+                .action(ArgAction::SetTrue)
+                .requires("match")
+                .help("Leave out the lines matched by --until-match and --from-match"),
+        )
+        .arg(
+            Arg::new("after_context")
+                .value_name("N")
+                .long("after-context")
+                .short('A')
+                .value_parser(clap::value_parser!(u64))
+                .requires("until_match")
+                .help("Also write the N lines following the one matched by --until-match"),
+        )
+        .arg(
+            Arg::new("before_context")
+                .value_name("N")
+                .long("before-context")
+                .short('B')
+                .value_parser(clap::value_parser!(u64))
+                .requires("from_match")
+                .help("Also write the N lines preceding the one matched by --from-match"),
+        )
+        .arg(
+            Arg::new("context")
+                .value_name("N")
+                .long("context")
+                .short('C')
+                .value_parser(clap::value_parser!(u64))
+                .requires("match")
+                .help("Like both -A N and -B N, unless given themselves"),
+        )
+        .arg(
+            Arg::new("characters")
+                .long("characters")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "regex",
+                    "lines",
+                    "skip",
+                    "match",
+                    "output_format",
+                    "base64",
+                    "escape",
+                    "offsets",
+                    "label",
+                    "fragment",
+                    "leading_fragment",
+                    "number",
+                    "follow",
+                    "in_place",
+                    "count",
+                    "dry_run",
+                    "check_utf8",
+                ])
+                .help("Reverse the characters of every line instead of the order of lines, like rev"),
+        )
+        .arg(
+            Arg::new("both")
+                .long("both")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("characters")
+                .help("Reverse both the order of lines and the characters of every line"),
+        )
+        .group(ArgGroup::new("within").args(["characters", "both"]))
+        .arg(
+            Arg::new("bytes")
+                .long("bytes")
+                .action(ArgAction::SetTrue)
+                .requires("within")
+                .help("Reverse bytes instead of UTF-8 characters with --characters and --both"),
+        )
+        .arg(
+            Arg::new("mmap_threshold")
+                .value_name("BYTES")
+                .long("mmap-threshold")
+                .value_parser(clap::value_parser!(u64))
+                .help("Read files smaller than BYTES instead of memory-mapping them [default: 65536]"),
+        )
+        .arg(
+            Arg::new("no_mmap")
+                .long("no-mmap")
+                .action(ArgAction::SetTrue)
+                .help("Never memory-map inputs, read files backwards in chunks instead"),
+        )
+        .arg(
+            Arg::new("mmap_advice")
+                .value_name("ADVICE")
+                .long("mmap-advice")
+                .value_parser(["normal", "willneed", "backwards"])
+                .help(
+                    "Let the kernel read memory-mapped inputs ahead as usual (normal), read them whole\n\
+                     as soon as they are mapped (willneed) or read them backwards ahead of the lines\n\
+                     being written (backwards) [default: backwards]",
+                ),
+        )
+        .arg(
+            Arg::new("read_ahead")
+                .value_name("BYTES")
+                .long("read-ahead")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("no_mmap")
+                .help("Read BYTES of memory-mapped inputs ahead at a time with --mmap-advice backwards [default: 16777216]"),
+        )
+        .arg(
+            Arg::new("threads")
+                .value_name("N")
+                .short('j')
+                .long("threads")
+                .visible_alias("jobs")
+                .value_parser(clap::value_parser!(usize))
+                .help("Reverse several files and search large ones on N threads, 0 for one per CPU [default: 0]"),
+        )
+        .arg(
+            Arg::new("chunk_size")
+                .value_name("BYTES")
+                .long("chunk-size")
+                .value_parser(clap::value_parser!(usize))
+                .help("Search large inputs on several threads BYTES at a time, 0 to fit the CPU caches [default: 0]"),
+        )
+        .arg(
+            Arg::new("calibrate_search")
+                .long("calibrate-search")
+                .action(ArgAction::SetTrue)
+                .help("Time the available line search implementations on startup and use the fastest"),
+        )
+        .arg(
+            Arg::new("engine")
+                .value_name("ENGINE")
+                .long("engine")
+                .value_parser([
+                    "auto", "scalar", "avx512", "avx2", "sse2", "neon", "rvv", "simd128", "memchr",
+                ])
+                .conflicts_with("calibrate_search")
+                .hide(true)
+                .help("Search for line breaks with the given implementation, failing if it is unsupported"),
+        )
+        .arg(
+            Arg::new("stdin_timeout")
+                .value_name("SECS")
+                .long("stdin-timeout")
+                .value_parser(|str: &str| {
+                    str.parse::<f64>()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or("Expected a non-negative number of seconds")
+                })
+                .help("Fail if no data arrives on stdin for SECS seconds"),
+        )
+        .arg(
+            Arg::new("temp_dir")
+                .value_name("DIR")
+                .long("temp-dir")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Spill large stdin into a temporary file in DIR [default: $TMPDIR or the system's]"),
+        )
+        .arg(
+            Arg::new("secure_delete")
+                .long("secure-delete")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Overwrite the temporary file stdin is spilled into before removing it.\n\
+                     Best-effort only: journaling or copy-on-write filesystems, snapshots and SSDs\n\
+                     may still retain copies of the data.",
+                ),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Report progress, throughput and the estimated time left of each input to stderr,\n\
+                     if it is a terminal",
+                ),
+        )
+        .arg(
+            Arg::new("follow")
+                .long("follow")
+                .short('f')
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "output_format",
+                    "base64",
+                    "escape",
+                    "offsets",
+                    "label",
+                    "regex",
+                    "dry_run",
+                    "check_utf8",
+                ])
+                .help(
+                    "After reversing FILE, keep writing the lines appended to it as they arrive,\n\
+                     in their original order",
+                ),
+        )
+        .arg(
+            Arg::new("debug")
+                .long("debug")
+                .action(ArgAction::SetTrue)
+                .help("Report how each input is read and reversed to stderr, as -vv does"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Report the bytes read and written, lines, search engine and read and reverse times\n\
+                     of each input to stderr, as a line of key=value pairs",
+                ),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["output", "tee", "follow", "in_place", "dry_run", "check_utf8"])
+                .help("Only print the number of lines of each input,\npreceded by its name if there are several"),
+        )
+        .arg(Arg::new("dry_run").long("dry-run").action(ArgAction::SetTrue).help(
+            "Check that every input can be read and report how it would be reversed,\nwithout producing any output",
+        ))
+        .arg(
+            Arg::new("check_utf8")
+                .long("check-utf8")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("dry_run")
+                .help(
+                    "Check that every input is valid UTF-8 without producing any output,\n\
+                     reporting where the first invalid sequence of each invalid input is",
+                ),
+        )
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .short('R')
+                .action(ArgAction::SetTrue)
+                .help("Reverse every file under each directory among FILE, in order of name"),
+        )
+        .arg(
+            Arg::new("include")
+                .value_name("GLOB")
+                .long("include")
+                .action(ArgAction::Append)
+                .requires("recursive")
+                .help("Only reverse the files under directories whose name matches GLOB, e.g. '*.log'"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .value_name("GLOB")
+                .long("exclude")
+                .action(ArgAction::Append)
+                .requires("recursive")
+                .help("Skip the files under directories whose name matches GLOB"),
+        )
+        .arg(
+            Arg::new("files")
+                .value_name("FILE")
+                .num_args(..)
+                .help("Files to be reversed.\nRead from stdin if it is `-` or not specified."),
+        );
+
+    #[cfg(target_os = "linux")]
+    let command = command.arg(
+        Arg::new("memfd")
+            .long("memfd")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("temp_dir")
+            .help("Spill large stdin into an anonymous in-memory file instead of the temporary directory"),
+    );
+
+    #[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+    let command = command.arg(
+        Arg::new("zero_copy")
+            .long("zero-copy")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all([
+                "output",
+                "tee",
+                "output_format",
+                "base64",
+                "escape",
+                "offsets",
+                "label",
+                "characters",
+                "follow",
+                "in_place",
+                "count",
+                "dry_run",
+                "check_utf8",
+            ])
+            .help("Hand memory-mapped inputs over to stdout with vmsplice if it is a pipe, instead of copying them"),
+    );
+
+    #[cfg(target_family = "unix")]
+    let command = command.arg(
+        Arg::new("metrics_fd")
+            .value_name("FD")
+            .long("metrics-fd")
+            .value_parser(clap::value_parser!(u32))
+            .help(
+                "Write the length, number of lines and read and reverse times of each input\n\
+                 as a line of key=value pairs to file descriptor FD",
+            ),
+    );
+
+    #[cfg(feature = "lz4")]
+    let command = command.arg(
+        Arg::new("compress_spill")
+            .long("compress-spill")
+            .action(ArgAction::SetTrue)
+            .help("LZ4-compress stdin when it is spilled to a temporary file"),
+    );
+
+    #[cfg(feature = "encrypt")]
+    let command = command.arg(
+        Arg::new("encrypt_spill")
+            .long("encrypt-spill")
+            .action(ArgAction::SetTrue)
+            .help("Encrypt stdin with an ephemeral key when it is spilled to a temporary file"),
+    );
+
+    #[cfg(feature = "encoding")]
+    let command = command
+        .arg(
+            Arg::new("encoding")
+                .value_name("ENCODING")
+                .long("encoding")
+                .value_parser(|label: &str| match label {
+                    "auto" => Ok(tac_k_lib::encoding_rs::UTF_8),
+                    _ => tac_k_lib::encoding_rs::Encoding::for_label(label.as_bytes())
+                        .ok_or_else(|| format!("unknown encoding '{label}'")),
+                })
+                .conflicts_with_all(["follow", "count", "check_utf8"])
+                .help(
+                    "Decode inputs from ENCODING, e.g. utf-16le or windows-1252, and reverse their lines,\n\
+                     which a byte order mark overrides, as auto does UTF-8",
+                ),
+        )
+        .arg(
+            Arg::new("utf8_output")
+                .long("utf8-output")
+                .action(ArgAction::SetTrue)
+                .requires("encoding")
+                .help("Write the output in UTF-8 instead of the encoding of the input"),
+        );
+
+    let mut command = command;
+    let matches = command.get_matches_mut();
+    // Headers conflict with the modes writing no reversed lines, but tracing them with -vv does not.
+    if matches.get_count("verbose") == 1 {
+        let modes = [
+            ("in_place", "--in-place"),
+            ("count", "--count"),
+            ("dry_run", "--dry-run"),
+            ("check_utf8", "--check-utf8"),
+            ("zero_copy", "--zero-copy"),
+        ];
+        if let Some((_, mode)) = modes
+            .into_iter()
+            .find(|&(id, _)| matches.value_source(id) == Some(ValueSource::CommandLine))
+        {
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument '--verbose' cannot be used with '{mode}'"),
+                )
+                .exit()
+        }
+    }
+
+    let force_flush = matches.get_flag("force_flush");
+    let files: Vec<String> = match matches.get_many::<String>("files") {
+        Some(files) => files.cloned().collect(),
+        None => vec!["-".to_owned()],
+    };
+    let files = if matches.get_flag("recursive") {
+        let patterns = |id| {
+            matches
+                .get_many::<String>(id)
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect()
+        };
+        let filter = Filter {
+            include: patterns("include"),
+            exclude: patterns("exclude"),
+        };
+        walk::expand(files, &filter)?
+    } else {
+        files
+    };
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+    let separator = matches.get_one::<String>("separator").map_or("\n", String::as_str);
+    let crlf = matches.get_one::<String>("crlf").map(String::as_str);
+    let unescaped = if matches.get_flag("zero_terminated") {
+        vec![b'\0']
+    } else if crlf == Some("always") {
+        b"\r\n".to_vec()
+    } else if matches.get_flag("regex") {
+        // A regex handles escape sequences itself.
+        separator.as_bytes().to_vec()
+    } else {
+        tac_k_lib::unescape(separator).unwrap_or_else(|e| {
+            command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("invalid value '{separator}' for '--separator <SEP>': {e}"),
+                )
+                .exit()
+        })
+    };
+    let options = match unescaped[..] {
+        _ if crlf == Some("always") => ReverseOptions::new().separator_bytes(&unescaped),
+        _ if matches.get_flag("regex") => match RegexBuilder::new(separator).multi_line(true).build() {
+            Ok(regex) => ReverseOptions::new().separator_regex(regex),
+            Err(e) => command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("invalid regex for '--separator <SEP>': {e}"),
+                )
+                .exit(),
+        },
+        [byte] => ReverseOptions::new().separator(byte),
+        _ => match std::str::from_utf8(&unescaped).map(|text| text.parse::<char>()) {
+            Ok(Ok(char)) => ReverseOptions::new().separator_char(char),
+            _ => command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("invalid value '{separator}' for '--separator <SEP>': Only a single byte or character is supported"),
+                )
+                .exit(),
+        },
+    };
+    let options = options
+        .detect_crlf(crlf == Some("auto"))
+        .before(matches.get_flag("before"))
+        .stdin_timeout(matches.get_one::<Duration>("stdin_timeout").copied())
+        .secure_delete(matches.get_flag("secure_delete"))
+        .output_format(match matches.get_one::<String>("output_format").map(String::as_str) {
+            _ if matches.get_flag("base64") => OutputFormat::Base64,
+            _ if matches.get_flag("escape") => OutputFormat::Escaped,
+            _ if matches.get_flag("offsets") => OutputFormat::Offsets,
+            Some("json") => OutputFormat::Json,
+            Some("ndjson") => OutputFormat::Ndjson,
+            Some("framed") => OutputFormat::Framed,
+            Some("base64") => OutputFormat::Base64,
+            Some("escaped") => OutputFormat::Escaped,
+            Some("offsets") => OutputFormat::Offsets,
+            _ => OutputFormat::Raw,
+        });
+    let options = options.fragment(match matches.get_one::<String>("fragment").map(String::as_str) {
+        Some("last") => Fragment::Last,
+        Some("drop") => Fragment::Drop,
+        _ => Fragment::First,
+    });
+    let options = options.leading_fragment(
+        match matches.get_one::<String>("leading_fragment").map(String::as_str) {
+            Some("first") => Fragment::First,
+            Some("drop") => Fragment::Drop,
+            _ => Fragment::Last,
+        },
+    );
+    let options = options.trailing_separator(if matches.get_flag("ensure_trailing_separator") {
+        TrailingSeparator::Ensure
+    } else if matches.get_flag("strip_trailing_separator") {
+        TrailingSeparator::Strip
+    } else {
+        TrailingSeparator::Keep
+    });
+    let options = options.number(matches.get_flag("number"));
+    let options = match matches.get_one::<String>("label") {
+        Some(separator) => options.label(separator),
+        None => options,
+    };
+    let options = match matches.get_one::<u64>("mmap_threshold") {
+        Some(&threshold) => options.mmap_threshold(threshold),
+        None => options,
+    };
+    let options = options.mmap(!matches.get_flag("no_mmap"));
+    let read_ahead = matches.get_one::<usize>("read_ahead").copied();
+    let options = options.mmap_advice(match matches.get_one::<String>("mmap_advice").map(String::as_str) {
+        Some("normal" | "willneed") if read_ahead.is_some() => bail!("--read-ahead requires --mmap-advice backwards"),
+        Some("normal") => MmapAdvice::Normal,
+        Some("willneed") => MmapAdvice::WillNeed,
+        _ => read_ahead.map_or_else(MmapAdvice::default, MmapAdvice::Backwards),
+    });
+    let options = match matches.get_one::<usize>("threads") {
+        Some(&threads) => options.threads(threads),
+        None => options,
+    };
+    let options = match matches.get_one::<usize>("chunk_size") {
+        Some(&chunk_size) => options.chunk_size(chunk_size),
+        None => options,
+    };
+    let options = options.calibrate_search(matches.get_flag("calibrate_search"));
+    let options = options.engine(match matches.get_one::<String>("engine").map(String::as_str) {
+        Some("scalar") => Engine::Scalar,
+        Some("avx512") => Engine::Avx512,
+        Some("avx2") => Engine::Avx2,
+        Some("sse2") => Engine::Sse2,
+        Some("neon") => Engine::Neon,
+        Some("rvv") => Engine::Rvv,
+        Some("simd128") => Engine::Simd128,
+        Some("memchr") => Engine::Memchr,
+        _ => Engine::Auto,
+    });
+    // The progress line is redrawn in place, which only makes sense on a terminal.
+    let options = if matches.get_flag("progress") && std::io::stderr().is_terminal() {
+        let mut throughput = Throughput::new(Duration::from_secs(10));
+        options.on_progress(move |done, total| {
+            throughput.update(done, total);
+            report_progress(&throughput, done, total);
+        })
+    } else {
+        options
+    };
+    let options = if matches.get_flag("debug") || matches.get_count("verbose") > 1 {
+        options.on_trace(|message| eprintln!("tac: {message}"))
+    } else {
+        options
+    };
+    let options = match matches.get_one::<u64>("lines") {
+        Some(&lines) => options.max_records(lines),
+        None => options,
+    };
+    let options = match matches.get_one::<u64>("skip") {
+        Some(&skip) => options.skip_records(skip),
+        None => options,
+    };
+    let inclusive = !matches.get_flag("exclusive");
+    let options = match matches.get_one::<String>("until_match") {
+        Some(pattern) => options.until_match(pattern, inclusive),
+        None => options,
+    };
+    let options = match matches.get_one::<String>("from_match") {
+        Some(pattern) => options.from_match(pattern, inclusive),
+        None => options,
+    };
+    let context = matches.get_one::<u64>("context").copied().unwrap_or_default();
+    let options = options.context(
+        matches.get_one::<u64>("before_context").copied().unwrap_or(context),
+        matches.get_one::<u64>("after_context").copied().unwrap_or(context),
+    );
+    let characters = match matches.get_flag("bytes") {
+        true => Characters::Bytes,
+        false => Characters::Utf8,
+    };
+    let options = match matches.get_flag("both") {
+        true => options.characters(characters),
+        false => options,
+    };
+    let options = match matches.get_one::<PathBuf>("temp_dir") {
+        Some(dir) => options.spill_policy(SpillPolicy::TempFileIn(dir.clone())),
+        None => options,
+    };
+    #[cfg(target_os = "linux")]
+    let options = if matches.get_flag("memfd") {
+        options.spill_file(SpillFile::memfd)
+    } else {
+        options
+    };
+    #[cfg(target_family = "unix")]
+    let metrics = match matches.get_one::<u32>("metrics_fd") {
+        // Reopening the descriptor through /dev/fd avoids taking ownership of a raw one.
+        Some(fd) => Some(
+            File::options()
+                .append(true)
+                .open(format!("/dev/fd/{fd}"))
+                .with_context(|| format!("failed to open file descriptor {fd}"))?,
+        ),
+        None => None,
+    };
+    #[cfg(not(target_family = "unix"))]
+    let metrics = None::<File>;
+    let stats = matches.get_flag("stats");
+    let options = if stats || metrics.is_some() {
+        options.on_stats(move |input| {
+            let line = format_metrics(input);
+            if stats {
+                eprint!("tac: {line}");
+            }
+            if let Err(e) = metrics
+                .as_ref()
+                .map_or(Ok(()), |mut metrics| metrics.write_all(line.as_bytes()))
+            {
+                eprintln!("tac: failed to write metrics: {e}");
+            }
+        })
+    } else {
+        options
+    };
+    #[cfg(feature = "encoding")]
+    let options = match matches.get_one::<&'static tac_k_lib::encoding_rs::Encoding>("encoding") {
+        Some(&encoding) => options.encoding(encoding).utf8_output(matches.get_flag("utf8_output")),
+        None => options,
+    };
+    #[cfg(feature = "lz4")]
+    let options = options.compress_spill(matches.get_flag("compress_spill"));
+    #[cfg(feature = "encrypt")]
+    let options = options.encrypt_spill(matches.get_flag("encrypt_spill"));
+
+    if matches.get_flag("dry_run") {
+        return dry_run(&files, &options);
+    }
+    if matches.get_flag("check_utf8") {
+        return check_utf8(&files, &options);
+    }
+    if matches.get_flag("count") {
+        return count(&files, &options);
+    }
+    if let Some(suffix) = matches.get_one::<String>("in_place") {
+        let confirm = if matches.get_flag("interactive") {
+            Confirm::EachFile
+        } else if matches.get_flag("force") || !std::io::stdin().is_terminal() {
+            Confirm::Never
+        } else {
+            Confirm::Once
+        };
+        return in_place(
+            &files,
+            &options,
+            Some(suffix.as_str()).filter(|suffix| !suffix.is_empty()),
+            confirm,
+        );
+    }
+
+    let paths: Vec<_> = files.iter().map(|&file| path(file)).collect();
+    // --characters conflicts with --regex, so the separator is fixed bytes.
+    let rev = match (matches.get_flag("characters"), &unescaped[..]) {
+        (false, _) => None,
+        (true, &[separator]) => Some((separator, characters)),
+        (true, _) => bail!("--characters requires a single-byte separator"),
+    };
+    let headers = matches.get_count("verbose") != 0;
+    let follow = match (matches.get_flag("follow"), &paths[..]) {
+        (false, _) => None,
+        // --follow conflicts with --regex, so the separator is fixed bytes.
+        (true, [Some(path)]) => Some(Follow::new(path, unescaped)?),
+        (true, _) => bail!("--follow requires a single FILE"),
+    };
+    #[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+    if matches.get_flag("zero_copy") {
+        return splice_all(&files, &paths, &options);
+    }
+    let mut atomic = None;
+    let mut writer = match matches.get_one::<PathBuf>("output") {
+        None => Writer::stdout(force_flush, &paths),
+        // Truncating an input before it is read would lose it, if not kill tac with SIGBUS.
+        Some(output) if matches.get_flag("atomic") || is_input(output, &paths) => {
+            if follow.is_some() {
+                bail!("--follow cannot write into the file it follows");
+            }
+            let file = AtomicFile::create(output)
+                .with_context(|| format!("failed to create a temporary file next to {}", output.display()))?;
+            let writer = Writer::file(file.try_clone()?, force_flush, &paths);
+            atomic = Some(file);
+            writer
+        }
+        Some(output) => {
+            let file = File::create(output).with_context(|| format!("failed to create {}", output.display()))?;
+            Writer::file(file, force_flush, &paths)
+        }
+    };
+
+    let options = options.line_buffered(writer.is_unbuffered());
+
+    match matches.get_one::<String>("tee") {
+        Some(path) => {
+            let file = File::create(path).with_context(|| format!("failed to create {path}"))?;
+            let mut tee = Tee {
+                writer: &mut writer,
+                file: BufWriter::new(file),
+            };
+            reverse_all(&mut tee, &paths, &options, rev, headers, follow)?;
+            tee.flush()?;
+        }
+        None => reverse_all(&mut writer, &paths, &options, rev, headers, follow)?,
+    }
+    writer.flush()?;
+    if let Some(atomic) = atomic {
+        atomic.commit(None).context("failed to replace the output file")?;
+    }
+    Ok(())
+}
+
+/// When to ask before replacing files with `--in-place`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Confirm {
+    /// Never, with `--force` or if stdin is not a terminal.
+    Never,
+    /// Once for all files, by default.
+    Once,
+    /// Before every file, with `--interactive`.
+    EachFile,
+}
+
+/// Ask `question` on stderr and whether the answer read from stdin is yes.
+fn ask(question: &str) -> Result<bool> {
+    eprint!("tac: {question} [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES"))
+}
+
+/// Replace each of `files` with its reversed content, backing the original up under its name
+/// followed by `suffix` if any, see `--in-place`.
+fn in_place(files: &[&str], options: &ReverseOptions, suffix: Option<&str>, confirm: Confirm) -> Result<()> {
+    if confirm == Confirm::Once && !ask(&format!("replace {} file(s) with their reversed content?", files.len()))? {
+        return Ok(());
+    }
+    let mut failed = 0;
+    for &file in files {
+        if confirm == Confirm::EachFile && file != "-" && !ask(&format!("reverse {file} in place?"))? {
+            continue;
+        }
+        let result = if file == "-" {
+            Err(anyhow::anyhow!("standard input cannot be reversed in place"))
+        } else {
+            reverse_in_place(Path::new(file), options, suffix)
+        };
+        if let Err(e) = result {
+            eprintln!("{file}: {e:#}");
+            failed += 1;
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} input(s) could not be reversed in place", files.len());
+    }
+    Ok(())
+}
+
+fn reverse_in_place(path: &Path, options: &ReverseOptions, suffix: Option<&str>) -> Result<()> {
+    let file = AtomicFile::create(path).context("failed to create a temporary file")?;
+    let mut writer = Writer::file(file.try_clone()?, false, &[Some(path)]);
+    options.run(&mut writer, Some(path))?;
+    writer.flush()?;
+    let backup = suffix.map(|suffix| {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        PathBuf::from(backup)
+    });
+    file.commit(backup.as_deref()).context("failed to replace the file")
+}
+
+/// Whether `output` is the same file as one of the inputs at `paths`.
+fn is_input(output: &Path, paths: &[Option<&str>]) -> bool {
+    let Ok(output) = output.canonicalize() else {
+        // An output that does not exist yet cannot be an input.
+        return false;
+    };
+    paths
+        .iter()
+        .flatten()
+        .any(|path| Path::new(path).canonicalize().is_ok_and(|path| path == output))
+}
+
+/// Path of the input named `file` on the command line, `None` for stdin.
+fn path(file: &str) -> Option<&str> {
+    if file == "-" {
+        None
+    } else {
+        Some(file)
+    }
+}
+
+/// Write the reversed content of every input into `writer`, preceded by a `==> name <==` header if
+/// `headers`, see `--verbose`.
+fn reverse_all<W: Write>(
+    writer: &mut W,
+    paths: &[Option<&str>],
+    options: &ReverseOptions,
+    rev: Option<(u8, Characters)>,
+    headers: bool,
+    follow: Option<Follow>,
+) -> Result<()> {
+    let summary = match rev {
+        None if !headers => options.run_all(writer, paths),
+        _ => {
+            let mut summary = Summary::default();
+            for (index, &path) in paths.iter().enumerate() {
+                if headers {
+                    // Like head and tail, headers after the first are set apart by an empty line.
+                    let blank = if index == 0 { "" } else { "\n" };
+                    if let Err(e) = writeln!(writer, "{blank}==> {} <==", path.unwrap_or("standard input")) {
+                        summary
+                            .inputs
+                            .push((path.map(PathBuf::from), Err(tac_k_lib::Error::Write { source: e })));
+                        summary.skipped = paths.len() - index - 1;
+                        break;
+                    }
+                }
+                let result = match rev {
+                    Some((separator, characters)) => rev_file(writer, path, separator, characters).map_err(Into::into),
+                    None => options.run(writer, path),
+                };
+                summary.inputs.push((path.map(PathBuf::from), result));
+            }
+            summary
+        }
+    };
+
+    for (path, e) in summary.errors() {
+        if tac_k_lib::is_broken_pipe(e.io()) {
+            return Ok(());
+        }
+        match path {
+            Some(path) if path.is_dir() => eprintln!("{}: is a directory, see --recursive", path.display()),
+            Some(path) => eprintln!("{}: {}", path.display(), describe(e)),
+            None => eprintln!("standard input: {}", describe(e)),
+        }
+    }
+    if summary.failed() != 0 {
+        bail!("{} of {} input(s) could not be reversed", summary.failed(), paths.len());
+    }
+    if let Some(follow) = follow {
+        follow.run(writer)?;
+    }
+    Ok(())
+}
+
+/// `error` followed by what caused it, like `{:#}` formats an [`anyhow::Error`].
+fn describe(error: &tac_k_lib::Error) -> String {
+    anyhow::Chain::new(error)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Write the reversed content of every input into stdout without copying it if stdout is a pipe,
+/// see `--zero-copy`.
+#[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+fn splice_all(files: &[&str], paths: &[Option<&str>], options: &ReverseOptions) -> Result<()> {
+    use std::os::fd::AsFd;
+
+    let stdout = std::io::stdout().lock();
+    let mut failed = 0;
+    for (file, &path) in files.iter().zip(paths) {
+        if let Err(e) = options.run_spliced(stdout.as_fd(), path) {
+            if tac_k_lib::is_broken_pipe(e.io()) {
+                return Ok(());
+            }
+            let name = if *file == "-" { "standard input" } else { file };
+            eprintln!("{name}: {}", describe(&e));
+            failed += 1;
+        }
+    }
+    if failed != 0 {
+        bail!("{failed} of {} input(s) could not be reversed", files.len());
+    }
+    Ok(())
+}
+
+/// Write the content of the input at `path`, stdin if `None`, with the characters of every line
+/// reversed, see `--characters`.
+fn rev_file<W: Write>(
+    writer: &mut W,
+    path: Option<&str>,
+    separator: u8,
+    characters: Characters,
+) -> std::io::Result<()> {
+    match path {
+        Some(path) => tac_k_lib::reverse_characters(writer, BufReader::new(File::open(path)?), separator, characters),
+        None => tac_k_lib::reverse_characters(writer, std::io::stdin().lock(), separator, characters),
+    }
+}
+
+/// A file whose new lines keep being written once it has been reversed, see `--follow`.
+struct Follow<'a> {
+    name: &'a str,
+    file: File,
+    separator: Vec<u8>,
+    /// Offset up to which the file has been read.
+    offset: u64,
+    /// The unterminated last line read, held back until the rest of it arrives.
+    pending: Vec<u8>,
+}
+
+impl<'a> Follow<'a> {
+    /// Interval at which the file is checked for new lines.
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Follow the file at `path` from its current end, i.e. past what reversing it is about to write.
+    fn new(path: &'a str, separator: Vec<u8>) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+        let offset = file.metadata()?.len();
+        Ok(Follow {
+            name: path,
+            file,
+            separator,
+            offset,
+            pending: vec![],
+        })
+    }
+
+    /// Write the lines appended to the file into `writer` as they arrive, until writing fails.
+    fn run<W: Write>(mut self, writer: &mut W) -> Result<()> {
+        writer.flush()?;
+        eprintln!("==> following {} <==", self.name);
+
+        loop {
+            if !self.poll(writer)? {
+                std::thread::sleep(Self::INTERVAL);
+            }
+        }
+    }
+
+    /// Write the lines appended to the file since the last call into `writer`, starting over from
+    /// its start if it was truncated, and return whether anything was read.
+    fn poll<W: Write>(&mut self, writer: &mut W) -> Result<bool> {
+        let len = self.file.metadata()?.len();
+        if len < self.offset {
+            eprintln!("tac: {}: file truncated", self.name);
+            self.offset = 0;
+            self.pending.clear();
+        }
+        if len == self.offset {
+            return Ok(false);
+        }
+
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let start = self.pending.len();
+        (&mut self.file)
+            .take(len - self.offset)
+            .read_to_end(&mut self.pending)?;
+        self.offset += (self.pending.len() - start) as u64;
+        // Hold back an unterminated last line until the rest of it arrives. Its separator may have
+        // been cut in two, so search what was held back as well.
+        if let Some(index) = tac_k_lib::rfind(&self.pending, &self.separator) {
+            let end = index + self.separator.len();
+            writer.write_all(&self.pending[..end])?;
+            writer.flush()?;
+            self.pending.drain(..end);
+        }
+        Ok(true)
+    }
+}
+
+/// Describe `stats` as a line of `key=value` pairs, e.g. `file="foo.log" bytes=1024
+/// bytes_written=1024 records=16 engine=AVX2 spilled=false input_seconds=0.000012
+/// reverse_seconds=0.000034`.
+fn format_metrics(stats: &tac_k_lib::Stats) -> String {
+    let file = match &stats.path {
+        Some(path) => path.to_string_lossy(),
+        None => "-".into(),
+    };
+    format!(
+        "file={file:?} bytes={} bytes_written={} records={} engine={} spilled={} input_seconds={:.6} \
+         reverse_seconds={:.6}\n",
+        stats.bytes,
+        stats.bytes_written,
+        stats.records,
+        stats.engine.name(),
+        stats.spilled,
+        stats.input_time.as_secs_f64(),
+        stats.reverse_time.as_secs_f64()
+    )
+}
+
+/// Percentage of `total` that `done` is, rounded down, `100` if `total` is `0`.
+fn percent(done: u64, total: u64) -> u128 {
+    // Widened, as `done * 100` overflows past 184 PB.
+    (u128::from(done) * 100).checked_div(u128::from(total)).unwrap_or(100)
+}
+
+/// Print a progress line to stderr, overwriting the previous one of the same input.
+fn report_progress(throughput: &Throughput, done: u64, total: u64) {
+    const MIB: f64 = 1024.0 * 1024.0;
+
+    let percent = percent(done, total);
+    let mut line = format!("{percent:3}% {:.1}/{:.1} MiB", done as f64 / MIB, total as f64 / MIB);
+    if let Some(rate) = throughput.bytes_per_sec() {
+        line += &format!(", {:.1} MiB/s", rate / MIB);
+    }
+    if done < total {
+        if let Some(eta) = throughput.eta() {
+            let secs = eta.as_secs();
+            line += &format!(", ETA {}:{:02}", secs / 60, secs % 60);
+        }
+    }
+    // Clear what is left of a longer previous line, and keep the final one.
+    let end = if done >= total { "\n" } else { "" };
+    eprint!("\r{line}\x1b[K{end}");
+}
+
+fn dry_run(files: &[&str], options: &ReverseOptions) -> Result<()> {
+    let mut failed = 0;
+    for &file in files {
+        let name = if file == "-" { "standard input" } else { file };
+        let info = match options.probe(path(file)) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("{name}: {}", describe(&e));
+                failed += 1;
+                continue;
+            }
+        };
+
+        match info.kind {
+            InputKind::Mapped | InputKind::Read => {
+                let how = if info.kind == InputKind::Mapped { "mmap" } else { "read" };
+                let len = info.len.unwrap_or_default();
+                match info.last_separator {
+                    Some(offset) => println!("{name}: {how}, {len} bytes, last separator at byte {offset}"),
+                    None if len == 0 => println!("{name}: {how}, empty"),
+                    None => println!("{name}: {how}, {len} bytes, separator not found (single record)"),
+                }
+            }
+            InputKind::Backwards => println!(
+                "{name}: read backwards, {} bytes, in chunks of {} bytes",
+                info.len.unwrap_or_default(),
+                info.buffer_size
+            ),
+            InputKind::Buffered => println!(
+                "{name}: buffered, in memory up to {} bytes, then spilled to a temporary file",
+                info.buffer_size
+            ),
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} input(s) cannot be reversed", files.len());
+    }
+    Ok(())
+}
+
+fn count(files: &[&str], options: &ReverseOptions) -> Result<()> {
+    let mut failed = 0;
+    for &file in files {
+        let name = if file == "-" { "standard input" } else { file };
+        match options.count(path(file)) {
+            Ok(records) if files.len() == 1 => println!("{records}"),
+            Ok(records) => println!("{name}:{records}"),
+            Err(e) => {
+                eprintln!("{name}: {}", describe(&e));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} input(s) could not be counted", files.len());
+    }
+    Ok(())
+}
+
+fn check_utf8(files: &[&str], options: &ReverseOptions) -> Result<()> {
+    let mut failed = 0;
+    for &file in files {
+        let name = if file == "-" { "standard input" } else { file };
+        match options.check_utf8(path(file)) {
+            Ok(None) => {}
+            Ok(Some(invalid)) => {
+                eprintln!(
+                    "{name}: invalid UTF-8 at byte {} (line starting at byte {})",
+                    invalid.offset, invalid.record_offset
+                );
+                failed += 1;
+            }
+            Err(e) => {
+                eprintln!("{name}: {}", describe(&e));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        bail!("{failed} of {} input(s) are not valid UTF-8", files.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_in_place() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("tac-k-test-in-place-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("known_hosts");
+        std::fs::write(&file, b"one\ntwo\n").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink("known_hosts", &link).unwrap();
+
+        let options = ReverseOptions::new();
+        reverse_in_place(&file, &options, None).unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), b"two\none\n");
+        assert_eq!(std::fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Through the link, the file is replaced and the link kept, as is the backup.
+        reverse_in_place(&link, &options, Some(".bak")).unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read(&file).unwrap(), b"one\ntwo\n");
+        assert_eq!(std::fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read(dir.join("link.bak")).unwrap(), b"two\none\n");
+        // No temporary file is left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_follow() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-follow-{}", std::process::id()));
+        std::fs::write(&path, b"old--").unwrap();
+        let append = |bytes: &[u8]| {
+            let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(bytes).unwrap();
+        };
+        let mut follow = Follow::new(path.to_str().unwrap(), b"--".to_vec()).unwrap();
+        let mut written = vec![];
+
+        // What the file held to begin with is not written again.
+        assert!(!follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"");
+
+        // An unterminated line, even one ending with part of a separator, is held back.
+        append(b"one--tw");
+        assert!(follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"one--");
+        append(b"o-");
+        assert!(follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"one--");
+        append(b"-three");
+        assert!(follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"one--two--");
+        assert!(!follow.poll(&mut written).unwrap());
+
+        // Once truncated, the file is followed from its start, and what was held back is dropped.
+        std::fs::write(&path, b"new--").unwrap();
+        assert!(follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"one--two--new--");
+        append(b"four--");
+        assert!(follow.poll(&mut written).unwrap());
+        assert_eq!(written, b"one--two--new--four--");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(0, 0), 100);
+        assert_eq!(percent(0, 200), 0);
+        assert_eq!(percent(199, 200), 99);
+        assert_eq!(percent(200, 200), 100);
+        assert_eq!(percent(u64::MAX / 2, u64::MAX), 49);
+        assert_eq!(percent(u64::MAX, u64::MAX), 100);
+    }
+}
@@ -0,0 +1,150 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use tac_k_lib::ReverseOptions;
+
+use crate::output::Writer;
+
+const HELP: &str = concat!(
+    "tac (",
+    env!("CARGO_PKG_VERSION"),
+    ") ",
+    env!("CARGO_PKG_AUTHORS"),
+    "\n",
+    env!("CARGO_PKG_DESCRIPTION"),
+    "
+
+Usage: tac [OPTIONS] [FILE]...
+
+Arguments:
+  [FILE]...  Files to be reversed.
+             Read from stdin if it is `-` or not specified.
+
+Options:
+  -s, --separator <BYTE>  Use BYTE as the separator instead of newline.
+                          Only single-byte character is supported,
+                          escapes like \\0, \\t or \\xNN included.
+      --line-buffered     Always flush output after each line
+  -h, --help              Print help
+  -V, --version           Print version
+"
+);
+
+/// Arguments accepted by the lightweight command line interface.
+struct Args {
+    separator: u8,
+    force_flush: bool,
+    files: Vec<OsString>,
+}
+
+pub fn main() -> ExitCode {
+    let args = match parse(std::env::args_os().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => return ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}\n\nFor more information, try '--help'.");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut paths: Vec<_> = args
+        .files
+        .into_iter()
+        .map(|file| if file == "-" { None } else { Some(PathBuf::from(file)) })
+        .collect();
+    if paths.is_empty() {
+        paths.push(None);
+    }
+
+    let mut writer = Writer::stdout(args.force_flush, &paths);
+    let summary = ReverseOptions::new()
+        .separator(args.separator)
+        .line_buffered(writer.is_unbuffered())
+        .run_all(&mut writer, &paths);
+
+    for (path, e) in summary.errors() {
+        // Whatever was reading the output went away, there is nothing left to report.
+        if tac_k_lib::is_broken_pipe(e.io()) {
+            return ExitCode::SUCCESS;
+        }
+        // What failed, then why.
+        let mut message = e.to_string();
+        let mut source = std::error::Error::source(e);
+        while let Some(cause) = source {
+            message = format!("{message}: {cause}");
+            source = cause.source();
+        }
+        let e = message;
+        match path {
+            Some(path) => eprintln!("{}: {e}", path.display()),
+            None => eprintln!("standard input: {e}"),
+        }
+    }
+    if summary.failed() != 0 {
+        eprintln!(
+            "Error: {} of {} input(s) could not be reversed",
+            summary.failed(),
+            paths.len()
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Parse the command line arguments, or return `None` if they only asked for help or the version.
+fn parse(mut args: impl Iterator<Item = OsString>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        separator: b'\n',
+        force_flush: false,
+        files: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        let option = match arg.to_str() {
+            Some(option) if option.starts_with('-') && option != "-" => option,
+            _ => {
+                parsed.files.push(arg);
+                continue;
+            }
+        };
+        match option {
+            "--" => {
+                parsed.files.extend(args);
+                break;
+            }
+            "-h" | "--help" => {
+                print!("{HELP}");
+                return Ok(None);
+            }
+            "-V" | "--version" => {
+                println!("tac {}", env!("CARGO_PKG_VERSION"));
+                return Ok(None);
+            }
+            "--line-buffered" => parsed.force_flush = true,
+            "-s" | "--separator" => {
+                let value = args.next().ok_or("a value is required for '--separator <BYTE>'")?;
+                parsed.separator = separator(value.to_str().unwrap_or_default())?;
+            }
+            _ => {
+                let value = option
+                    .strip_prefix("--separator=")
+                    .or_else(|| option.strip_prefix("-s").filter(|_| !option.starts_with("--")))
+                    .ok_or_else(|| format!("unexpected argument '{option}' found"))?;
+                parsed.separator = separator(value)?;
+            }
+        }
+    }
+    Ok(Some(parsed))
+}
+
+fn separator(value: &str) -> Result<u8, String> {
+    let unescaped =
+        tac_k_lib::unescape(value).map_err(|e| format!("invalid value '{value}' for '--separator <BYTE>': {e}"))?;
+    match unescaped[..] {
+        [byte] => Ok(byte),
+        _ => Err(format!(
+            "invalid value '{value}' for '--separator <BYTE>': Only single-byte character is supported"
+        )),
+    }
+}
@@ -0,0 +1,33 @@
+/* C bindings to the tac-k reverse engine, built as a shared library by `cargo build -p tac-k-capi`. */
+
+#ifndef TAC_K_H
+#define TAC_K_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Called with every record given to tac_reverse_buf, last record first, along with its user_data.
+ * Returning anything but 0 stops the reversal. */
+typedef int (*tac_record_callback)(const uint8_t *record, size_t len, void *user_data);
+
+/* Write the records read from in_fd, terminated by sep, into out_fd, last record first. Neither is
+ * closed. A regular file is memory-mapped, anything else is read until its end first.
+ *
+ * Returns 0 on success, the error number of the failure if it has one, -1 otherwise. Unix only. */
+int tac_reverse_fd(int in_fd, int out_fd, uint8_t sep);
+
+/* Call callback with every record of the len bytes at buf, terminated by sep, last record first.
+ * Records include their trailing separator if any, and point into buf, which may be NULL if len is 0.
+ *
+ * Returns 0 on success, or the first value other than 0 returned by callback. */
+int tac_reverse_buf(const uint8_t *buf, size_t len, uint8_t sep, tac_record_callback callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TAC_K_H */
@@ -0,0 +1,201 @@
+//! C bindings to the reverse engine of [`tac_k_lib`], see `include/tac_k.h`.
+//!
+//! Every function returns `0` on success. Panics are caught at the boundary rather than unwinding
+//! into the caller.
+
+use std::ffi::{c_int, c_void};
+use std::panic::catch_unwind;
+
+use tac_k_lib::Sink;
+
+/// Returned when a failure has no error number, or the library panicked.
+const FAILURE: c_int = -1;
+
+/// Called with every record of the buffer given to [`tac_reverse_buf`], last record first, along
+/// with its `user_data`. Returning anything but `0` stops the reversal.
+pub type TacRecordCallback = unsafe extern "C" fn(record: *const u8, len: usize, user_data: *mut c_void) -> c_int;
+
+/// Write the records read from the file descriptor `in_fd`, terminated by `sep`, into the file
+/// descriptor `out_fd`, last record first. Neither is closed.
+///
+/// A regular file is memory-mapped, anything else (e.g. a pipe or a socket) is read until its end
+/// first. Returns the error number of the failure if it has one, `-1` otherwise.
+///
+/// # Safety
+///
+/// Both file descriptors must be open for the duration of the call. A regular file must not be
+/// truncated while it is mapped.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn tac_reverse_fd(in_fd: c_int, out_fd: c_int, sep: u8) -> c_int {
+    use std::fs::File;
+    use std::mem::ManuallyDrop;
+    use std::os::fd::FromRawFd;
+
+    let reverse = || -> Result<(), tac_k_lib::Error> {
+        // Borrow the file descriptors without closing them afterwards, the caller still owns them.
+        let mut input = ManuallyDrop::new(File::from_raw_fd(in_fd));
+        let mut output = ManuallyDrop::new(File::from_raw_fd(out_fd));
+        if input.metadata()?.is_file() {
+            let mmap = memmap2::Mmap::map(&*input)?;
+            tac_k_lib::reverse_bytes(&mut *output, &mmap, sep)
+        } else {
+            tac_k_lib::reverse_reader(&mut *output, &mut *input, sep)
+        }
+    };
+    match catch_unwind(reverse) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => e.io().raw_os_error().unwrap_or(FAILURE),
+        Err(_) => FAILURE,
+    }
+}
+
+/// Call `callback` with every record of the `len` bytes at `buf`, terminated by `sep`, last record
+/// first. Records include their trailing separator if any, and point into `buf`.
+///
+/// Returns the first value other than `0` returned by `callback`, which stops the reversal, or `-1`
+/// if the library panicked.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, unless `len` is `0`, in which case it may be null.
+/// `callback` must be safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn tac_reverse_buf(
+    buf: *const u8,
+    len: usize,
+    sep: u8,
+    callback: TacRecordCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(buf, len)
+    };
+    let mut sink = Callback { callback, user_data };
+    // The sink only holds raw pointers, which are not touched again if the callback unwinds.
+    let reverse = std::panic::AssertUnwindSafe(|| tac_k_lib::reverse_into(bytes, sep, &mut sink));
+    match catch_unwind(reverse) {
+        Ok(Ok(())) => 0,
+        Ok(Err(status)) => status,
+        Err(_) => FAILURE,
+    }
+}
+
+/// Passes records on to a C callback.
+struct Callback {
+    callback: TacRecordCallback,
+    user_data: *mut c_void,
+}
+
+impl Sink for Callback {
+    type Error = c_int;
+
+    fn record(&mut self, record: &[u8]) -> Result<(), c_int> {
+        match unsafe { (self.callback)(record.as_ptr(), record.len(), self.user_data) } {
+            0 => Ok(()),
+            status => Err(status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects the records it is called with into the `Vec<Vec<u8>>` at `user_data`, stopping
+    /// with `7` at a record of `stop`.
+    unsafe extern "C" fn collect(record: *const u8, len: usize, user_data: *mut c_void) -> c_int {
+        let record = std::slice::from_raw_parts(record, len);
+        if record == b"stop\n" {
+            return 7;
+        }
+        (*user_data.cast::<Vec<Vec<u8>>>()).push(record.to_vec());
+        0
+    }
+
+    fn reverse_buf(buf: *const u8, len: usize) -> (c_int, Vec<Vec<u8>>) {
+        let mut records: Vec<Vec<u8>> = vec![];
+        let status = unsafe { tac_reverse_buf(buf, len, b'\n', collect, (&mut records as *mut Vec<Vec<u8>>).cast()) };
+        (status, records)
+    }
+
+    #[test]
+    fn test_reverse_buf() {
+        let bytes = b"one\ntwo\nthree";
+        assert_eq!(
+            reverse_buf(bytes.as_ptr(), bytes.len()),
+            (0, vec![b"three".to_vec(), b"two\n".to_vec(), b"one\n".to_vec()])
+        );
+        // An empty buffer may be null.
+        assert_eq!(reverse_buf(std::ptr::null(), 0), (0, vec![]));
+        // What the callback returns stops the reversal.
+        let bytes = b"one\nstop\nthree\n";
+        assert_eq!(reverse_buf(bytes.as_ptr(), bytes.len()), (7, vec![b"three\n".to_vec()]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reverse_fd() {
+        use std::ffi::OsStr;
+        use std::io::{Read, Seek, Write};
+        use std::os::fd::AsRawFd;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join(format!("tac-k-capi-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Paths are only ever opened by the caller, so they need not be UTF-8.
+        let input_path = dir.join(OsStr::from_bytes(b"input-\xff"));
+        std::fs::write(&input_path, b"one\ntwo\n").unwrap();
+        let input = std::fs::File::open(&input_path).unwrap();
+        let output_path = dir.join("output");
+        let mut output = std::fs::File::options()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&output_path)
+            .unwrap();
+        let read_output = |output: &mut std::fs::File| {
+            let mut written = vec![];
+            output.rewind().unwrap();
+            output.read_to_end(&mut written).unwrap();
+            output.set_len(0).unwrap();
+            output.rewind().unwrap();
+            written
+        };
+
+        // A regular file.
+        assert_eq!(
+            unsafe { tac_reverse_fd(input.as_raw_fd(), output.as_raw_fd(), b'\n') },
+            0
+        );
+        assert_eq!(read_output(&mut output), b"two\none\n");
+
+        // A pipe.
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        writer.write_all(b"a,b,c").unwrap();
+        drop(writer);
+        assert_eq!(
+            unsafe { tac_reverse_fd(reader.as_raw_fd(), output.as_raw_fd(), b',') },
+            0
+        );
+        assert_eq!(read_output(&mut output), b"cb,a,");
+
+        // Failures return their error number, and leave the file descriptors open.
+        const EBADF: c_int = 9;
+        let read_only = std::fs::File::open(&output_path).unwrap();
+        assert_eq!(
+            unsafe { tac_reverse_fd(input.as_raw_fd(), read_only.as_raw_fd(), b'\n') },
+            EBADF
+        );
+        assert_eq!(unsafe { tac_reverse_fd(c_int::MAX, output.as_raw_fd(), b'\n') }, EBADF);
+        assert_eq!(
+            unsafe { tac_reverse_fd(input.as_raw_fd(), output.as_raw_fd(), b'\n') },
+            0
+        );
+        assert_eq!(read_output(&mut output), b"two\none\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+//! Throughput of every search engine this machine supports, and of whole reversals, on synthetic
+//! inputs of various line lengths. Run with `cargo bench -p tac-k-lib`, or `cargo bench -p
+//! tac-k-lib -- default` to check that the default options cost no more than a plain reversal.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tac_k_lib::{bench, Engine, ReverseOptions};
+
+/// Size of the inputs searched.
+const LEN: usize = 4 * 1024 * 1024;
+/// Average line lengths, from separator-dense inputs to nearly separator-free ones.
+const LINE_LENGTHS: [usize; 5] = [0, 8, 80, 1024, 64 * 1024];
+
+fn search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    for line_len in LINE_LENGTHS {
+        let input = bench::synthetic_input(LEN, line_len, 1);
+        for engine in bench::engines() {
+            group.bench_with_input(BenchmarkId::new(engine.name(), line_len), &input, |b, input| {
+                b.iter(|| bench::search(engine, black_box(input), b'\n'))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn reverse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reverse");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    for line_len in LINE_LENGTHS {
+        let input = bench::synthetic_input(LEN, line_len, 1);
+        for engine in [Engine::Scalar, Engine::Auto] {
+            let options = ReverseOptions::new().engine(engine);
+            group.bench_with_input(BenchmarkId::new(engine.name(), line_len), &input, |b, input| {
+                b.iter(|| options.run_bytes(&mut std::io::sink(), black_box(input)))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// The default options against the plainest possible reversal, which they should not fall far
+/// behind: every record is written as it is found, without any per-record work.
+fn default_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("default");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    let mut output = Vec::with_capacity(LEN);
+    for line_len in [8, 80] {
+        let input = bench::synthetic_input(LEN, line_len, 1);
+        group.bench_with_input(BenchmarkId::new("plain", line_len), &input, |b, input| {
+            b.iter(|| {
+                output.clear();
+                bench::reverse_plain(&mut output, black_box(input), b'\n')
+            })
+        });
+        let options = ReverseOptions::new();
+        group.bench_with_input(BenchmarkId::new("options", line_len), &input, |b, input| {
+            b.iter(|| {
+                output.clear();
+                options.run_bytes(&mut output, black_box(input))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search, reverse, default_path);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+//! Reverse arbitrary inputs with every search engine this machine supports, and compare the result
+//! with splitting the input forwards. Run with `cargo fuzz run reverse` from `src/tac-k-lib`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tac_k_lib::{bench, ReverseOptions};
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the separator, so that dense and sparse inputs are both explored.
+    let Some((&separator, bytes)) = data.split_first() else {
+        return;
+    };
+    let mut expected: Vec<&[u8]> = bytes.split_inclusive(|&byte| byte == separator).collect();
+    expected.reverse();
+    let expected = expected.concat();
+    for engine in bench::engines() {
+        let mut output = Vec::with_capacity(bytes.len());
+        ReverseOptions::new()
+            .separator(separator)
+            .engine(engine)
+            .run_bytes(&mut output, bytes)
+            .unwrap();
+        assert!(output == expected, "the {} search disagrees", engine.name());
+    }
+});
@@ -0,0 +1,79 @@
+use memmap2::Mmap;
+
+/// Default number of bytes read ahead of the part of a memory-mapped input being scanned, see
+/// [`MmapAdvice::Backwards`].
+pub(crate) const DEFAULT_WINDOW: usize = 16 * 1024 * 1024; // 16 MiB
+
+/// How the kernel is advised to read memory-mapped inputs ahead, see
+/// [`ReverseOptions::mmap_advice`].
+///
+/// Inputs are scanned from their end to their start, while the kernel reads ahead of every page
+/// fault, i.e. past the part being scanned. This matters little for inputs in the page cache, but
+/// a lot for cold inputs on spinning disks. Advice is only given on Unix.
+///
+/// [`ReverseOptions::mmap_advice`]: crate::ReverseOptions::mmap_advice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmapAdvice {
+    /// No advice, the kernel reads ahead as it sees fit.
+    Normal,
+    /// Read the whole input ahead as soon as it is mapped (`MADV_WILLNEED`), best for inputs that
+    /// fit in memory.
+    WillNeed,
+    /// Disable the readahead of the kernel (`MADV_RANDOM`), and ask for the given number of bytes
+    /// preceding the part being scanned instead (`MADV_WILLNEED`), so that the input is read
+    /// backwards in large requests.
+    Backwards(usize),
+}
+
+impl Default for MmapAdvice {
+    fn default() -> Self {
+        MmapAdvice::Backwards(DEFAULT_WINDOW)
+    }
+}
+
+/// Give `advice` about the whole of `mmap`, returning the window to prefetch backwards, if any.
+/// Failing to advise the kernel is not an error, merely a missed optimization.
+pub(crate) fn advise(mmap: &Mmap, advice: MmapAdvice) -> Option<usize> {
+    #[cfg(target_family = "unix")]
+    match advice {
+        MmapAdvice::Normal => None,
+        MmapAdvice::WillNeed => {
+            let _ = mmap.advise(memmap2::Advice::WillNeed);
+            None
+        }
+        MmapAdvice::Backwards(window) => {
+            let _ = mmap.advise(memmap2::Advice::Random);
+            will_need(&mmap[mmap.len().saturating_sub(window)..]);
+            Some(window)
+        }
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        let _ = (mmap, advice);
+        None
+    }
+}
+
+/// Ask the kernel to read the pages of `bytes`, part of a memory mapping, ahead.
+pub(crate) fn will_need(bytes: &[u8]) {
+    will_need_at(bytes.as_ptr() as usize, bytes.len());
+}
+
+/// Ask the kernel to read the `len` bytes at `address`, part of a memory mapping, ahead.
+pub(crate) fn will_need_at(address: usize, len: usize) {
+    #[cfg(target_family = "unix")]
+    {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+        let aligned = address - address % page_size;
+        // Only advice, which never invalidates the mapping: an error is of no consequence.
+        let _ = unsafe {
+            libc::madvise(
+                aligned as *mut libc::c_void,
+                address - aligned + len,
+                libc::MADV_WILLNEED,
+            )
+        };
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = (address, len);
+}
@@ -0,0 +1,37 @@
+use std::fs::File;
+use std::io::Result;
+use std::path::Path;
+
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Split an `archive.zip:member` path into the path of the archive and the name of the member.
+///
+/// Only paths that do not exist themselves while the archive does are taken apart, so a file
+/// which happens to be named like that is still read as is.
+pub(crate) fn split_member(path: &Path) -> Option<(&Path, &str)> {
+    let name = path.to_str()?;
+    let (archive, member) = name.split_once(".zip:")?;
+    let archive = Path::new(&name[..archive.len() + ".zip".len()]);
+    if member.is_empty() || path.exists() || !archive.is_file() {
+        return None;
+    }
+    Some((archive, member))
+}
+
+/// Open the zip archive at `path`.
+pub(crate) fn open(path: &Path) -> Result<ZipArchive<File>> {
+    Ok(ZipArchive::new(File::open(path)?)?)
+}
+
+/// Look up the member `name` of `archive`, which was opened from `path`.
+pub(crate) fn member<'a>(archive: &'a mut ZipArchive<File>, path: &Path, name: &str) -> Result<ZipFile<'a>> {
+    archive.by_name(name).map_err(|e| match e {
+        ZipError::FileNotFound => std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} has no member named {name}", path.display()),
+        ),
+        e => e.into(),
+    })
+}
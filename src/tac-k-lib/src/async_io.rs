@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Poll, Waker};
+
+use futures_util::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::emit::Emitter;
+use crate::{Error, ReverseOptions, Stop};
+
+/// Number of bytes the input buffer is grown by before each read.
+const READ_CHUNK_SIZE: usize = 64 * 1024; // 64 KiB
+/// Size of the chunks of output handed over by the thread reversing a file.
+const WRITE_CHUNK_SIZE: usize = 64 * 1024; // 64 KiB
+/// Number of chunks the thread reversing a file may get ahead of the writer by.
+const PENDING_CHUNKS: usize = 4;
+
+/// Write the reversed content read from `reader` into `writer`, last line first.
+///
+/// This is the asynchronous counterpart of [`reverse_file`](crate::reverse_file), built on the
+/// runtime-agnostic [`futures-io`](https://docs.rs/futures-io) traits, so it works with any
+/// executor (async-std, smol, tokio through its compat layer, ...). An async stream can neither be
+/// memory-mapped nor spilled, so the whole input is buffered in memory, and so is the reversed
+/// output before it is written.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_async;
+///
+/// let mut result = vec![];
+/// futures_executor::block_on(reverse_async(&mut &b"one\ntwo\n"[..], &mut result, b'\n')).unwrap();
+///
+/// assert_eq!(result, b"two\none\n");
+/// ```
+pub async fn reverse_async<R, W>(reader: &mut R, writer: &mut W, separator: u8) -> std::result::Result<(), Error>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    reverse(reader, writer, &ReverseOptions::new().separator(separator))
+        .await
+        .map_err(Error::from)
+}
+
+pub(crate) async fn reverse<R, W>(reader: &mut R, writer: &mut W, options: &ReverseOptions) -> Result<()>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    // Grow the buffer by hand rather than through `read_to_end`, so that running out of memory is
+    // an error instead of an abort.
+    let mut input = vec![];
+    loop {
+        let len = input.len();
+        crate::try_reserve(&mut input, READ_CHUNK_SIZE)?;
+        input.resize(len + READ_CHUNK_SIZE, 0);
+        let bytes_read = reader.read(&mut input[len..]).await?;
+        input.truncate(len + bytes_read);
+        if bytes_read == 0 {
+            break;
+        }
+    }
+
+    #[cfg(feature = "encoding")]
+    let (input, decoded) = crate::encoding::decode(input, options)?;
+    #[cfg(feature = "encoding")]
+    let options = &*decoded;
+
+    // The emitter is never held across an await point, so the future stays `Send`.
+    let mut output = vec![];
+    crate::try_reserve(&mut output, input.len())?;
+    let result = (|| -> Result<()> {
+        let _pinned = crate::search::PinnedEngine::new(options.engine)?;
+        let detected = options.for_input(&input);
+        let options = &*detected;
+        let mut emitter = Emitter::new(&mut output, input.len() as u64, options);
+        crate::emit_reversed(&input, 0, &options.separator, &mut emitter)?;
+        emitter.finish()
+    })();
+    match result {
+        Err(e) if Stop::is(&e) => {}
+        result => result?,
+    }
+    drop(input);
+
+    writer.write_all(&output).await?;
+    writer.flush().await
+}
+
+/// Write the reversed content of the file at `path` into `writer`, last line first, with lines
+/// terminated by `separator`.
+///
+/// This is the asynchronous counterpart of [`reverse_file`](crate::reverse_file) for inputs that
+/// can be memory-mapped, read backwards or spilled as usual: the input is reversed on a thread of
+/// its own, so that the executor is never blocked, and handed over to `writer` in chunks of 64 KiB.
+/// At most a few chunks are ever waiting to be written, so a slow writer holds the reversal back
+/// instead of letting the output pile up in memory. If writing fails, the reversal stops as well.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_file_async;
+///
+/// let path = std::env::temp_dir().join("tac-k-reverse-file-async.txt");
+/// std::fs::write(&path, "one\ntwo\n").unwrap();
+///
+/// let mut result = vec![];
+/// futures_executor::block_on(reverse_file_async(&mut result, Some(&path), b'\n')).unwrap();
+///
+/// assert_eq!(result, b"two\none\n");
+/// ```
+pub async fn reverse_file_async<W, P>(writer: &mut W, path: Option<P>, separator: u8) -> std::result::Result<(), Error>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref().map(|path| path.as_ref().to_path_buf());
+    reverse_file(writer, path, ReverseOptions::new().separator(separator))
+        .await
+        .map_err(Error::from)
+}
+
+pub(crate) async fn reverse_file<W>(writer: &mut W, path: Option<PathBuf>, options: ReverseOptions) -> Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let channel = Arc::new(Channel::default());
+    let mut sender = Sender {
+        channel: channel.clone(),
+        buf: Vec::new(),
+    };
+    std::thread::Builder::new()
+        .name("tac-k-reverse".to_owned())
+        .spawn(move || {
+            let result = options.run(&mut sender, path);
+            sender.finish(result.map_err(Into::into));
+        })?;
+
+    let receiver = Receiver(channel);
+    loop {
+        let chunk = std::future::poll_fn(|cx| {
+            let mut state = receiver.0.lock();
+            if let Some(chunk) = state.chunks.pop_front() {
+                receiver.0.space.notify_one();
+                return Poll::Ready(Ok(Some(chunk)));
+            }
+            match state.result.take() {
+                Some(result) => Poll::Ready(result.map(|()| None)),
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await?;
+        match chunk {
+            Some(chunk) => writer.write_all(&chunk).await?,
+            None => return writer.flush().await,
+        }
+    }
+}
+
+/// Chunks of output on their way from the thread reversing a file to the async writer.
+#[derive(Default)]
+struct Channel {
+    state: Mutex<State>,
+    /// Notified whenever a chunk is taken out, or the receiver goes away.
+    space: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    chunks: VecDeque<Vec<u8>>,
+    /// Result of the reversal, once it is over.
+    result: Option<Result<()>>,
+    /// Whether the receiver went away, i.e. writing failed.
+    closed: bool,
+    /// Waker of the task waiting for chunks, if any.
+    waker: Option<Waker>,
+}
+
+impl Channel {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // Nothing panics while holding the lock.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The reversing end of a [`Channel`], collecting the output into chunks.
+struct Sender {
+    channel: Arc<Channel>,
+    buf: Vec<u8>,
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        // The receiver must not wait forever for the result of a reversal that panicked.
+        if std::thread::panicking() {
+            self.finish(Err(std::io::Error::other("reversal panicked")));
+        }
+    }
+}
+
+impl Sender {
+    /// Pass the `result` of the reversal on, once it is over.
+    fn finish(&self, result: Result<()>) {
+        let mut state = self.channel.lock();
+        state.result = Some(result);
+        state.wake();
+    }
+
+    /// Hand the chunk collected so far over, waiting for there to be room for it.
+    fn send(&mut self) -> Result<()> {
+        let mut state = self.channel.lock();
+        while state.chunks.len() >= PENDING_CHUNKS && !state.closed {
+            state = self.channel.space.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.closed {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        state.chunks.push_back(std::mem::take(&mut self.buf));
+        state.wake();
+        Ok(())
+    }
+}
+
+impl Write for Sender {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.buf.is_empty() {
+            crate::try_reserve(&mut self.buf, WRITE_CHUNK_SIZE)?;
+        }
+        let len = buf.len().min(WRITE_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() == WRITE_CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+/// The writing end of a [`Channel`], which tells the thread reversing a file to stop once dropped.
+struct Receiver(Arc<Channel>);
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+        self.0.space.notify_one();
+    }
+}
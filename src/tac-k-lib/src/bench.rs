@@ -0,0 +1,118 @@
+//! Helpers to measure the search engines on this machine, as used by the criterion suite in
+//! `benches/`.
+//!
+//! ## Example
+//!
+//! ```
+//! use tac_k_lib::bench;
+//!
+//! let input = bench::synthetic_input(64 * 1024, 80, 1);
+//! for (engine, time) in bench::compare_engines(&input, b'\n', 3) {
+//!     println!("{}: {time:?}", engine.name());
+//! }
+//! ```
+
+use std::io::Write;
+use std::time::Duration;
+
+use crate::search::Backend;
+use crate::Engine;
+
+/// Every engine but [`Engine::Auto`], whether this machine supports it or not.
+const ENGINES: [Engine; 8] = [
+    Engine::Scalar,
+    Engine::Avx512,
+    Engine::Avx2,
+    Engine::Sse2,
+    Engine::Neon,
+    Engine::Rvv,
+    Engine::Simd128,
+    Engine::Memchr,
+];
+
+/// `len` bytes of lowercase letters terminated by `b'\n'` every `line_len` bytes on average, the
+/// length of each line being picked uniformly between `0` and twice that. The same `seed` gives the
+/// same input. A `line_len` of `0` gives nothing but separators.
+pub fn synthetic_input(len: usize, line_len: usize, seed: u64) -> Vec<u8> {
+    // A xorshift generator, whose state must not be zero.
+    let mut state = seed | 1;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut input = Vec::with_capacity(len);
+    while input.len() < len {
+        let line = (next() % (line_len as u64).saturating_mul(2).saturating_add(1)) as usize;
+        let line = line.min(len - input.len() - 1);
+        input.extend((0..line).map(|_| b'a' + (next() % 26) as u8));
+        input.push(b'\n');
+    }
+    input
+}
+
+/// Every engine this machine supports, the scalar one first.
+pub fn engines() -> Vec<Engine> {
+    ENGINES.into_iter().filter(|engine| engine.is_supported()).collect()
+}
+
+/// Number of occurrences of `separator` in `bytes` found by `engine`, searching backwards like a
+/// reversal does, or `None` if this machine does not support it.
+pub fn search(engine: Engine, bytes: &[u8], separator: u8) -> Option<usize> {
+    let backend = match engine {
+        Engine::Auto => crate::search::backend(),
+        engine => engine.backend()?,
+    };
+    Some(count(backend, bytes, separator))
+}
+
+/// The shortest of `runs` searches of `bytes` for `separator` with every engine this machine
+/// supports, the scalar one first.
+///
+/// ## Panics
+///
+/// If an engine finds a different number of separators than the scalar one does.
+pub fn compare_engines(bytes: &[u8], separator: u8, runs: usize) -> Vec<(Engine, Duration)> {
+    let expected = count(Backend::Scalar, bytes, separator);
+    engines()
+        .into_iter()
+        .filter_map(|engine| Some((engine, engine.backend()?)))
+        .map(|(engine, backend)| {
+            let time = (0..runs.max(1))
+                .filter_map(|_| {
+                    let start = crate::now()?;
+                    let found = count(backend, std::hint::black_box(bytes), separator);
+                    let elapsed = start.elapsed();
+                    assert_eq!(found, expected, "the {} search disagrees", engine.name());
+                    Some(elapsed)
+                })
+                .min()
+                .unwrap_or_default();
+            (engine, time)
+        })
+        .collect()
+}
+
+/// Write the records of `bytes` separated by `separator` into `writer`, last record first, straight
+/// from the search without any of the options of [`ReverseOptions`](crate::ReverseOptions). That
+/// is the least a reversal can cost, which the default options should come close to.
+pub fn reverse_plain(writer: &mut dyn Write, bytes: &[u8], separator: u8) -> std::io::Result<()> {
+    let mut last_printed = bytes.len();
+    crate::search::search_auto(bytes, separator, &mut |offset| {
+        writer.write_all(&bytes[offset..last_printed])?;
+        last_printed = offset;
+        Ok(())
+    })?;
+    writer.write_all(&bytes[..last_printed])
+}
+
+fn count(backend: Backend, bytes: &[u8], separator: u8) -> usize {
+    let mut found = 0;
+    // Counting never aborts the search.
+    let _ = backend.search(bytes, separator, &mut |_| {
+        found += 1;
+        Ok(())
+    });
+    found
+}
@@ -0,0 +1,73 @@
+#[cfg(feature = "allocator_api")]
+use std::ptr::NonNull;
+#[cfg(feature = "allocator_api")]
+use std::sync::Arc;
+
+#[cfg(feature = "allocator_api")]
+use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+
+/// Buffer that small files and `stdin` are read into.
+#[cfg(not(feature = "allocator_api"))]
+pub(crate) type Buffer = Vec<u8>;
+
+/// Buffer that small files and `stdin` are read into, allocated by the allocator set with
+/// [`ReverseOptions::buffer_allocator`](crate::ReverseOptions::buffer_allocator).
+#[cfg(feature = "allocator_api")]
+pub(crate) type Buffer = allocator_api2::vec::Vec<u8, BufferAllocator>;
+
+#[cfg(not(feature = "allocator_api"))]
+pub(crate) use crate::try_reserve as reserve;
+
+/// Make room for `additional` more bytes in `buf`, see [`try_reserve`](crate::try_reserve).
+#[cfg(feature = "allocator_api")]
+pub(crate) fn reserve(buf: &mut Buffer, additional: usize) -> std::io::Result<()> {
+    buf.try_reserve(additional)
+        .map_err(|e| crate::out_of_memory(additional, e))
+}
+
+/// Allocator shared by the buffers of a [`ReverseOptions`](crate::ReverseOptions), the global one
+/// unless another one is set.
+#[cfg(feature = "allocator_api")]
+#[derive(Clone)]
+pub(crate) struct BufferAllocator(pub(crate) Option<Arc<dyn Allocator + Send + Sync>>);
+
+#[cfg(feature = "allocator_api")]
+unsafe impl Allocator for BufferAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        match &self.0 {
+            Some(allocator) => allocator.allocate(layout),
+            None => Global.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        match &self.0 {
+            Some(allocator) => allocator.deallocate(ptr, layout),
+            None => Global.deallocate(ptr, layout),
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match &self.0 {
+            Some(allocator) => allocator.grow(ptr, old_layout, new_layout),
+            None => Global.grow(ptr, old_layout, new_layout),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match &self.0 {
+            Some(allocator) => allocator.shrink(ptr, old_layout, new_layout),
+            None => Global.shrink(ptr, old_layout, new_layout),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+/// Sizes of the CPU caches and memory pages of this machine, as far as they could be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CacheSizes {
+    /// Size of the level 2 cache of a core, in bytes.
+    pub(crate) l2: Option<usize>,
+    /// Size of the level 3 cache shared by the cores, in bytes.
+    pub(crate) l3: Option<usize>,
+    /// Size of a memory page, in bytes.
+    pub(crate) page: usize,
+}
+
+/// The cache and page sizes of this machine, detected once.
+pub(crate) fn sizes() -> CacheSizes {
+    static DETECTED: std::sync::OnceLock<CacheSizes> = std::sync::OnceLock::new();
+
+    *DETECTED.get_or_init(|| CacheSizes {
+        l2: cache_size(2),
+        l3: cache_size(3),
+        page: page_size(),
+    })
+}
+
+/// Size of the data or unified cache of `level` of the first CPU, as reported by sysfs.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cache_size(level: u32) -> Option<usize> {
+    let caches = std::fs::read_dir("/sys/devices/system/cpu/cpu0/cache").ok()?;
+    caches.flatten().find_map(|cache| {
+        let read = |name| std::fs::read_to_string(cache.path().join(name)).ok();
+        if read("level")?.trim().parse::<u32>().ok()? != level || read("type")?.trim() == "Instruction" {
+            return None;
+        }
+        parse_size(read("size")?.trim())
+    })
+}
+
+/// Size of the cache of `level`, as reported by `sysctl`.
+#[cfg(target_vendor = "apple")]
+fn cache_size(level: u32) -> Option<usize> {
+    let name: &[u8] = match level {
+        2 => b"hw.l2cachesize\0",
+        3 => b"hw.l3cachesize\0",
+        _ => return None,
+    };
+    let mut size: u64 = 0;
+    let mut len = std::mem::size_of::<u64>();
+    let result = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr().cast(),
+            (&mut size as *mut u64).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    match (result, usize::try_from(size)) {
+        (0, Ok(size)) if size != 0 => Some(size),
+        _ => None,
+    }
+}
+
+/// Cache sizes are not detected on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+fn cache_size(_level: u32) -> Option<usize> {
+    None
+}
+
+/// Parse a size like `2048K` or `32M`, as sysfs reports them.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+pub(crate) fn parse_size(size: &str) -> Option<usize> {
+    let (digits, unit) = match size.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => size.split_at(at),
+        None => (size, ""),
+    };
+    let unit = match unit {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Size of a memory page, `4096` if it cannot be found out.
+fn page_size() -> usize {
+    #[cfg(target_family = "unix")]
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => return size as usize,
+        _ => {}
+    }
+    4096
+}
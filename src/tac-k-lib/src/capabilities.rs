@@ -0,0 +1,59 @@
+/// What this build of the library supports, as returned by [`capabilities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// LZ4-compressed spill files, see the `lz4` feature.
+    pub lz4: bool,
+    /// Encrypted spill files, see the `encrypt` feature.
+    pub encrypt: bool,
+    /// The `RevRecordsExt` extension trait, see the `bstr` feature.
+    pub bstr: bool,
+    /// The asynchronous API, see the `async` feature.
+    pub async_io: bool,
+    /// Trace messages through the `log` crate, see the `log` feature.
+    pub log: bool,
+    /// Custom allocators for input buffers, see the `allocator_api` feature.
+    pub allocator_api: bool,
+    /// `archive.zip:member` input paths, see the `zip` feature.
+    pub zip: bool,
+    /// Regular expression separators, see the `regex` feature.
+    pub regex: bool,
+    /// Decompressing `.gz` inputs, see the `gzip` feature.
+    pub gzip: bool,
+    /// Decompressing `.zst` inputs, see the `zstd` feature.
+    pub zstd: bool,
+    /// Decompressing `.xz` inputs, see the `xz` feature.
+    pub xz: bool,
+    /// Anonymous memory-backed spill files, see `SpillFile::memfd`.
+    pub memfd: bool,
+    /// Name of the SIMD implementation used to scan inputs on this machine: `"AVX-512"`, `"AVX2"`,
+    /// `"SSE2"`, `"NEON"`, `"RVV"`, `"SIMD128"`, `"memchr"`, or `"scalar"` if none is available or
+    /// it turned out slower, see `calibrate_search`.
+    pub simd: &'static str,
+}
+
+/// Describe the optional features this library was compiled with and the SIMD support detected at
+/// runtime, e.g. for diagnostics or to only offer options that are actually available.
+///
+/// ## Example
+///
+/// ```
+/// let capabilities = tac_k_lib::capabilities();
+/// println!("scanning with {}", capabilities.simd);
+/// ```
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        lz4: cfg!(feature = "lz4"),
+        encrypt: cfg!(feature = "encrypt"),
+        bstr: cfg!(feature = "bstr"),
+        async_io: cfg!(feature = "async"),
+        log: cfg!(feature = "log"),
+        allocator_api: cfg!(feature = "allocator_api"),
+        zip: cfg!(feature = "zip"),
+        regex: cfg!(feature = "regex"),
+        gzip: cfg!(feature = "gzip"),
+        zstd: cfg!(feature = "zstd"),
+        xz: cfg!(feature = "xz"),
+        memfd: cfg!(target_os = "linux"),
+        simd: crate::search::search_backend(),
+    }
+}
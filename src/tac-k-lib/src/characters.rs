@@ -0,0 +1,83 @@
+use std::io::{BufRead, Result, Write};
+
+/// The units the content of a record is reversed in, see [`reverse_characters`] and
+/// [`ReverseOptions::characters`].
+///
+/// [`ReverseOptions::characters`]: crate::ReverseOptions::characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Characters {
+    /// UTF-8 encoded characters, i.e. code points. Invalid UTF-8 sequences are kept as they are.
+    #[default]
+    Utf8,
+    /// Bytes, whatever the encoding of the content.
+    Bytes,
+}
+
+/// Write the content read from `reader` into `writer` with the characters of every record
+/// reversed, records terminated by `separator` staying in order, like `rev`.
+///
+/// Each record is reversed without its separator, which stays at its end. Reading stops after each
+/// record, so this streams inputs of any size and is suitable for pipes. Use
+/// [`ReverseOptions::characters`](crate::ReverseOptions::characters) to reverse the order of the
+/// records as well.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{reverse_characters, Characters};
+///
+/// let mut result = vec![];
+/// reverse_characters(&mut result, &b"abc\nd\xc3\xa9f\n"[..], b'\n', Characters::Utf8).unwrap();
+///
+/// assert_eq!(result, "cba\nf\u{e9}d\n".as_bytes());
+/// ```
+pub fn reverse_characters<W: Write, R: BufRead>(
+    writer: &mut W,
+    mut reader: R,
+    separator: u8,
+    characters: Characters,
+) -> Result<()> {
+    let mut record = Vec::new();
+    let mut reversed = Vec::new();
+    loop {
+        record.clear();
+        if reader.read_until(separator, &mut record)? == 0 {
+            return writer.flush();
+        }
+        let content = record.strip_suffix(&[separator]).unwrap_or(&record);
+        reversed.clear();
+        reverse_into(content, characters, &mut reversed);
+        writer.write_all(&reversed)?;
+        writer.write_all(&record[content.len()..])?;
+    }
+}
+
+/// Append `content` to `out` with its characters in reverse order.
+pub(crate) fn reverse_into(content: &[u8], characters: Characters, out: &mut Vec<u8>) {
+    match characters {
+        Characters::Bytes => out.extend(content.iter().rev()),
+        Characters::Utf8 => {
+            // Copy every character (or invalid sequence) in order, filling `out` from the end.
+            let mut end = out.len() + content.len();
+            out.resize(end, 0);
+            let mut rest = content;
+            while !rest.is_empty() {
+                let (valid, invalid) = match std::str::from_utf8(rest) {
+                    Ok(valid) => (valid, 0),
+                    Err(e) => (
+                        std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or_default(),
+                        e.error_len().unwrap_or(rest.len() - e.valid_up_to()),
+                    ),
+                };
+                for char in valid.chars() {
+                    end -= char.len_utf8();
+                    char.encode_utf8(&mut out[end..]);
+                }
+                rest = &rest[valid.len()..];
+                end -= invalid;
+                out[end..end + invalid].copy_from_slice(&rest[..invalid]);
+                rest = &rest[invalid..];
+            }
+        }
+    }
+}
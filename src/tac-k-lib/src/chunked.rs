@@ -0,0 +1,129 @@
+use std::io::{Read, Result, Seek, SeekFrom};
+
+use crate::emit::Emitter;
+use crate::separator::Separator;
+
+/// Reverses an input that is only available piecewise, as chunks fed from the end of the input to
+/// its start. Records spanning the boundary between two chunks are stitched back together.
+///
+/// Each chunk is copied in front of the leading fragment left by the chunks fed before it, and
+/// only searched for separators together with the few bytes of the fragment a separator could
+/// straddle, so that a record spanning many chunks is neither copied nor searched more than once.
+pub(crate) struct ChunkedReverser<'a> {
+    separator: &'a Separator,
+    /// Offset of the first byte fed so far.
+    start: u64,
+    /// Holds the leading fragment of the chunks fed so far from `head` to its end, i.e. the start
+    /// of a record whose beginning lies in a chunk yet to be fed. Chunks are copied into the space
+    /// before `head`, which is made by moving the fragment to the end of a larger buffer.
+    buffer: Vec<u8>,
+    head: usize,
+}
+
+impl<'a> ChunkedReverser<'a> {
+    /// Create a reverser for an input of `len` bytes.
+    pub(crate) fn new(separator: &'a Separator, len: u64) -> Self {
+        ChunkedReverser {
+            separator,
+            start: len,
+            buffer: Vec::new(),
+            head: 0,
+        }
+    }
+
+    /// Feed the chunk immediately preceding all chunks fed so far, emitting every record that is now
+    /// complete.
+    pub(crate) fn feed(&mut self, chunk: &[u8], emitter: &mut Emitter) -> Result<()> {
+        self.start -= chunk.len() as u64;
+        let carry = self.buffer.len() - self.head;
+        if chunk.len() > self.head {
+            // Grow geometrically, so that a long record costs as many moves as a long `Vec` does.
+            let len = (carry + chunk.len()).max(self.buffer.len() * 2);
+            let mut buffer = Vec::new();
+            crate::try_reserve(&mut buffer, len)?;
+            buffer.resize(len - carry, 0);
+            buffer.extend_from_slice(&self.buffer[self.head..]);
+            self.buffer = buffer;
+            self.head = len - carry;
+        }
+        self.head -= chunk.len();
+        self.buffer[self.head..][..chunk.len()].copy_from_slice(chunk);
+
+        let bytes = &self.buffer[self.head..];
+        // The fragment holds no separator but the one ending it, or starting it with separators
+        // before records, which any separator found in the chunk cannot overlap unless the
+        // fragment is shorter than two of them. Regular expressions can match anything, though.
+        let searched = match self.separator.bytes() {
+            Some(separator) if carry >= 2 * separator.len() => {
+                chunk.len() + separator.len() - usize::from(!emitter.before())
+            }
+            _ => bytes.len(),
+        };
+        let leading = crate::emit_records(bytes, searched, self.start, self.separator, emitter)?;
+        // Keep the leading fragment at the end of the buffer, if records followed it.
+        let end = self.buffer.len();
+        self.buffer.copy_within(self.head..self.head + leading, end - leading);
+        self.head = end - leading;
+        Ok(())
+    }
+
+    /// Emit the leading fragment of the input once its first chunk has been fed.
+    pub(crate) fn finish(self, emitter: &mut Emitter) -> Result<()> {
+        debug_assert_eq!(self.start, 0);
+        if self.head != self.buffer.len() {
+            emitter.record(&self.buffer[self.head..], 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reverse the first `len` bytes of `file` by reading them backwards, in chunks of `chunk_size`
+/// bytes, instead of mapping them.
+///
+/// The file shrinking meanwhile makes this fail with [`ErrorKind::UnexpectedEof`] rather than kill
+/// the process with `SIGBUS` like an access to a truncated mapping would.
+///
+/// [`ErrorKind::UnexpectedEof`]: std::io::ErrorKind::UnexpectedEof
+pub(crate) fn reverse_backwards(
+    file: &mut (impl Read + Seek),
+    len: u64,
+    chunk_size: usize,
+    separator: &Separator,
+    emitter: &mut Emitter,
+) -> Result<()> {
+    let mut reverser = ChunkedReverser::new(separator, len);
+    let mut chunk = Vec::new();
+    if emitter.leads() {
+        // Read the start of the file up to its first separator, for the leading fragment.
+        file.seek(SeekFrom::Start(0))?;
+        let mut searched = 0;
+        while (chunk.len() as u64) < len && separator.find(&chunk[searched..]).is_none() {
+            // A fixed separator may straddle the chunks read, a regular expression match anything.
+            searched = match separator.bytes() {
+                Some(separator) => chunk.len().saturating_sub(separator.len() - 1),
+                None => 0,
+            };
+            let read = (len - chunk.len() as u64).min(chunk_size.max(1) as u64) as usize;
+            crate::try_reserve(&mut chunk, read)?;
+            let from = chunk.len();
+            chunk.resize(from + read, 0);
+            file.read_exact(&mut chunk[from..])?;
+        }
+        emitter.lead(&chunk)?;
+        chunk.clear();
+    }
+    let mut end = len;
+    while end != 0 {
+        let chunk_len = end.min(chunk_size.max(1) as u64) as usize;
+        end -= chunk_len as u64;
+        // The first chunk is the largest one.
+        if chunk.is_empty() {
+            crate::try_reserve(&mut chunk, chunk_len)?;
+        }
+        chunk.resize(chunk_len, 0);
+        file.seek(SeekFrom::Start(end))?;
+        file.read_exact(&mut chunk)?;
+        reverser.feed(&chunk, emitter)?;
+    }
+    reverser.finish(emitter)
+}
@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io::{Read, Result};
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::error::context;
+use crate::{Error, RevRecords, ReverseOptions, StdinReader};
+
+/// Size by which the buffer of an input of unknown length is grown.
+const READ_CHUNK_SIZE: usize = 64 * 1024; // 64 KiB
+
+/// The whole content of an input, memory-mapped if possible and read into memory otherwise, see
+/// [`ReverseOptions::open`].
+///
+/// It dereferences to the bytes of the input, so that its records can be processed one by one with
+/// [`rev_records`](Contents::rev_records) instead of being written into a writer.
+pub struct Contents {
+    bytes: Bytes,
+}
+
+enum Bytes {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Contents {
+    /// Iterate over the records separated by `separator`, last record first, see
+    /// [`rev_records`](crate::rev_records).
+    pub fn rev_records(&self, separator: u8) -> RevRecords<'_> {
+        crate::rev_records(self, separator)
+    }
+
+    /// Whether the input is memory-mapped rather than read into memory.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.bytes, Bytes::Mapped(_))
+    }
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.bytes {
+            Bytes::Mapped(mmap) => mmap,
+            Bytes::Read(buf) => buf,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Contents {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl std::fmt::Debug for Contents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Contents")
+            .field("len", &self.len())
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+pub(crate) fn open(path: Option<&Path>, options: &ReverseOptions) -> Result<Contents> {
+    let (mut reader, len): (Box<dyn Read>, _) = match path {
+        None => {
+            if options.mmap {
+                if let Ok(mmap) = crate::map_stdin() {
+                    return Ok(mapped(mmap));
+                }
+            }
+            let stdin = StdinReader::new(options.stdin_timeout);
+            (Box::new(context(stdin, read_error(path))?), None)
+        }
+        Some(path) => {
+            let open = |source| Error::Open {
+                path: path.to_path_buf(),
+                source,
+            };
+            #[cfg(feature = "zip")]
+            if let Some((archive_path, name)) = crate::archive::split_member(path) {
+                let mut archive = context(crate::archive::open(archive_path), open)?;
+                let mut member = context(crate::archive::member(&mut archive, archive_path, name), open)?;
+                let len = member.size() as usize;
+                return context(read(&mut member, Some(len)), read_error(Some(path))).map(read_into);
+            }
+            #[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+            if let Some(mut decoder) = context(crate::decompress::open(path), open)? {
+                return context(read(&mut decoder, None), read_error(Some(path))).map(read_into);
+            }
+            let file = context(File::open(path), open)?;
+            let metadata = context(file.metadata(), open)?;
+            if metadata.is_file() && metadata.len() < options.mmap_threshold {
+                (Box::new(file), Some(metadata.len() as usize))
+            } else if !options.mmap || crate::is_stream(&metadata) || crate::network_fs(&file).is_some() {
+                (Box::new(file), None)
+            } else {
+                let mmap = unsafe { Mmap::map(&file) };
+                let map_error = |source| Error::Map {
+                    path: Some(path.to_path_buf()),
+                    source,
+                };
+                return context(mmap, map_error).map(mapped);
+            }
+        }
+    };
+    context(read(&mut reader, len), read_error(path)).map(read_into)
+}
+
+/// Build the error of failing to read the input at `path`, `None` for `stdin`.
+fn read_error(path: Option<&Path>) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |source| Error::Read {
+        path: path.map(Path::to_path_buf),
+        source,
+    }
+}
+
+fn mapped(mmap: Mmap) -> Contents {
+    Contents {
+        bytes: Bytes::Mapped(mmap),
+    }
+}
+
+fn read_into(buf: Vec<u8>) -> Contents {
+    Contents {
+        bytes: Bytes::Read(buf),
+    }
+}
+
+/// Read the whole input from `reader`, whose length is `len` if known. The buffer is grown by hand
+/// so that running out of memory is an error instead of an abort.
+fn read(reader: &mut dyn Read, len: Option<usize>) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut total_read = 0;
+    loop {
+        let additional = match len {
+            Some(len) if total_read < len => len - total_read,
+            _ => READ_CHUNK_SIZE,
+        };
+        crate::try_reserve(&mut buf, additional)?;
+        buf.resize(total_read + additional, 0);
+        let bytes_read = crate::read_full(reader, &mut buf[total_read..])?;
+        total_read += bytes_read;
+        if bytes_read < additional {
+            buf.truncate(total_read);
+            return Ok(buf);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Result};
+use std::path::Path;
+
+/// Open the file at `path` through a decoder if its extension names a compression format this
+/// build supports: `.gz` with the `gzip` feature, `.zst` with `zstd` and `.xz` with `xz`.
+///
+/// Concatenated members, frames or streams are all decompressed, like `zcat` does.
+pub(crate) fn open(path: &Path) -> Result<Option<Box<dyn Read>>> {
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+        return Ok(None);
+    };
+    let decoder: Box<dyn Read> = match extension.to_ascii_lowercase().as_str() {
+        #[cfg(feature = "gzip")]
+        "gz" => Box::new(flate2::read::MultiGzDecoder::new(BufReader::new(File::open(path)?))),
+        #[cfg(feature = "zstd")]
+        "zst" => Box::new(ZstdDecoder::new(BufReader::new(File::open(path)?))),
+        #[cfg(feature = "xz")]
+        "xz" => Box::new(lzma_rust2::XzReader::new(BufReader::new(File::open(path)?), true)),
+        _ => return Ok(None),
+    };
+    Ok(Some(decoder))
+}
+
+/// Decoder of every frame of a zstd stream, as `ruzstd::StreamingDecoder` stops after the first.
+#[cfg(feature = "zstd")]
+struct ZstdDecoder<R> {
+    source: R,
+    decoder: ruzstd::FrameDecoder,
+    /// Whether a frame is being decoded, rather than its header to be read next.
+    in_frame: bool,
+}
+
+#[cfg(feature = "zstd")]
+impl<R: std::io::BufRead> ZstdDecoder<R> {
+    fn new(source: R) -> Self {
+        ZstdDecoder {
+            source,
+            decoder: ruzstd::FrameDecoder::new(),
+            in_frame: false,
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<R: std::io::BufRead> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        use ruzstd::frame::ReadFrameHeaderError;
+        use ruzstd::frame_decoder::FrameDecoderError;
+        use ruzstd::BlockDecodingStrategy;
+
+        let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        loop {
+            if self.in_frame {
+                while self.decoder.can_collect() < buf.len() && !self.decoder.is_finished() {
+                    let needed = buf.len() - self.decoder.can_collect();
+                    self.decoder
+                        .decode_blocks(&mut self.source, BlockDecodingStrategy::UptoBytes(needed))
+                        .map_err(invalid)?;
+                }
+                let bytes_read = self.decoder.read(buf)?;
+                if bytes_read != 0 || buf.is_empty() {
+                    return Ok(bytes_read);
+                }
+                self.in_frame = false;
+            }
+            if self.source.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            match self.decoder.init(&mut self.source) {
+                Ok(()) => self.in_frame = true,
+                // Skippable frames hold metadata rather than content.
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame { length, .. })) => {
+                    std::io::copy(&mut (&mut self.source).take(length.into()), &mut std::io::sink())?;
+                }
+                Err(e) => return Err(invalid(e)),
+            }
+        }
+    }
+}
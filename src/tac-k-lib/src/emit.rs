@@ -0,0 +1,951 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, IoSlice, Result, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::MutexGuard;
+
+use crate::advice;
+use crate::characters;
+use crate::format::{self, OutputFormat};
+use crate::options::{Fragment, ProgressHook, TrailingSeparator};
+use crate::{ReverseOptions, Stop};
+
+/// Minimum number of bytes processed between two calls of the progress hook, by default.
+pub(crate) const PROGRESS_INTERVAL: u64 = 16 * 1024 * 1024; // 16 MiB
+
+/// Number of records written with a single vectored write, see [`Emitter::records`].
+pub(crate) const BATCH_SIZE: usize = 64;
+
+/// Predicate deciding whether a record is written into an [`Output`].
+type Filter<'w> = dyn Fn(&[u8]) -> bool + 'w;
+
+/// One of several outputs a single reversal writes into, see [`ReverseOptions::run_fanout`].
+pub struct Output<'w> {
+    writer: &'w mut dyn Write,
+    filter: Option<Box<Filter<'w>>>,
+    /// Whether writing into `writer` has failed (or was asked to [`Stop`]).
+    pub(crate) failed: bool,
+}
+
+impl<'w> Output<'w> {
+    /// Write every record into `writer`.
+    pub fn new(writer: &'w mut dyn Write) -> Self {
+        Output {
+            writer,
+            filter: None,
+            failed: false,
+        }
+    }
+
+    /// Only write the records for which `filter` returns `true`. It is passed each record without
+    /// its trailing separator.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + 'w,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        let result = self.writer.flush();
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+/// Writes `record`, whose content is `content` and which starts at byte `start` of the input, into
+/// a sink in one of the output formats, prefixed with `label` unless the format is a structured
+/// one. Chosen once per input by [`record_writer`], rather than per record.
+type WriteRecord<'a> = fn(&mut Sink<'a>, label: &[u8], record: &[u8], content: &[u8], start: u64) -> Result<()>;
+
+/// The way records are written in `format`, their separator being held back if `strip`.
+fn record_writer<'a>(format: OutputFormat, strip: bool) -> WriteRecord<'a> {
+    match format {
+        OutputFormat::Raw if strip => Sink::write_stripped,
+        OutputFormat::Raw => Sink::write_raw,
+        OutputFormat::Json => Sink::write_json,
+        OutputFormat::Ndjson => Sink::write_ndjson,
+        OutputFormat::Framed => Sink::write_framed,
+        OutputFormat::Offsets => Sink::write_offsets,
+        OutputFormat::Base64 => Sink::write_base64,
+        OutputFormat::Escaped => Sink::write_escaped,
+    }
+}
+
+/// An output as written into by an [`Emitter`].
+struct Sink<'a> {
+    writer: Counted<'a>,
+    filter: Option<&'a Filter<'a>>,
+    /// Set once writing into `writer` fails, to tell such failures apart from reading failures.
+    failed: Option<&'a mut bool>,
+    /// Whether any record has been written yet.
+    started: bool,
+    /// The separator of the last record written, held back until another one is, see
+    /// [`TrailingSeparator::Strip`].
+    held: Vec<u8>,
+    /// The last records `filter` rejected, written before the next one it accepts, see
+    /// [`ReverseOptions::context`].
+    preceding: VecDeque<Context>,
+    /// Number of records left to write after the last one `filter` accepted.
+    following: u64,
+}
+
+/// A record held back to be written as context, with its label, the range of its content and its
+/// offset in the input, see [`ReverseOptions::context`].
+struct Context {
+    label: Vec<u8>,
+    record: Vec<u8>,
+    content: Range<usize>,
+    start: u64,
+}
+
+/// A writer counting the bytes written into it, see [`Emitter::written`].
+struct Counted<'a> {
+    writer: &'a mut dyn Write,
+    written: u64,
+    /// The encoding UTF-8 written into this writer is encoded into, see
+    /// [`ReverseOptions::encoding`].
+    #[cfg(feature = "encoding")]
+    encoding: Option<&'static encoding_rs::Encoding>,
+    #[cfg(feature = "encoding")]
+    encoded: Vec<u8>,
+}
+
+impl<'a> Counted<'a> {
+    fn new(writer: &'a mut dyn Write) -> Self {
+        Counted {
+            writer,
+            written: 0,
+            #[cfg(feature = "encoding")]
+            encoding: None,
+            #[cfg(feature = "encoding")]
+            encoded: Vec::new(),
+        }
+    }
+
+    /// Write all of `buf` encoded into `encoding`.
+    #[cfg(feature = "encoding")]
+    fn write_encoded(&mut self, encoding: &'static encoding_rs::Encoding, buf: &[u8]) -> Result<usize> {
+        self.encoded.clear();
+        crate::encoding::encode(encoding, buf, &mut self.encoded);
+        self.writer.write_all(&self.encoded)?;
+        self.written += self.encoded.len() as u64;
+        Ok(buf.len())
+    }
+}
+
+impl Write for Counted<'_> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.encoding {
+            return self.write_encoded(encoding, buf);
+        }
+        let len = self.writer.write(buf)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.encoding {
+            let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &**buf);
+            return self.write_encoded(encoding, buf);
+        }
+        let len = self.writer.write_vectored(bufs)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Receives the records of an input, last record first, and writes them into the outputs.
+pub(crate) struct Emitter<'a> {
+    sinks: Vec<Sink<'a>>,
+    /// The output if records are written as they are into a single unfiltered one, the common
+    /// case, which then bypasses `sinks` entirely: fanning out into one output costs nothing, and
+    /// unless other options need records one by one, they go straight into it, see
+    /// [`emit_direct`](Self::emit_direct).
+    plain: Option<Sink<'a>>,
+    options: &'a ReverseOptions,
+    /// Pattern (and whether to emit the record containing it) that must be seen before any record
+    /// is emitted, see [`ReverseOptions::from_match`].
+    waiting_for: Option<(&'a [u8], bool)>,
+    /// The last records skipped while `waiting_for` a pattern, with their offset and line number,
+    /// written before the record matching it, see [`ReverseOptions::context`].
+    preceding: VecDeque<(Vec<u8>, u64, u64)>,
+    /// Number of records left to write once past the record matching
+    /// [`ReverseOptions::until_match`], see [`ReverseOptions::context`].
+    following: Option<u64>,
+    /// Whether the next record is the first one and might have to be moved, see
+    /// [`ReverseOptions::fragment`].
+    at_fragment: bool,
+    /// The unterminated record, its offset and its line number, if any, held back to be written
+    /// last.
+    fragment: Option<(Vec<u8>, u64, u64)>,
+    /// Whether the record at the start of the input that no separator precedes is to be skipped
+    /// when it is reached, having been written first or being dropped, see
+    /// [`ReverseOptions::leading_fragment`].
+    skip_leading: bool,
+    /// The separator appended to the unterminated record, see [`TrailingSeparator::Ensure`].
+    terminator: Option<&'a [u8]>,
+    /// Whether the record being emitted is the unterminated one.
+    terminate: bool,
+    /// Number of records left to emit, see [`ReverseOptions::max_records`].
+    remaining: Option<u64>,
+    /// Number of records left to skip, see [`ReverseOptions::skip_records`].
+    to_skip: u64,
+    /// Number of records written so far.
+    pub(crate) records: u64,
+    /// What every record is prefixed with, see [`ReverseOptions::label`].
+    label: Option<Vec<u8>>,
+    /// Line number of the next record, counted from the start of the input, see
+    /// [`ReverseOptions::number`].
+    number: Option<u64>,
+    /// Line number of the record being emitted.
+    line: u64,
+    /// The label and line number of the record being written, if numbered.
+    prefix: Vec<u8>,
+    /// The record being written with its characters reversed, see [`ReverseOptions::characters`].
+    reversed: Vec<u8>,
+    /// Length of the whole input.
+    total: u64,
+    progress: Option<Progress<'a>>,
+    prefetch: Option<Prefetch>,
+    /// How records are written into `sinks`, see [`ReverseOptions::output_format`].
+    write_record: WriteRecord<'a>,
+}
+
+/// What to do with a record, see [`Emitter::admit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admit {
+    Skip,
+    Write,
+    /// Write the record, then stop.
+    WriteLast,
+}
+
+/// Reads a memory-mapped input ahead of the records being emitted, see [`MmapAdvice::Backwards`].
+///
+/// [`MmapAdvice::Backwards`]: crate::MmapAdvice::Backwards
+struct Prefetch {
+    /// Address of the start of the mapping.
+    start: usize,
+    /// Number of bytes read ahead at once.
+    window: usize,
+    /// Address below which the next window is due.
+    next: usize,
+}
+
+struct Progress<'a> {
+    hook: MutexGuard<'a, ProgressHook>,
+    /// Number of processed bytes at which the hook is next due.
+    next: u64,
+    /// Number of processed bytes last reported.
+    reported: u64,
+}
+
+impl<'a> Emitter<'a> {
+    pub(crate) fn new(output: &'a mut dyn Write, total: u64, options: &'a ReverseOptions) -> Self {
+        let sink = Sink {
+            writer: Counted::new(output),
+            filter: None,
+            failed: None,
+            started: false,
+            held: Vec::new(),
+            preceding: VecDeque::new(),
+            following: 0,
+        };
+        Self::with_sinks(vec![sink], total, options)
+    }
+
+    pub(crate) fn fanout(outputs: &'a mut [Output], total: u64, options: &'a ReverseOptions) -> Self {
+        let sinks = outputs
+            .iter_mut()
+            .map(|output| Sink {
+                writer: Counted::new(&mut *output.writer),
+                filter: output.filter.as_deref(),
+                failed: Some(&mut output.failed),
+                started: false,
+                held: Vec::new(),
+                preceding: VecDeque::new(),
+                following: 0,
+            })
+            .collect();
+        Self::with_sinks(sinks, total, options)
+    }
+
+    fn with_sinks(mut sinks: Vec<Sink<'a>>, total: u64, options: &'a ReverseOptions) -> Self {
+        #[cfg(feature = "encoding")]
+        for sink in &mut sinks {
+            sink.writer.encoding = options.encoding.filter(|_| !options.utf8_output);
+        }
+        let plain = match &sinks[..] {
+            [sink]
+                if sink.filter.is_none()
+                    && options.output_format == OutputFormat::Raw
+                    && options.characters.is_none()
+                    && !options.number
+                    && options.context == (0, 0)
+                    && options.trailing_separator != TrailingSeparator::Strip =>
+            {
+                sinks.pop()
+            }
+            _ => None,
+        };
+        let progress = options.progress_hook.as_ref().map(|hook| Progress {
+            // A panicking hook has already failed a previous reversal; keep reporting to it anyway.
+            hook: hook.lock().unwrap_or_else(|e| e.into_inner()),
+            next: options.progress_interval,
+            reported: 0,
+        });
+        let terminator = match options.trailing_separator {
+            TrailingSeparator::Ensure if !options.before => options.separator.bytes(),
+            _ => None,
+        };
+        Emitter {
+            plain,
+            sinks,
+            options,
+            waiting_for: options
+                .from_match
+                .as_ref()
+                .map(|(pattern, inclusive)| (&pattern[..], *inclusive)),
+            preceding: VecDeque::new(),
+            following: None,
+            // With separators before records, the last record of the input is always complete.
+            at_fragment: (options.fragment != Fragment::First || terminator.is_some()) && !options.before,
+            fragment: None,
+            skip_leading: options.before && options.leading_fragment != Fragment::Last,
+            terminator,
+            terminate: false,
+            remaining: options.max_records,
+            to_skip: options.skip_records,
+            records: 0,
+            label: None,
+            number: None,
+            line: 0,
+            prefix: Vec::new(),
+            reversed: Vec::new(),
+            total,
+            progress,
+            prefetch: None,
+            write_record: record_writer(
+                options.output_format,
+                options.trailing_separator == TrailingSeparator::Strip && !options.before,
+            ),
+        }
+    }
+
+    /// Read `mapped`, the memory-mapped input whose end has been read ahead already, ahead of the
+    /// records as they are emitted, `window` bytes at a time.
+    pub(crate) fn prefetch(mut self, mapped: &[u8], window: Option<usize>) -> Self {
+        self.prefetch = window.filter(|&window| window != 0).map(|window| {
+            let start = mapped.as_ptr() as usize;
+            let end = start + mapped.len();
+            Prefetch {
+                start,
+                window,
+                next: end.saturating_sub(window / 2).max(start),
+            }
+        });
+        self
+    }
+
+    /// Prefix records with their line number, `records` being the number of records of the input,
+    /// if the options ask for it.
+    pub(crate) fn numbered(mut self, records: u64) -> Self {
+        self.number = self.options.number.then_some(records);
+        self
+    }
+
+    /// Number of bytes written into all of the outputs so far.
+    pub(crate) fn written(&self) -> u64 {
+        self.plain
+            .iter()
+            .chain(&self.sinks)
+            .map(|sink| sink.writer.written)
+            .sum()
+    }
+
+    /// Whether records can be written straight into the only output as they are found, see
+    /// [`emit_direct`](Self::emit_direct): that is the case unless some option needs records to be
+    /// filtered, formatted, counted towards a limit or reported.
+    pub(crate) fn is_direct(&self) -> bool {
+        #[cfg(feature = "encoding")]
+        let encoded = self.plain.as_ref().is_some_and(|sink| sink.writer.encoding.is_some());
+        #[cfg(not(feature = "encoding"))]
+        let encoded = false;
+        self.plain.is_some()
+            && !encoded
+            && !self.options.before
+            && !self.at_fragment
+            && self.waiting_for.is_none()
+            && self.to_skip == 0
+            && self.remaining.is_none()
+            && self.options.until_match.is_none()
+            && self.label.as_deref().is_none_or(<[u8]>::is_empty)
+            && self.progress.is_none()
+            && self.options.cancel.is_none()
+            && !self.options.line_buffered
+    }
+
+    /// Number of records to gather before passing them to [`records`](Self::records).
+    pub(crate) fn batch_size(&self) -> usize {
+        match self.options.line_buffered {
+            true => 1,
+            false => BATCH_SIZE,
+        }
+    }
+
+    /// Write every record of `bytes` that ends with `separator` within its first `searched` bytes
+    /// into the output as it is found, last record first, and return the length of the leading
+    /// fragment, like [`emit_records`](crate::emit_records) does. Must only be called if
+    /// [`is_direct`](Self::is_direct).
+    pub(crate) fn emit_direct(&mut self, bytes: &[u8], searched: usize, separator: u8) -> Result<usize> {
+        let Some(sink) = self.plain.as_mut() else {
+            unreachable!("only plain outputs are written into directly");
+        };
+        let writer = &mut *sink.writer.writer;
+        let prefetch = &mut self.prefetch;
+        let mut last_printed = bytes.len();
+        let mut records = 0;
+        let mut written = 0;
+        let result = crate::search::search_auto(&bytes[..searched], separator, &mut |offset| {
+            // A separator right at the end ends the last record rather than starting an empty one.
+            if offset != last_printed {
+                let record = &bytes[offset..last_printed];
+                if let Some(prefetch) = prefetch.as_mut() {
+                    prefetch.read_ahead(record);
+                }
+                writer.write_all(record)?;
+                records += 1;
+                written += record.len() as u64;
+                last_printed = offset;
+            }
+            Ok(())
+        });
+        self.records += records;
+        sink.writer.written += written;
+        sink.check(result)?;
+        Ok(last_printed)
+    }
+
+    /// Whether the input is read ahead of the records, see [`prefetch`](Self::prefetch).
+    pub(crate) fn prefetching(&self) -> bool {
+        self.prefetch.is_some()
+    }
+
+    /// Whether separators are attached to the start of records, see [`ReverseOptions::before`].
+    pub(crate) fn before(&self) -> bool {
+        self.options.before
+    }
+
+    /// Prefix every record with `path`, if the options ask for it.
+    pub(crate) fn label(mut self, path: Option<&Path>) -> Self {
+        self.label = self.options.label.as_ref().map(|separator| {
+            let name = path.map_or("(standard input)".into(), |path| path.to_string_lossy());
+            [name.as_bytes(), separator].concat()
+        });
+        self
+    }
+
+    /// Emit `record`, which starts at byte `start` of the input.
+    #[inline]
+    pub(crate) fn record(&mut self, record: &[u8], start: u64) -> Result<()> {
+        if let Some(number) = self.number.as_mut() {
+            // The empty "record" following a trailing separator is not a record of its own.
+            self.line = *number;
+            *number = number.saturating_sub(u64::from(!record.is_empty()));
+        }
+        self.read_ahead(record);
+        let admit = self.admit(record, start)?;
+        if admit != Admit::Skip {
+            self.write(record, start)?;
+        }
+        self.terminate = false;
+        if admit == Admit::WriteLast {
+            self.end()?;
+            return Err(Stop::error());
+        }
+        self.report(start);
+        Ok(())
+    }
+
+    /// Emit `records`, each with the byte of the input it starts at, like [`record`](Self::record)
+    /// would one by one. Records written as they are into a plain output are gathered into a single
+    /// vectored write, so that an unbuffered output costs one system call per batch rather than
+    /// one or two per record. The outputs are flushed afterwards if
+    /// [`line_buffered`](ReverseOptions::line_buffered).
+    pub(crate) fn records(&mut self, records: &[(&[u8], u64)]) -> Result<()> {
+        let result = match self.plain {
+            Some(_) => self.plain_records(records),
+            None => records
+                .iter()
+                .try_for_each(|&(record, start)| self.record(record, start)),
+        };
+        match self.options.line_buffered {
+            true => result.and_then(|()| self.flush()),
+            false => result,
+        }
+    }
+
+    /// Flush every output that has not failed.
+    fn flush(&mut self) -> Result<()> {
+        for sink in self.plain.iter_mut().chain(&mut self.sinks) {
+            if !sink.failed.as_deref().is_some_and(|&failed| failed) {
+                let result = sink.writer.flush();
+                sink.check(result)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// [`records`](Self::records) for a plain output.
+    fn plain_records(&mut self, records: &[(&[u8], u64)]) -> Result<()> {
+        let label = self.label.take();
+        let mut parts = Vec::with_capacity(records.len() * 2);
+        let mut result = Ok(());
+        for &(record, start) in records {
+            self.read_ahead(record);
+            let admit = match self.admit(record, start) {
+                Ok(admit) => admit,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            // The empty "record" following a trailing separator is not a record of its own.
+            if admit != Admit::Skip && !record.is_empty() {
+                self.records += 1;
+                parts.extend(label.as_deref().filter(|label| !label.is_empty()).map(IoSlice::new));
+                parts.push(IoSlice::new(record));
+                parts.extend(self.terminator.filter(|_| self.terminate).map(IoSlice::new));
+            }
+            self.terminate = false;
+            if admit == Admit::WriteLast {
+                result = Err(Stop::error());
+                break;
+            }
+            self.report(start);
+        }
+        let written = match &mut self.plain {
+            Some(sink) => {
+                let written = write_all_vectored(&mut sink.writer, &mut parts);
+                sink.check(written)
+            }
+            None => Ok(()),
+        };
+        drop(parts);
+        self.label = label;
+        written.and(result)
+    }
+
+    /// Whether the leading fragment of the input is to be written first, which takes passing the
+    /// start of the input to [`lead`](Self::lead) before any record.
+    pub(crate) fn leads(&self) -> bool {
+        self.skip_leading && self.options.leading_fragment == Fragment::First
+    }
+
+    /// Write the leading fragment of the input first if the options ask for it, see
+    /// [`ReverseOptions::leading_fragment`]. `head` is the start of the input, up to its first
+    /// separator at least, or all of it.
+    pub(crate) fn lead(&mut self, head: &[u8]) -> Result<()> {
+        if !self.leads() {
+            return Ok(());
+        }
+        let fragment = &head[..self
+            .options
+            .separator
+            .find(head)
+            .map_or(head.len(), |found| found.start)];
+        if fragment.is_empty() {
+            return Ok(());
+        }
+        // Not skipped this once, nor reported as progress, since it is reached again at the end.
+        self.skip_leading = false;
+        self.line = 1;
+        let admit = self.admit(fragment, 0);
+        self.skip_leading = true;
+        let admit = admit?;
+        if admit != Admit::Skip {
+            self.write(fragment, 0)?;
+        }
+        if admit == Admit::WriteLast {
+            self.end()?;
+            return Err(Stop::error());
+        }
+        Ok(())
+    }
+
+    /// Decide whether `record`, which starts at byte `start` of the input, is to be written, or
+    /// fail with [`Stop`] if no more records are.
+    #[inline]
+    fn admit(&mut self, record: &[u8], start: u64) -> Result<Admit> {
+        self.options.check_cancelled()?;
+        if self.skip_leading && start == 0 && !record.is_empty() && self.options.separator.strip(record, true).is_none()
+        {
+            return Ok(Admit::Skip);
+        }
+        if self.at_fragment {
+            self.at_fragment = false;
+            if !record.is_empty() && self.options.separator.strip(record, false).is_none() {
+                match self.options.fragment {
+                    // Only checked for if the record is to be terminated.
+                    Fragment::First => self.terminate = true,
+                    Fragment::Last => {
+                        self.fragment = Some((record.to_vec(), start, self.line));
+                        return Ok(Admit::Skip);
+                    }
+                    Fragment::Drop => return Ok(Admit::Skip),
+                }
+            }
+        }
+
+        if let Some((pattern, inclusive)) = self.waiting_for {
+            if !contains(self.content(record), pattern) {
+                let preceding = self.options.context.0;
+                if preceding != 0 && !record.is_empty() {
+                    if self.preceding.len() as u64 == preceding {
+                        self.preceding.pop_front();
+                    }
+                    self.preceding.push_back((record.to_vec(), start, self.line));
+                }
+                return Ok(Admit::Skip);
+            }
+            self.waiting_for = None;
+            self.write_preceding()?;
+            if !inclusive {
+                return Ok(Admit::Skip);
+            }
+        }
+
+        if self.to_skip != 0 && !record.is_empty() {
+            self.to_skip -= 1;
+            return Ok(Admit::Skip);
+        }
+
+        if let Some(remaining) = self.remaining.as_mut() {
+            if *remaining == 0 {
+                self.end()?;
+                return Err(Stop::error());
+            }
+            if !record.is_empty() {
+                *remaining -= 1;
+            }
+        }
+
+        if let Some(following) = self.following.as_mut() {
+            *following -= 1;
+            return Ok(match *following {
+                0 => Admit::WriteLast,
+                _ => Admit::Write,
+            });
+        }
+
+        if let Some((pattern, inclusive)) = &self.options.until_match {
+            if contains(self.content(record), pattern) {
+                let following = self.options.context.1;
+                if following != 0 {
+                    self.following = Some(following);
+                    return Ok(if *inclusive { Admit::Write } else { Admit::Skip });
+                }
+                if *inclusive {
+                    return Ok(Admit::WriteLast);
+                }
+                self.end()?;
+                return Err(Stop::error());
+            }
+        }
+
+        Ok(Admit::Write)
+    }
+
+    /// Write the records skipped before the one matching [`ReverseOptions::from_match`] that are
+    /// to precede it, see [`ReverseOptions::context`].
+    fn write_preceding(&mut self) -> Result<()> {
+        let line = self.line;
+        let terminate = std::mem::take(&mut self.terminate);
+        for (record, start, line) in std::mem::take(&mut self.preceding) {
+            self.line = line;
+            self.write(&record, start)?;
+        }
+        self.line = line;
+        self.terminate = terminate;
+        Ok(())
+    }
+
+    /// Read the window preceding `record` ahead if it is due, see [`prefetch`](Self::prefetch).
+    #[inline]
+    fn read_ahead(&mut self, record: &[u8]) {
+        if let Some(prefetch) = self.prefetch.as_mut() {
+            prefetch.read_ahead(record);
+        }
+    }
+
+    /// Call the progress hook if it is due, `start` being the offset of the last record emitted.
+    #[inline]
+    fn report(&mut self, start: u64) {
+        if let Some(progress) = self.progress.as_mut() {
+            let done = self.total - start;
+            if done >= progress.next {
+                (progress.hook)(done, self.total);
+                progress.next = done.saturating_add(self.options.progress_interval);
+                progress.reported = done;
+            }
+        }
+    }
+
+    /// Write `record`, which starts at byte `start` of the input, into every output it passes the
+    /// filter of.
+    #[inline]
+    fn write(&mut self, record: &[u8], start: u64) -> Result<()> {
+        // The empty "record" following a trailing separator is not a record of its own.
+        self.records += u64::from(!record.is_empty());
+        let label = self.label.as_deref().unwrap_or_default();
+        let terminator = self.terminator.filter(|_| self.terminate).unwrap_or_default();
+        if let Some(sink) = &mut self.plain {
+            let result = match record.is_empty() {
+                false => sink
+                    .writer
+                    .write_all(label)
+                    .and_then(|()| sink.writer.write_all(record))
+                    .and_then(|()| sink.writer.write_all(terminator)),
+                true => Ok(()),
+            };
+            return sink.check(result);
+        }
+        if record.is_empty() {
+            return Ok(());
+        }
+        let label = match self.number {
+            Some(_) => {
+                // Right-aligned like `cat -n` does.
+                self.prefix.clear();
+                self.prefix.extend_from_slice(label);
+                let _ = write!(self.prefix, "{:>6}\t", self.line);
+                &self.prefix[..]
+            }
+            None => label,
+        };
+        let terminated;
+        let mut record = record;
+        if !terminator.is_empty() {
+            terminated = [record, terminator].concat();
+            record = &terminated;
+        }
+        let mut content = self.content(record);
+        if let Some(characters) = self.options.characters {
+            // Only the content is reversed, the separator stays before or after it.
+            let separator = record.len() - content.len();
+            self.reversed.clear();
+            if self.options.before {
+                self.reversed.extend_from_slice(&record[..separator]);
+            }
+            characters::reverse_into(content, characters, &mut self.reversed);
+            if !self.options.before {
+                self.reversed.extend_from_slice(&record[content.len()..]);
+            }
+            record = &self.reversed;
+            content = match self.options.before {
+                true => &record[separator..],
+                false => &record[..content.len()],
+            };
+        }
+        let (preceding, following) = self.options.context;
+        for sink in &mut self.sinks {
+            if let Some(filter) = sink.filter {
+                if filter(content) {
+                    sink.following = following;
+                    let result = sink.write_preceding(self.write_record);
+                    sink.check(result)?;
+                } else if sink.following != 0 {
+                    sink.following -= 1;
+                } else {
+                    if preceding != 0 {
+                        if sink.preceding.len() as u64 == preceding {
+                            sink.preceding.pop_front();
+                        }
+                        let offset = content.as_ptr() as usize - record.as_ptr() as usize;
+                        sink.preceding.push_back(Context {
+                            label: label.to_vec(),
+                            record: record.to_vec(),
+                            content: offset..offset + content.len(),
+                            start,
+                        });
+                    }
+                    continue;
+                }
+            }
+            let result = (self.write_record)(sink, label, record, content, start);
+            sink.check(result)?;
+        }
+        Ok(())
+    }
+
+    /// Write whatever the output format needs after the last record.
+    fn end(&mut self) -> Result<()> {
+        for sink in &mut self.sinks {
+            let result = sink.end(self.options.output_format);
+            sink.check(result)?;
+        }
+        Ok(())
+    }
+
+    /// Strip the trailing separator off `record`, if any.
+    #[inline]
+    fn content<'r>(&self, record: &'r [u8]) -> &'r [u8] {
+        self.options
+            .separator
+            .strip(record, self.options.before)
+            .unwrap_or(record)
+    }
+
+    /// Signal that all records have been emitted.
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        if let Some((fragment, start, line)) = self.fragment.take() {
+            self.number = self.number.and(Some(line));
+            self.terminate = true;
+            self.record(&fragment, start)?;
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            if progress.reported != self.total || self.total == 0 {
+                (progress.hook)(self.total, self.total);
+            }
+        }
+        self.end()
+    }
+}
+
+impl Prefetch {
+    /// Read the window preceding `record` ahead if it is due.
+    #[inline]
+    fn read_ahead(&mut self, record: &[u8]) {
+        // E.g. a fragment held back is not part of the mapping.
+        let address = record.as_ptr() as usize;
+        if address < self.start || address >= self.next {
+            return;
+        }
+        let from = address.saturating_sub(self.window).max(self.start);
+        advice::will_need_at(from, address - from);
+        // Overlap the windows, so that the next one is due before this one has been scanned.
+        self.next = if from == self.start {
+            from
+        } else {
+            from + self.window / 2
+        };
+    }
+}
+
+impl<'a> Sink<'a> {
+    /// Pass on `result` of writing into this sink, noting whether it failed.
+    #[inline]
+    fn check(&mut self, result: Result<()>) -> Result<()> {
+        if let (Err(_), Some(failed)) = (&result, self.failed.as_mut()) {
+            **failed = true;
+        }
+        crate::error::context(result, |source| crate::Error::Write { source })
+    }
+
+    /// Write the records held back to precede the one about to be written with `write_record`, see
+    /// [`ReverseOptions::context`].
+    fn write_preceding(&mut self, write_record: WriteRecord<'a>) -> Result<()> {
+        while let Some(context) = self.preceding.pop_front() {
+            write_record(
+                self,
+                &context.label,
+                &context.record,
+                &context.record[context.content.clone()],
+                context.start,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write `record` as it is, prefixed with `label`.
+    fn write_raw(&mut self, label: &[u8], record: &[u8], _: &[u8], _: u64) -> Result<()> {
+        self.writer.write_all(label)?;
+        self.writer.write_all(record)
+    }
+
+    /// Write `record` prefixed with `label`, holding its separator back until the next record is
+    /// written, see [`TrailingSeparator::Strip`].
+    fn write_stripped(&mut self, label: &[u8], record: &[u8], content: &[u8], _: u64) -> Result<()> {
+        if !self.held.is_empty() {
+            self.writer.write_all(&self.held)?;
+            self.held.clear();
+        }
+        self.writer.write_all(label)?;
+        self.writer.write_all(content)?;
+        self.held.extend_from_slice(&record[content.len()..]);
+        Ok(())
+    }
+
+    fn write_json(&mut self, _: &[u8], _: &[u8], content: &[u8], start: u64) -> Result<()> {
+        self.writer.write_all(if self.started { b",\n" } else { b"[\n" })?;
+        self.started = true;
+        format::write_json_record(&mut self.writer, content, start)
+    }
+
+    fn write_ndjson(&mut self, _: &[u8], _: &[u8], content: &[u8], start: u64) -> Result<()> {
+        format::write_json_record(&mut self.writer, content, start)?;
+        self.writer.write_all(b"\n")
+    }
+
+    fn write_framed(&mut self, _: &[u8], _: &[u8], content: &[u8], _: u64) -> Result<()> {
+        format::write_framed_record(&mut self.writer, content)
+    }
+
+    fn write_offsets(&mut self, _: &[u8], record: &[u8], _: &[u8], start: u64) -> Result<()> {
+        writeln!(&mut self.writer, "{start}\t{}", record.len())
+    }
+
+    /// Write `content` base64-encoded, prefixed with `label`, on a line of its own.
+    fn write_base64(&mut self, label: &[u8], _: &[u8], content: &[u8], _: u64) -> Result<()> {
+        self.writer.write_all(label)?;
+        format::write_base64_record(&mut self.writer, content)?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Write `content` with C-style escapes, prefixed with `label`, on a line of its own.
+    fn write_escaped(&mut self, label: &[u8], _: &[u8], content: &[u8], _: u64) -> Result<()> {
+        self.writer.write_all(label)?;
+        format::write_escaped_record(&mut self.writer, content)?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Write whatever `format` needs after the last record.
+    fn end(&mut self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Json if self.started => self.writer.write_all(b"\n]\n"),
+            OutputFormat::Json => self.writer.write_all(b"[]\n"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Write all of `parts` into `writer`, with as few vectored writes as it accepts. `parts` must not
+/// contain empty slices, and is left advanced past whatever was written.
+fn write_all_vectored(writer: &mut dyn Write, mut parts: &mut [IoSlice]) -> Result<()> {
+    while !parts.is_empty() {
+        match writer.write_vectored(parts) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            // Whatever was written may end in the middle of a part.
+            Ok(written) => IoSlice::advance_slices(&mut parts, written),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `needle` occurs in `haystack`.
+#[inline]
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    crate::rfind(haystack, needle).is_some()
+}
@@ -0,0 +1,99 @@
+use std::cell::Cell;
+use std::io::{Read, Result};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+/// Number of bytes read from the source at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reader of UTF-8 decoded from `source` in some encoding, see [`ReverseOptions::encoding`].
+///
+/// [`ReverseOptions::encoding`]: crate::ReverseOptions::encoding
+pub(crate) struct Decoder<'a, R> {
+    source: R,
+    decoder: encoding_rs::Decoder,
+    /// Bytes read from `source`.
+    input: Vec<u8>,
+    /// UTF-8 decoded from `input`, and how much of it has been read.
+    output: Vec<u8>,
+    read: usize,
+    /// Whether `source` has been read until its end.
+    done: bool,
+    /// Where the encoding the input turns out to be in is kept, once a byte order mark has been
+    /// looked for.
+    detected: &'a Cell<Option<&'static Encoding>>,
+}
+
+impl<'a, R: Read> Decoder<'a, R> {
+    pub(crate) fn new(source: R, encoding: &'static Encoding, detected: &'a Cell<Option<&'static Encoding>>) -> Self {
+        Decoder {
+            source,
+            // A byte order mark overrides `encoding`, and is left out of the output.
+            decoder: encoding.new_decoder(),
+            input: vec![0; CHUNK_SIZE],
+            output: Vec::new(),
+            read: 0,
+            done: false,
+            detected,
+        }
+    }
+}
+
+impl<R: Read> Read for Decoder<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Decoding may produce nothing, e.g. out of a byte order mark or half a code unit.
+        while self.read == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+            let len = self.source.read(&mut self.input)?;
+            self.done = len == 0;
+            let max_len = self
+                .decoder
+                .max_utf8_buffer_length(len)
+                .ok_or_else(|| crate::out_of_memory(len, "the decoded input would overflow"))?;
+            self.output.resize(max_len, 0);
+            let (_, _, written, _) = self
+                .decoder
+                .decode_to_utf8(&self.input[..len], &mut self.output, self.done);
+            self.output.truncate(written);
+            self.read = 0;
+            self.detected.set(self.decoder.encoding().into());
+        }
+        let len = buf.len().min(self.output.len() - self.read);
+        buf[..len].copy_from_slice(&self.output[self.read..self.read + len]);
+        self.read += len;
+        Ok(len)
+    }
+}
+
+/// Decode the whole of `input` if the options ask for it, along with the options to reverse it
+/// with, see [`ReverseOptions::decoded_from`](crate::ReverseOptions::decoded_from).
+#[cfg(feature = "async")]
+pub(crate) fn decode(
+    input: Vec<u8>,
+    options: &crate::ReverseOptions,
+) -> Result<(Vec<u8>, std::borrow::Cow<'_, crate::ReverseOptions>)> {
+    let Some(encoding) = options.encoding else {
+        return Ok((input, std::borrow::Cow::Borrowed(options)));
+    };
+    let detected = Cell::new(None);
+    let mut decoded = vec![];
+    Decoder::new(&input[..], encoding, &detected).read_to_end(&mut decoded)?;
+    Ok((decoded, options.decoded_from(detected.get())))
+}
+
+/// Append `utf8`, decoded by a [`Decoder`], to `output` in `encoding`. Invalid UTF-8, e.g. out of
+/// characters reversed as bytes, is replaced with U+FFFD, and characters `encoding` cannot
+/// represent with numeric character references, as `encoding_rs` does.
+pub(crate) fn encode(encoding: &'static Encoding, utf8: &[u8], output: &mut Vec<u8>) {
+    let text = String::from_utf8_lossy(utf8);
+    // `encoding_rs` only encodes into UTF-16 as UTF-8, like browsers do.
+    if encoding == UTF_16LE {
+        output.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    } else if encoding == UTF_16BE {
+        output.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    } else {
+        output.extend_from_slice(&encoding.encode(&text).0);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What failed while reversing an input, returned by the functions of the library.
+///
+/// An error raised while opening, reading, memory-mapping or spilling the input, or writing the
+/// output, is one of the first variants with the path involved, and anything else, e.g. invalid
+/// options or a [`Cancelled`](crate::Cancelled) reversal, is [`Error::Other`]. The underlying I/O
+/// error is the [`source`](std::error::Error::source) of the first ones, see [`Error::io`], and
+/// [`Error::kind`] is its kind. An `Error` converts into an [`io::Error`] carrying it, e.g. with `?`
+/// in a function returning [`io::Result`], which [`Error::of`] finds it back in.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{reverse_file, Error};
+///
+/// let e = reverse_file(&mut vec![], Some("missing.txt"), b'\n').unwrap_err();
+///
+/// assert!(matches!(e, Error::Open { .. }));
+/// assert_eq!(e.path(), Some("missing.txt".as_ref()));
+/// assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+/// assert_eq!(e.to_string(), "failed to open");
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Opening the input at `path`, or finding out what it is, failed.
+    Open { path: PathBuf, source: io::Error },
+    /// Reading the input failed, `path` being `None` for `stdin` and readers.
+    Read { path: Option<PathBuf>, source: io::Error },
+    /// Memory-mapping the input failed, `path` being `None` for `stdin`.
+    Map { path: Option<PathBuf>, source: io::Error },
+    /// Creating, writing or reading back the temporary file the input is spilled to failed, `path`
+    /// being that of the file unless it is anonymous.
+    Spill { path: Option<PathBuf>, source: io::Error },
+    /// Writing into the writer failed.
+    Write { source: io::Error },
+    /// Anything else, which is displayed as the I/O error itself.
+    Other(io::Error),
+}
+
+impl Error {
+    /// The error carried by `error`, an [`io::Error`] converted from one, if any.
+    pub fn of(error: &io::Error) -> Option<&Error> {
+        error.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+
+    /// The path involved, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Open { path, .. } => Some(path),
+            Error::Read { path, .. } | Error::Map { path, .. } | Error::Spill { path, .. } => path.as_deref(),
+            Error::Write { .. } | Error::Other(_) => None,
+        }
+    }
+
+    /// The underlying I/O error.
+    pub fn io(&self) -> &io::Error {
+        match self {
+            Error::Open { source, .. }
+            | Error::Read { source, .. }
+            | Error::Map { source, .. }
+            | Error::Spill { source, .. }
+            | Error::Write { source }
+            | Error::Other(source) => source,
+        }
+    }
+
+    /// The kind of the underlying I/O error.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.io().kind()
+    }
+}
+
+impl fmt::Display for Error {
+    /// Describes what failed without the path, which callers usually report first, nor the
+    /// underlying error, which is the [`source`](std::error::Error::source).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Open { .. } => f.write_str("failed to open"),
+            Error::Read { .. } => f.write_str("failed to read"),
+            Error::Map { .. } => f.write_str("failed to memory-map"),
+            Error::Spill { .. } => f.write_str("failed to spill to a temporary file"),
+            Error::Write { .. } => f.write_str("failed to write"),
+            Error::Other(source) => fmt::Display::fmt(source, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Other(source) => source.source(),
+            _ => Some(self.io()),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Other(source) => source,
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// The error carried by `error` if it was converted from one, and [`Error::Other`] otherwise.
+    fn from(error: io::Error) -> Error {
+        if Error::of(&error).is_none() {
+            return Error::Other(error);
+        }
+        let kind = error.kind();
+        match error.into_inner().map(|inner| inner.downcast::<Error>()) {
+            Some(Ok(error)) => *error,
+            Some(Err(inner)) => Error::Other(io::Error::new(kind, inner)),
+            None => Error::Other(kind.into()),
+        }
+    }
+}
+
+/// Attach what failed to `result`, with `stage` building the [`Error`] from the I/O error. An error
+/// already carrying one is passed on as is, like the [`Stop`](crate::Stop) of a writer or
+/// [`Cancelled`](crate::Cancelled).
+pub(crate) fn context<T>(result: io::Result<T>, stage: impl FnOnce(io::Error) -> Error) -> io::Result<T> {
+    result.map_err(|e| {
+        if Error::of(&e).is_some() || crate::Stop::is(&e) || crate::Cancelled::is(&e) {
+            e
+        } else {
+            stage(e).into()
+        }
+    })
+}
@@ -0,0 +1,108 @@
+/// Find the last occurrence of `needle` in `haystack`, returning the index of its first byte.
+///
+/// This uses the same SIMD-accelerated backwards scan as [`reverse_file`](crate::reverse_file): the
+/// haystack is scanned for the last byte of `needle`, and every candidate is then verified. An empty
+/// `needle` matches at the end of `haystack`, like [`str::rfind`].
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::rfind;
+///
+/// assert_eq!(rfind(b"one\r\ntwo\r\nthree", b"\r\n"), Some(8));
+/// assert_eq!(rfind(b"one\ntwo", b"\r\n"), None);
+/// ```
+pub fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let (&anchor, _) = match needle.split_last() {
+        Some(split) => split,
+        None => return Some(haystack.len()),
+    };
+
+    let mut found = None;
+    // The search is aborted with an error as soon as a match has been verified.
+    let _ = crate::search::search_auto(haystack, anchor, &mut |offset| {
+        if offset >= needle.len() && &haystack[offset - needle.len()..offset] == needle {
+            found = Some(offset - needle.len());
+            return Err(crate::search::abort());
+        }
+        Ok(())
+    });
+    found
+}
+
+/// Call `f` with the index of every occurrence of the byte `separator` in `bytes`, from its end to
+/// its start.
+///
+/// This is the SIMD-accelerated backwards scan of [`reverse_file`](crate::reverse_file) on its own,
+/// for callers that need the offsets of records rather than their content, e.g. to index a huge
+/// log. Unlike [`rfind_iter`], `bytes` is scanned in one uninterrupted pass, which makes this the
+/// fastest way to visit every separator; use [`rfind_iter`] to stop early.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::for_each_separator_rev;
+///
+/// let mut indices = vec![];
+/// for_each_separator_rev(b"one\ntwo\nthree", b'\n', |index| indices.push(index));
+/// assert_eq!(indices, [7, 3]);
+/// ```
+pub fn for_each_separator_rev(bytes: &[u8], separator: u8, mut f: impl FnMut(usize)) {
+    let _ = crate::search::search_auto(bytes, separator, &mut |offset| {
+        f(offset - 1);
+        Ok(())
+    });
+}
+
+/// Iterate over the non-overlapping occurrences of `needle` in `haystack` from its end to its
+/// start, yielding the index of the first byte of each, like [`str::rmatch_indices`].
+///
+/// Each step resumes the backwards scan of [`rfind`] where the previous one stopped, so the
+/// haystack is scanned only once however many occurrences are consumed.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::rfind_iter;
+///
+/// let matches: Vec<_> = rfind_iter(b"a---b---c", b"---").collect();
+/// assert_eq!(matches, [5, 1]);
+/// ```
+pub fn rfind_iter<'a>(haystack: &'a [u8], needle: &'a [u8]) -> RFindIter<'a> {
+    RFindIter {
+        haystack,
+        needle,
+        end: Some(haystack.len()),
+    }
+}
+
+/// Iterator returned by [`rfind_iter`].
+#[derive(Debug, Clone)]
+pub struct RFindIter<'a> {
+    haystack: &'a [u8],
+    needle: &'a [u8],
+    /// End of the part of the haystack not searched yet, `None` once exhausted.
+    end: Option<usize>,
+}
+
+impl Iterator for RFindIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let end = self.end?;
+        match rfind(&self.haystack[..end], self.needle) {
+            Some(index) => {
+                // An empty needle matches at every position, so step over it manually.
+                self.end = match self.needle.is_empty() {
+                    true => index.checked_sub(1),
+                    false => Some(index),
+                };
+                Some(index)
+            }
+            None => {
+                self.end = None;
+                None
+            }
+        }
+    }
+}
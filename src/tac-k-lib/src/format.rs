@@ -0,0 +1,111 @@
+use std::io::{Result, Write};
+
+/// How records are written into the output, see [`ReverseOptions::output_format`].
+///
+/// [`ReverseOptions::output_format`]: crate::ReverseOptions::output_format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Records are written as they are, separator included.
+    #[default]
+    Raw,
+    /// A single JSON array of `{"offset":..,"record":".."}` objects, one per record.
+    Json,
+    /// One `{"offset":..,"record":".."}` JSON object per line.
+    Ndjson,
+    /// Every record without its separator, prefixed with its length as a big-endian `u32`.
+    Framed,
+    /// Every record without its separator, base64-encoded (standard alphabet, padded) on a line of
+    /// its own.
+    Base64,
+    /// Every record without its separator on a line of its own, with backslashes, control
+    /// characters and non-ASCII bytes escaped C-style (e.g. `\\`, `\n`, `\x7f`).
+    Escaped,
+    /// The offset and length of every record, separator included, as decimal numbers separated by a
+    /// tab on a line of their own, without the content.
+    Offsets,
+}
+
+/// Write the `{"offset":..,"record":".."}` object describing a record starting at `offset`.
+///
+/// `content` is the record without its trailing separator. JSON strings must be valid Unicode, so
+/// invalid UTF-8 sequences are replaced with U+FFFD.
+pub(crate) fn write_json_record(output: &mut dyn Write, content: &[u8], offset: u64) -> Result<()> {
+    write!(output, "{{\"offset\":{offset},\"record\":\"")?;
+    let content = String::from_utf8_lossy(content);
+    let mut rest = content.as_bytes();
+    while let Some(index) = rest.iter().position(|&b| b == b'"' || b == b'\\' || b < 0x20) {
+        output.write_all(&rest[..index])?;
+        match rest[index] {
+            b'"' => output.write_all(b"\\\"")?,
+            b'\\' => output.write_all(b"\\\\")?,
+            b'\n' => output.write_all(b"\\n")?,
+            b'\r' => output.write_all(b"\\r")?,
+            b'\t' => output.write_all(b"\\t")?,
+            0x08 => output.write_all(b"\\b")?,
+            0x0c => output.write_all(b"\\f")?,
+            b => write!(output, "\\u{b:04x}")?,
+        }
+        rest = &rest[index + 1..];
+    }
+    output.write_all(rest)?;
+    output.write_all(b"\"}")
+}
+
+/// Write `content` prefixed with its length as a big-endian `u32`.
+pub(crate) fn write_framed_record(output: &mut dyn Write, content: &[u8]) -> Result<()> {
+    let len = u32::try_from(content.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "record too long for a 32-bit length prefix",
+        )
+    })?;
+    output.write_all(&len.to_be_bytes())?;
+    output.write_all(content)
+}
+
+/// Write `content` base64-encoded, with the standard alphabet and padding.
+pub(crate) fn write_base64_record(output: &mut dyn Write, content: &[u8]) -> Result<()> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    // Encode in pieces whose length is a multiple of 3, so only the last one may need padding.
+    let mut encoded = [0; 1024];
+    for piece in content.chunks(768) {
+        let mut len = 0;
+        for group in piece.chunks(3) {
+            let bits = group
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, &b)| bits | u32::from(b) << (16 - 8 * i));
+            for i in 0..4 {
+                encoded[len + i] = match i <= group.len() {
+                    true => ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize],
+                    false => b'=',
+                };
+            }
+            len += 4;
+        }
+        output.write_all(&encoded[..len])?;
+    }
+    Ok(())
+}
+
+/// Write `content` with C-style backslash escapes, so that only printable ASCII is written.
+pub(crate) fn write_escaped_record(output: &mut dyn Write, content: &[u8]) -> Result<()> {
+    let mut rest = content;
+    while let Some(index) = rest.iter().position(|&b| b == b'\\' || !(b' '..=b'~').contains(&b)) {
+        output.write_all(&rest[..index])?;
+        match rest[index] {
+            b'\\' => output.write_all(b"\\\\")?,
+            0x07 => output.write_all(b"\\a")?,
+            0x08 => output.write_all(b"\\b")?,
+            b'\t' => output.write_all(b"\\t")?,
+            b'\n' => output.write_all(b"\\n")?,
+            0x0b => output.write_all(b"\\v")?,
+            0x0c => output.write_all(b"\\f")?,
+            b'\r' => output.write_all(b"\\r")?,
+            b => write!(output, "\\x{b:02x}")?,
+        }
+        rest = &rest[index + 1..];
+    }
+    output.write_all(rest)
+}
@@ -1,22 +1,145 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+use buffer::Buffer;
+#[cfg(feature = "std")]
+use emit::Emitter;
+#[cfg(feature = "std")]
 use memmap2::Mmap;
+#[cfg(feature = "std")]
+use search::{search_backend, Backend, PinnedEngine};
+#[cfg(feature = "std")]
+use separator::Separator;
 
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::prelude::*;
+#[cfg(feature = "std")]
 use std::io::Result;
+#[cfg(feature = "std")]
+use std::ops::Range;
+#[cfg(feature = "std")]
 use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+mod advice;
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+mod buffer;
+#[cfg(feature = "std")]
+mod cache;
+mod capabilities;
+#[cfg(feature = "std")]
+mod characters;
+#[cfg(feature = "std")]
+mod chunked;
+#[cfg(feature = "std")]
+mod contents;
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+mod decompress;
+#[cfg(feature = "std")]
+mod emit;
+#[cfg(feature = "encoding")]
+mod encoding;
+#[cfg(feature = "std")]
+mod error;
+mod find;
+#[cfg(feature = "std")]
+mod format;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod numa;
+#[cfg(feature = "std")]
+mod options;
+#[cfg(feature = "std")]
+mod parallel;
+#[cfg(feature = "std")]
+mod progress;
+mod records;
+mod search;
+#[cfg(feature = "std")]
+mod separator;
+mod sink;
+#[cfg(feature = "std")]
+mod spill;
+#[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+mod splice;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod summary;
+#[cfg(feature = "std")]
+mod utf8;
 
+#[cfg(feature = "std")]
+pub use advice::MmapAdvice;
+#[cfg(feature = "allocator_api")]
+pub use allocator_api2;
+#[cfg(feature = "async")]
+pub use async_io::{reverse_async, reverse_file_async};
+pub use capabilities::{capabilities, Capabilities};
+#[cfg(feature = "std")]
+pub use characters::{reverse_characters, Characters};
+#[cfg(feature = "std")]
+pub use contents::Contents;
+#[cfg(feature = "std")]
+pub use emit::Output;
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
+#[cfg(feature = "std")]
+pub use error::Error;
+pub use find::{for_each_separator_rev, rfind, rfind_iter, RFindIter};
+#[cfg(feature = "std")]
+pub use format::OutputFormat;
+#[cfg(feature = "std")]
+pub use options::{Fragment, ProgressHook, ReverseOptions, StatsHook, TraceHook, TrailingSeparator};
+#[cfg(feature = "std")]
+pub use progress::Throughput;
+#[cfg(feature = "rayon")]
+pub use rayon;
+#[cfg(feature = "bstr")]
+pub use records::RevRecordsExt;
+pub use records::{rev_records, RevRecords};
+#[cfg(feature = "regex")]
+pub use regex;
+#[cfg(feature = "std")]
+pub use search::calibrate_search;
+#[cfg(feature = "std")]
+pub use separator::unescape;
+pub use sink::{reverse_into, Sink};
+#[cfg(feature = "std")]
+pub use spill::{SpillFile, SpillHook, SpillPolicy};
+#[cfg(feature = "std")]
+pub use stats::Stats;
+#[cfg(feature = "std")]
+pub use summary::Summary;
+#[cfg(feature = "std")]
+pub use utf8::InvalidUtf8;
+
+#[cfg(feature = "std")]
 const MAX_BUF_SIZE: usize = 4 * 1024 * 1024; // 4 MiB
+#[cfg(feature = "std")]
+const DEFAULT_MMAP_THRESHOLD: u64 = 64 * 1024; // 64 KiB
+/// Length of the end of a file first mapped to find the records [`ReverseOptions::max_records`]
+/// leaves in, doubled until it holds them all.
+#[cfg(feature = "std")]
+const TAIL_WINDOW: u64 = 64 * 1024; // 64 KiB
 
-#[cfg_attr(
-    target_family = "unix",
-    allow(unreachable_code),
-    allow(unused_mut),
-    allow(unused_variables)
-)]
 /// Write the reversed content from `path` into `writer`, last line first.
 ///
 /// If `path` is `Some(_)`, read from the file at the specified path.
 /// If `path` is `None`, read from `stdin` instead.
+/// With the `zip` feature, a `path` of the form `archive.zip:member` that does not exist itself
+/// reads the member `member` of the zip archive `archive.zip`, which is buffered like `stdin`.
+/// Likewise, a `path` ending in `.gz`, `.zst` or `.xz` is decompressed and buffered with the `gzip`,
+/// `zstd` and `xz` features respectively.
 ///
 /// `separator` is used to partition the content into lines.
 /// This is normally the newline character, `b'\n'`.
@@ -29,12 +152,12 @@ const MAX_BUF_SIZE: usize = 4 * 1024 * 1024; // 4 MiB
 /// ## Example
 ///
 /// ```
-/// use tac_k::reverse_file;
+/// use tac_k_lib::reverse_file;
 /// use std::path::Path;
 ///
-/// // Read from `README.md` file, separated by '.'.
+/// // Read from `Cargo.toml` file, separated by '.'.
 /// let mut result = vec![];
-/// reverse_file(&mut result, Some("README.md"), b'.').unwrap();
+/// reverse_file(&mut result, Some("Cargo.toml"), b'.').unwrap();
 ///
 /// assert!(std::str::from_utf8(&result).is_ok());
 ///
@@ -44,353 +167,3322 @@ const MAX_BUF_SIZE: usize = 4 * 1024 * 1024; // 4 MiB
 ///
 /// assert!(result.is_empty());
 /// ```
-pub fn reverse_file<W: Write, P: AsRef<Path>>(writer: &mut W, path: Option<P>, separator: u8) -> Result<()> {
-    fn inner(writer: &mut dyn Write, path: Option<&Path>, separator: u8) -> Result<()> {
-        let mut temp_path = None;
-        {
-            let mmap;
-            let mut buf;
-            let bytes = match path {
-                #[cfg_attr(not(target_family = "unix"), allow(unused_labels))]
-                None => 'stdin: {
+#[cfg(feature = "std")]
+pub fn reverse_file<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: Option<P>,
+    separator: u8,
+) -> std::result::Result<(), Error> {
+    ReverseOptions::new().separator(separator).run(writer, path)
+}
+
+/// Write the reversed content from `path` into `writer`, last line first, like [`reverse_file`],
+/// returning what became of the input.
+///
+/// The [`Stats`] tell how many bytes and records were read and written, which search
+/// implementation scanned the input and whether it had to be spilled to a temporary file.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_file_with_stats;
+///
+/// let path = std::env::temp_dir().join("tac-k-with-stats.txt");
+/// std::fs::write(&path, "one\ntwo\n").unwrap();
+///
+/// let mut result = vec![];
+/// let stats = reverse_file_with_stats(&mut result, Some(&path), b'\n').unwrap();
+///
+/// assert_eq!(result, b"two\none\n");
+/// assert_eq!((stats.bytes, stats.bytes_written, stats.records), (8, 8, 2));
+/// assert!(!stats.spilled);
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_file_with_stats<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: Option<P>,
+    separator: u8,
+) -> std::result::Result<Stats, Error> {
+    ReverseOptions::new().separator(separator).run_with_stats(writer, path)
+}
+
+/// Write the reversed content from `path` into `writer`, last record first, with records
+/// terminated by the byte string `separator` rather than by a single byte.
+///
+/// See [`reverse_file`] for the meaning of `path`, and [`ReverseOptions::separator_bytes`] for how
+/// occurrences of `separator` are found. The input is still scanned with SIMD instructions, for
+/// the last byte of `separator`, and each candidate is then verified.
+///
+/// ## Panics
+///
+/// If `separator` is empty.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_file_with_separator;
+///
+/// let path = std::env::temp_dir().join("tac-k-with-separator.txt");
+/// std::fs::write(&path, "one\n---\ntwo\n---\n").unwrap();
+///
+/// let mut result = vec![];
+/// reverse_file_with_separator(&mut result, Some(&path), b"---\n").unwrap();
+///
+/// assert_eq!(result, b"two\n---\none\n---\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_file_with_separator<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: Option<P>,
+    separator: &[u8],
+) -> std::result::Result<(), Error> {
+    ReverseOptions::new().separator_bytes(separator).run(writer, path)
+}
+
+/// Write the reversed content from `path` into `writer`, last record first, with records
+/// terminated by the matches of the regular expression `pattern`, like GNU `tac --regex`.
+///
+/// See [`reverse_file`] for the meaning of `path`. `^` and `$` match at the start and end of every
+/// line as well, so a separator can be described by a line of its own, e.g. `^==> .* <==\n`. Fails
+/// with [`ErrorKind::InvalidInput`] if `pattern` is not a valid regular expression, see
+/// [`ReverseOptions::separator_regex`] for more.
+///
+/// [`ErrorKind::InvalidInput`]: std::io::ErrorKind::InvalidInput
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_file_regex;
+///
+/// let path = std::env::temp_dir().join("tac-k-regex.txt");
+/// std::fs::write(&path, "one\n--\ntwo\n----\n").unwrap();
+///
+/// let mut result = vec![];
+/// reverse_file_regex(&mut result, Some(&path), "^-+\n").unwrap();
+///
+/// assert_eq!(result, b"two\n----\none\n--\n");
+/// ```
+#[cfg(feature = "regex")]
+pub fn reverse_file_regex<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: Option<P>,
+    pattern: &str,
+) -> std::result::Result<(), Error> {
+    let regex = regex::bytes::RegexBuilder::new(pattern)
+        .multi_line(true)
+        .build()
+        .map_err(|e| Error::Other(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+    ReverseOptions::new().separator_regex(regex).run(writer, path)
+}
+
+/// Write the reversed content read from `reader` into `writer`, last record first, with records
+/// terminated by `separator`.
+///
+/// Like `stdin`, `reader` has to be read in full before the first record can be written: it is
+/// buffered in memory up to 4 MiB and spilled to a temporary file beyond that, see
+/// [`ReverseOptions::spill_policy`] to change that. This allows reversing content from sockets,
+/// child processes or decoders, without writing it to a file first.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_reader;
+///
+/// let mut result = vec![];
+/// reverse_reader(&mut result, &mut &b"one\ntwo\n"[..], b'\n').unwrap();
+///
+/// assert_eq!(result, b"two\none\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_reader<W: Write, R: Read>(
+    writer: &mut W,
+    reader: &mut R,
+    separator: u8,
+) -> std::result::Result<(), Error> {
+    ReverseOptions::new().separator(separator).run_reader(writer, reader)
+}
+
+/// Write the reversed content of `bytes` into `writer`, last record first, with records terminated
+/// by `separator`.
+///
+/// `bytes` is searched in place with the same SIMD implementation [`reverse_file`] uses, so content
+/// already in memory (e.g. a database blob) does not need to be written to a file first.
+///
+/// ## Stability
+///
+/// The output is exactly what [`reverse_file`] writes for a file holding `bytes`, and that will not
+/// change within a major version. Which SIMD implementation is used is an implementation detail,
+/// see [`capabilities`] to find out.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_bytes;
+///
+/// let mut result = vec![];
+/// reverse_bytes(&mut result, b"one\ntwo\nthree", b'\n').unwrap();
+///
+/// assert_eq!(result, b"threetwo\none\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_bytes<W: Write>(writer: &mut W, bytes: &[u8], separator: u8) -> std::result::Result<(), Error> {
+    ReverseOptions::new().separator(separator).run_bytes(writer, bytes)
+}
+
+/// Write the reversed content from each of `paths` into `writer` in turn, returning the result of
+/// every input.
+///
+/// See [`reverse_file`] for the meaning of each path. A failing input does not prevent the next
+/// ones from being reversed, unless it is `writer` that failed (or asked to [`Stop`]): results are
+/// then only returned for the inputs up to and including that one. Buffers are reused from one
+/// input to the next.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_files;
+///
+/// let path = std::env::temp_dir().join("tac-k-reverse-files.txt");
+/// std::fs::write(&path, "one\ntwo\n").unwrap();
+///
+/// let mut result = vec![];
+/// let results = reverse_files(&mut result, &[Some(&path), Some(&path.with_extension("missing"))], b'\n');
+///
+/// assert_eq!(result, b"two\none\n");
+/// assert!(results[0].is_ok() && results[1].is_err());
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_files<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    paths: &[Option<P>],
+    separator: u8,
+) -> Vec<std::result::Result<(), Error>> {
+    ReverseOptions::new().separator(separator).run_files(writer, paths)
+}
+
+/// Write the reversed records of the file at `path` that overlap `range` into `writer`, last record
+/// first, with records terminated by `separator`.
+///
+/// `range` is a range of byte offsets in the file, e.g. taken from an index, which is widened to
+/// the start and end of the records it cuts through: every record with a byte in it is written
+/// whole, and nothing else. Only that part of a mapped file is touched. A range extending past the
+/// end of the file is clamped to it.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::reverse_range;
+///
+/// let path = std::env::temp_dir().join("tac-k-reverse-range.txt");
+/// std::fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+///
+/// let mut result = vec![];
+/// reverse_range(&mut result, &path, b'\n', 5..9).unwrap();
+///
+/// assert_eq!(result, b"three\ntwo\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_range<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: P,
+    separator: u8,
+    range: Range<u64>,
+) -> std::result::Result<(), Error> {
+    ReverseOptions::new()
+        .separator(separator)
+        .run_range(writer, path, range)
+}
+
+/// Count the records of the input at `path`, terminated by `separator`, without writing them
+/// anywhere.
+///
+/// See [`reverse_file`] for the meaning of `path`. The input is accessed and scanned exactly as
+/// [`reverse_file`] would, so this is as fast as reversing it into [`std::io::sink`] and much
+/// faster than counting the lines of the output. An unterminated last record counts as a record,
+/// unlike for `wc -l`. Use [`ReverseOptions::count`] to count the records other options would
+/// write, e.g. with [`ReverseOptions::until_match`].
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::count_records;
+///
+/// let path = std::env::temp_dir().join("tac-k-count-records.txt");
+/// std::fs::write(&path, "one\ntwo\nthree").unwrap();
+///
+/// assert_eq!(count_records(Some(&path), b'\n').unwrap(), 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn count_records<P: AsRef<Path>>(path: Option<P>, separator: u8) -> std::result::Result<u64, Error> {
+    ReverseOptions::new().separator(separator).count(path)
+}
+
+/// Write the reversed content from `path` into `writer`, last record first, searching for
+/// `separator` with the given search implementation instead of the fastest one available.
+///
+/// See [`reverse_file`] for the meaning of `path`. This is meant to benchmark and debug the search
+/// implementations, and fails with [`ErrorKind::Unsupported`] if this machine does not support
+/// `engine`, see [`Engine::is_supported`].
+///
+/// [`ErrorKind::Unsupported`]: std::io::ErrorKind::Unsupported
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{reverse_file_with_engine, Engine};
+///
+/// let path = std::env::temp_dir().join("tac-k-with-engine.txt");
+/// std::fs::write(&path, "one\ntwo\n").unwrap();
+///
+/// let mut result = vec![];
+/// reverse_file_with_engine(&mut result, Some(&path), b'\n', Engine::Scalar).unwrap();
+///
+/// assert_eq!(result, b"two\none\n");
+/// ```
+#[cfg(feature = "std")]
+pub fn reverse_file_with_engine<W: Write, P: AsRef<Path>>(
+    writer: &mut W,
+    path: Option<P>,
+    separator: u8,
+    engine: Engine,
+) -> std::result::Result<(), Error> {
+    ReverseOptions::new()
+        .separator(separator)
+        .engine(engine)
+        .run(writer, path)
+}
+
+#[cfg(feature = "std")]
+fn reverse_all<'p>(
+    writer: &mut dyn Write,
+    paths: impl Iterator<Item = Option<&'p Path>>,
+    options: &ReverseOptions,
+) -> Summary {
+    let paths: Vec<_> = paths.collect();
+    let threads = options.search_threads();
+    if threads > 1 && paths.len() > 1 && !options.line_buffered {
+        return parallel::reverse_all(writer, &paths, threads, options);
+    }
+    let mut paths = paths.into_iter();
+    let mut buf = options.new_buffer();
+    let mut summary = Summary::default();
+    for path in paths.by_ref() {
+        let mut outputs = [Output::new(&mut *writer)];
+        let result = reverse(&mut outputs, Input::Path(path), options, &mut buf).map(drop);
+        let cancelled = result.as_ref().is_err_and(Cancelled::is);
+        summary
+            .inputs
+            .push((path.map(Path::to_path_buf), result.map_err(Error::from)));
+        if outputs[0].failed || cancelled {
+            break;
+        }
+    }
+    summary.skipped = paths.count();
+    summary
+}
+
+/// Reverse `input` into `outputs`, returning what became of it.
+#[cfg_attr(
+    target_family = "unix",
+    allow(unreachable_code),
+    allow(unused_mut),
+    allow(unused_variables)
+)]
+#[cfg(feature = "std")]
+fn reverse(outputs: &mut [Output], input: Input, options: &ReverseOptions, buf: &mut Buffer) -> Result<Stats> {
+    let path = match input {
+        Input::Path(path) => path,
+        Input::Range(path, ..) => Some(path),
+        Input::Reader(_) | Input::Bytes(_) => None,
+    };
+    if options.calibrate_search {
+        calibrate_search();
+    }
+    let _pinned = PinnedEngine::new(options.engine)?;
+    let started = now();
+    let owned = || path.map(Path::to_path_buf);
+    let read = |source| Error::Read { path: owned(), source };
+    let mut temp_path = None;
+    // Length of the input, number of records and bytes written and when reversing started, for the
+    // stats.
+    let mut reversed = None;
+    let mut spilled = false;
+    // Offset of the bytes reversed in the input, unless that is reversed as a whole.
+    let mut base = 0;
+    let decoding = options.decoding();
+    // The encoding a decoded input turns out to be in.
+    #[cfg(feature = "encoding")]
+    let decoded_from = std::cell::Cell::new(None);
+    let result = (|| -> Result<()> {
+        let mmap;
+        // Window to read the mapped input ahead by, see `MmapAdvice::Backwards`.
+        let mut read_ahead = None;
+        #[cfg(feature = "zip")]
+        let mut archive;
+        let bytes = 'input: {
+            let mut reader: Box<dyn Read + '_> = match input {
+                Input::Bytes(bytes) if !decoding => break 'input bytes,
+                Input::Bytes(bytes) => Box::new(bytes),
+                Input::Range(_, bytes, start) => {
+                    base = start;
+                    break 'input bytes;
+                }
+                Input::Reader(reader) => {
+                    options.trace(|| "buffering the reader".into());
+                    Box::new(reader)
+                }
+                Input::Path(None) => {
                     // Depending on what the STDIN fd actually points to, it may still be possible to
                     // mmap the input (e.g. in case of `tac - < foo.txt`).
-                    #[cfg(target_family = "unix")]
-                    {
-                        let stdin = std::io::stdin();
-                        if let Ok(stdin) = unsafe { Mmap::map(&stdin) } {
-                            mmap = stdin;
-                            break 'stdin &mmap[..];
+                    if decoding {
+                        options.trace(|| "buffering stdin to decode it".into());
+                    } else {
+                        if options.mmap {
+                            match map_stdin() {
+                                Ok(stdin) => {
+                                    options.trace(|| "stdin is memory-mapped".into());
+                                    read_ahead = advice::advise(&stdin, options.mmap_advice);
+                                    mmap = stdin;
+                                    break 'input &mmap[..];
+                                }
+                                Err(e) => options.trace(|| format!("stdin cannot be memory-mapped ({e})")),
+                            }
+                        } else {
+                            options.trace(|| "memory-mapping is disabled for stdin".into());
                         }
-                    }
-
-                    // We unfortunately need to buffer the entirety of the stdin input first;
-                    // we try to do so purely in memory but will switch to a backing file if
-                    // the input exceeds MAX_BUF_SIZE.
-                    buf = vec![0; MAX_BUF_SIZE];
-                    let mut reader = std::io::stdin();
-                    let mut total_read = 0;
-
-                    // Once/if we switch to a file-backed buffer, this will contain the handle.
-                    loop {
-                        let bytes_read = reader.read(&mut buf[total_read..])?;
-                        if bytes_read == 0 {
-                            break &buf[0..total_read];
+                        // A file redirected to stdin can still be read backwards rather than copied.
+                        if let Some((mut stdin, len)) = seekable_stdin() {
+                            options.trace(|| "stdin is seekable, reading it backwards".into());
+                            let result = reverse_backwards(outputs, &mut stdin, len, None, options, &mut reversed);
+                            return error::context(result, read);
                         }
-                        total_read += bytes_read;
-
-                        if total_read == MAX_BUF_SIZE {
-                            temp_path = Some(std::env::temp_dir().join(format!(".tac-{}", std::process::id())));
-                            let mut temp_file = File::create(temp_path.as_ref().unwrap())?;
-                            // Write everything we've read so far
-                            temp_file.write_all(&buf)?;
-                            // Copy remaining bytes directly from stdin
-                            std::io::copy(&mut reader, &mut temp_file)?;
-                            mmap = unsafe { Mmap::map(&temp_file)? };
-                            break &mmap[..];
+                        options.trace(|| "buffering stdin".into());
+                    }
+                    let stdin = StdinReader::new(options.stdin_timeout);
+                    Box::new(error::context(stdin, |source| Error::Read { path: None, source })?)
+                }
+                Input::Path(Some(path)) => 'file: {
+                    let open = |source| Error::Open {
+                        path: path.to_path_buf(),
+                        source,
+                    };
+                    #[cfg(feature = "zip")]
+                    if let Some((archive_path, name)) = archive::split_member(path) {
+                        options.trace(|| format!("extracting {name} from {}", archive_path.display()));
+                        archive = error::context(archive::open(archive_path), open)?;
+                        break 'file Box::new(error::context(archive::member(&mut archive, archive_path, name), open)?);
+                    }
+                    #[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+                    if let Some(decoder) = error::context(decompress::open(path), open)? {
+                        options.trace(|| format!("decompressing {}", path.display()));
+                        break 'file decoder;
+                    }
+                    let mut file = error::context(File::open(path), open)?;
+                    if decoding {
+                        options.trace(|| format!("buffering {} to decode it", path.display()));
+                        break 'file Box::new(file);
+                    }
+                    let metadata = error::context(file.metadata(), open)?;
+                    if metadata.is_file() && metadata.len() < options.mmap_threshold {
+                        options.trace(|| format!("{} is small, reading it instead of mapping it", path.display()));
+                        let len = metadata.len() as usize;
+                        buf.clear();
+                        buffer::reserve(buf, len)?;
+                        buf.resize(len, 0);
+                        let len = error::context(read_full(&mut file, buf), read)?;
+                        break 'input &buf[..len];
+                    }
+                    if is_stream(&metadata) {
+                        // E.g. `/dev/fd/N` from a process substitution, which is a pipe.
+                        options.trace(|| format!("{} is not a regular file, buffering it", path.display()));
+                        break 'file Box::new(file);
+                    }
+                    if metadata.is_file() {
+                        // A misbehaving mount can hang or SIGBUS a process accessing a mapping.
+                        match network_fs(&file) {
+                            Some(fs) => {
+                                options.trace(|| format!("{} is on {fs}, reading it backwards", path.display()))
+                            }
+                            None if !options.mmap => options
+                                .trace(|| format!("memory-mapping is disabled, reading {} backwards", path.display())),
+                            None => match map_file(&file, metadata.len(), options) {
+                                Ok((file, start)) => {
+                                    read_ahead = advice::advise(&file, options.mmap_advice);
+                                    mmap = file;
+                                    base = start;
+                                    options.trace(|| match start {
+                                        0 => format!("{} is memory-mapped", path.display()),
+                                        _ => format!("{} is memory-mapped from byte {start} on", path.display()),
+                                    });
+                                    break 'input &mmap[..];
+                                }
+                                // E.g. a file larger than the address space of a 32-bit target.
+                                Err(e) => options.trace(|| {
+                                    format!("{} cannot be memory-mapped ({e}), reading it backwards", path.display())
+                                }),
+                            },
                         }
+                        let result =
+                            reverse_backwards(outputs, &mut file, metadata.len(), Some(path), options, &mut reversed);
+                        // Errors writing into the outputs are already told apart.
+                        return error::context(result, read);
+                    }
+                    // A block device has no length to read backwards from.
+                    if !options.mmap {
+                        options.trace(|| format!("memory-mapping is disabled, buffering {}", path.display()));
+                        break 'file Box::new(file);
                     }
+                    mmap = error::context(unsafe { Mmap::map(&file) }, |source| Error::Map {
+                        path: owned(),
+                        source,
+                    })?;
+                    read_ahead = advice::advise(&mmap, options.mmap_advice);
+                    options.trace(|| format!("{} is memory-mapped", path.display()));
+                    break 'input &mmap[..];
                 }
-                Some(path) => {
-                    let file = File::open(path)?;
-                    mmap = unsafe { Mmap::map(&file)? };
-                    &mmap[..]
+            };
+            #[cfg(feature = "encoding")]
+            if let Some(encoding) = options.encoding {
+                reader = Box::new(encoding::Decoder::new(reader, encoding, &decoded_from));
+            }
+
+            // We unfortunately need to buffer the entirety of the input first;
+            // we try to do so purely in memory but will switch to a backing file if
+            // the input exceeds the buffer size.
+            let buffer_size = options.buffer_size();
+            let len = buffer_size.min(MAX_BUF_SIZE);
+            buf.clear();
+            buffer::reserve(buf, len)?;
+            buf.resize(len, 0);
+            let mut total_read = 0;
+
+            loop {
+                if total_read == buf.len() {
+                    if buf.len() < buffer_size {
+                        // Only grow the buffer as needed when it may exceed the default size.
+                        let len = buf.len().saturating_mul(2).min(buffer_size);
+                        buffer::reserve(buf, len - buf.len())?;
+                        buf.resize(len, 0);
+                    } else {
+                        break;
+                    }
+                }
+                options.check_cancelled()?;
+                let bytes_read = error::context(reader.read(&mut buf[total_read..]), read)?;
+                if bytes_read == 0 {
+                    break 'input &buf[0..total_read];
+                }
+                total_read += bytes_read;
+            }
+
+            // Once/if we switch to a file-backed buffer, this will contain the handle.
+            let spill_file = match (&options.spill_policy, &options.spill_hook) {
+                (Some(SpillPolicy::TempFileIn(dir)), _) => SpillFile::in_dir(dir),
+                (Some(_), _) => {
+                    // An input filling the buffer exactly does not need to be spilled.
+                    if error::context(read_full(&mut reader, &mut [0]), read)? == 0 {
+                        break 'input &buf[..];
+                    }
+                    return Err(std::io::Error::other(format!(
+                        "input exceeds {buffer_size} bytes, which the spill policy does not allow to spill"
+                    )));
                 }
+                (None, Some(hook)) => hook(),
+                (None, None) => SpillFile::in_temp_dir(),
             };
+            let SpillFile {
+                file: mut temp_file,
+                path: spill_path,
+            } = error::context(spill_file, |source| Error::Spill { path: None, source })?;
+            temp_path = spill_path;
+            spilled = true;
+            let spilling = |source| Error::Spill {
+                path: temp_path.clone(),
+                source,
+            };
+            options.trace(|| match &temp_path {
+                Some(path) => format!("input exceeds {buffer_size} bytes, spilling it to {}", path.display()),
+                None => format!("input exceeds {buffer_size} bytes, spilling it to an anonymous file"),
+            });
+
+            #[cfg(any(feature = "lz4", feature = "encrypt"))]
+            if spill::BlockSpill::required(options) {
+                options.trace(|| "storing the spill file as compressed/encrypted blocks".into());
+                #[cfg(feature = "encoding")]
+                let decoded = options.decoded_from(decoded_from.get());
+                #[cfg(feature = "encoding")]
+                let options = &*decoded;
+                let detected = options.for_input(buf);
+                let options = &*detected;
+                let mut blocks = error::context(spill::BlockSpill::new(temp_file, options), spilling)?;
+                error::context(blocks.push(buf), spilling)?;
+                loop {
+                    options.check_cancelled()?;
+                    let len = error::context(read_full(&mut reader, buf), read)?;
+                    if len == 0 {
+                        break;
+                    }
+                    error::context(blocks.push(&buf[..len]), spilling)?;
+                }
+                options.trace(|| format!("scanning {} bytes with the {} search", blocks.len(), search_backend()));
+                let len = blocks.len();
+                // Reading the blocks back twice would mean decompressing or decrypting them twice.
+                let lines = match blocks.records() {
+                    Some(lines) => lines,
+                    None => {
+                        let lines = count_lines(options, len, |emitter| blocks.reverse(&options.separator, emitter));
+                        error::context(lines, spilling)?
+                    }
+                };
+                let reversing = now();
+                let mut emitter = Emitter::fanout(outputs, len, options).label(path).numbered(lines);
+                let result = blocks
+                    .reverse(&options.separator, &mut emitter)
+                    .and_then(|()| emitter.finish());
+                reversed = Some((len, emitter.records, emitter.written(), reversing));
+                return error::context(result, spilling);
+            }
+
+            // Write everything we've read so far, then the remaining bytes of the input
+            error::context(temp_file.write_all(buf), spilling)?;
+            loop {
+                options.check_cancelled()?;
+                let len = error::context(read_full(&mut reader, buf), read)?;
+                if len == 0 {
+                    break;
+                }
+                error::context(temp_file.write_all(&buf[..len]), spilling)?;
+            }
+            mmap = error::context(unsafe { Mmap::map(&temp_file) }, spilling)?;
+            &mmap[..]
+        };
 
-            search_auto(bytes, separator, writer)?;
+        #[cfg(feature = "encoding")]
+        let decoded = options.decoded_from(decoded_from.get());
+        #[cfg(feature = "encoding")]
+        let options = &*decoded;
+        let detected = options.for_input(bytes);
+        let options = &*detected;
+        let threads = options.search_threads();
+        let chunk_size = options.search_chunk_size(threads);
+        let len = base + bytes.len() as u64;
+        let lines = count_lines(options, len, |emitter| {
+            emit_reversed(bytes, base, &options.separator, emitter)
+        })?;
+        let reversing = now();
+        let mut emitter = Emitter::fanout(outputs, len, options)
+            .label(path)
+            .numbered(lines)
+            .prefetch(bytes, read_ahead);
+        let result = match &options.separator {
+            Separator::Bytes(separator) if threads > 1 && separator.len() == 1 && bytes.len() > chunk_size => {
+                options.trace(|| {
+                    format!(
+                        "scanning {} bytes with the {} search on {threads} threads, {chunk_size} bytes at a time",
+                        bytes.len(),
+                        search_backend()
+                    )
+                });
+                #[cfg(target_os = "linux")]
+                if let Some(nodes) = numa::nodes() {
+                    options.trace(|| {
+                        format!(
+                            "binding search threads to the one of {} NUMA nodes holding their chunk",
+                            nodes.len()
+                        )
+                    });
+                }
+                parallel::emit_reversed(bytes, base, separator[0], threads, chunk_size, options, &mut emitter)
+            }
+            _ => {
+                options.trace(|| format!("scanning {} bytes with the {} search", bytes.len(), search_backend()));
+                emit_reversed(bytes, base, &options.separator, &mut emitter)
+            }
         }
+        .and_then(|()| emitter.finish());
+        reversed = Some((bytes.len() as u64, emitter.records, emitter.written(), reversing));
+        result
+    })();
 
-        if let Some(ref path) = temp_path.as_ref() {
-            // This should never fail unless we've somehow kept a handle open to it
-            if let Err(e) = std::fs::remove_file(path) {
-                eprintln!("Error: failed to remove temporary file {}\n{}", path.display(), e)
-            };
+    // Clean up even if reversing failed halfway, the spill file is of no use to anyone.
+    if let Some(ref path) = temp_path.as_ref() {
+        if options.secure_delete {
+            if let Err(e) = spill::overwrite(path) {
+                eprintln!("Error: failed to overwrite temporary file {}\n{}", path.display(), e)
+            }
         }
+        // This should never fail unless we've somehow kept a handle open to it
+        if let Err(e) = std::fs::remove_file(path) {
+            eprintln!("Error: failed to remove temporary file {}\n{}", path.display(), e)
+        };
+    }
 
-        writer.flush()?;
-        Ok(())
+    // What has been written before a cancellation is flushed all the same.
+    let cancelled = match result {
+        Err(e) if Stop::is(&e) => None,
+        Err(e) if Cancelled::is(&e) => Some(e),
+        result => result.map(|()| None)?,
+    };
+    for output in outputs {
+        error::context(output.flush(), |source| Error::Write { source })?;
+    }
+    if let Some(e) = cancelled {
+        return Err(e);
     }
-    inner(writer, path.as_ref().map(AsRef::as_ref), separator)
+    let (bytes, records, bytes_written, reversing) = reversed.unwrap_or_default();
+    let stats = Stats {
+        path: path.map(Path::to_path_buf),
+        bytes,
+        bytes_written,
+        records,
+        engine: search::backend().engine(),
+        spilled,
+        input_time: reversing
+            .zip(started)
+            .map_or(Duration::ZERO, |(reversing, started)| reversing - started),
+        reverse_time: reversing.map_or(Duration::ZERO, |reversing| reversing.elapsed()),
+    };
+    if let Some(hook) = &options.stats_hook {
+        hook(&stats);
+    }
+    Ok(stats)
 }
 
-fn search_auto(bytes: &[u8], separator: u8, mut output: &mut dyn Write) -> Result<()> {
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("lzcnt") && is_x86_feature_detected!("bmi2") {
-        return unsafe { search256(bytes, separator, &mut output) };
+/// Number of records `emit` passes to an emitter, to number them from the first one, or `0` unless
+/// the options ask for numbers, see [`ReverseOptions::number`].
+#[cfg(feature = "std")]
+fn count_lines(options: &ReverseOptions, len: u64, emit: impl FnOnce(&mut Emitter) -> Result<()>) -> Result<u64> {
+    if !options.number {
+        return Ok(0);
     }
+    let counting = ReverseOptions {
+        separator: options.separator.clone(),
+        before: options.before,
+        ..ReverseOptions::default()
+    };
+    let mut sink = std::io::sink();
+    let mut emitter = Emitter::new(&mut sink, len, &counting);
+    emit(&mut emitter).and_then(|()| emitter.finish())?;
+    Ok(emitter.records)
+}
+
+/// The current time, unless there is no clock to read, i.e. on `wasm32-unknown-unknown` where
+/// [`Instant::now`] panics.
+#[cfg(feature = "std")]
+fn now() -> Option<Instant> {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return None;
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    Some(Instant::now())
+}
+
+/// Where [`reverse`] reads an input from.
+#[cfg(feature = "std")]
+enum Input<'a> {
+    /// The file at the path, or `stdin` if `None`.
+    Path(Option<&'a Path>),
+    /// Any reader, buffered like `stdin`.
+    Reader(&'a mut dyn Read),
+    /// Bytes already in memory, reversed in place.
+    Bytes(&'a [u8]),
+    /// The part of the file at the path already in memory, and its offset in the file.
+    Range(&'a Path, &'a [u8], u64),
+}
+
+/// Marker error a writer can return to end a reversal early without failing it, e.g. once it has
+/// seen enough records.
+///
+/// Any error returned by the writer aborts the backwards scan immediately, so the rest of the input
+/// is never touched. Errors carrying `Stop` are then swallowed and the reversal returns `Ok(())`.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{reverse_file, Stop};
+/// use std::io::Write;
+///
+/// /// Keeps the first record written into it only.
+/// struct First(Vec<u8>);
+///
+/// impl Write for First {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         if !self.0.is_empty() {
+///             return Err(Stop::error());
+///         }
+///         self.0.extend_from_slice(buf);
+///         Ok(buf.len())
+///     }
+///
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut first = First(vec![]);
+/// reverse_file(&mut first, Some("Cargo.toml"), b'\n').unwrap();
+/// assert_eq!(first.0.iter().filter(|&&b| b == b'\n').count(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub struct Stop;
 
-    #[cfg(target_arch = "aarch64")]
-    if std::arch::is_aarch64_feature_detected!("neon") {
-        return unsafe { search128(bytes, separator, &mut output) };
+#[cfg(feature = "std")]
+impl Stop {
+    /// Create an I/O error carrying `Stop`.
+    pub fn error() -> std::io::Error {
+        std::io::Error::other(Stop)
     }
 
-    search(bytes, separator, &mut output)
+    /// Whether `error` carries `Stop`.
+    pub fn is(error: &std::io::Error) -> bool {
+        error.get_ref().is_some_and(|inner| inner.is::<Stop>())
+    }
 }
 
-/// This is the default, naïve byte search
-#[inline(always)]
-fn search(bytes: &[u8], separator: u8, output: &mut dyn Write) -> Result<()> {
-    let mut last_printed = bytes.len();
-    slow_search_and_print(bytes, 0, last_printed, &mut last_printed, separator, output)?;
-    output.write_all(&bytes[..last_printed])?;
-    Ok(())
+#[cfg(feature = "std")]
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("reversal stopped by the writer")
+    }
 }
 
-#[inline(always)]
-/// Search a range index-by-index and write to `output` when a match is found. Primarily used to
-/// search before/after the aligned portion of a range.
-fn slow_search_and_print(
-    bytes: &[u8],
-    start: usize,
-    end: usize,
-    stop: &mut usize,
-    separator: u8,
-    output: &mut dyn Write,
-) -> Result<()> {
-    for index in (start..end).rev() {
-        if bytes[index] == separator {
-            output.write_all(&bytes[index + 1..*stop])?;
-            *stop = index + 1;
-        }
+#[cfg(feature = "std")]
+impl std::error::Error for Stop {}
+
+/// Marker error a reversal fails with once the flag given to [`ReverseOptions::cancel_on`] is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub struct Cancelled;
+
+#[cfg(feature = "std")]
+impl Cancelled {
+    pub(crate) fn error() -> std::io::Error {
+        std::io::Error::other(Cancelled)
     }
 
-    Ok(())
+    /// Whether `error` carries `Cancelled`.
+    pub fn is(error: &std::io::Error) -> bool {
+        error.get_ref().is_some_and(|inner| inner.is::<Cancelled>())
+    }
 }
 
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-#[target_feature(enable = "avx2")]
-#[target_feature(enable = "lzcnt")]
-#[target_feature(enable = "bmi2")]
-/// This is an AVX2-optimized newline search function that searches a 32-byte (256-bit) window
-/// instead of scanning character-by-character (once aligned). This is a *safe* function, but must
-/// be adorned with `unsafe` to guarantee it's not called without first checking for AVX2 support.
-///
-/// We need to explicitly enable lzcnt support for u32::leading_zeros() to use the `lzcnt`
-/// instruction instead of an extremely slow combination of branching + BSR.
-///
-/// BMI2 is explicitly opted into to inline the BZHI instruction; otherwise a call to the intrinsic
-/// function is added and not inlined.
-unsafe fn search256(bytes: &[u8], separator: u8, mut output: &mut dyn Write) -> Result<()> {
-    #[cfg(target_arch = "x86")]
-    use core::arch::x86::*;
-    #[cfg(target_arch = "x86_64")]
-    use core::arch::x86_64::*;
-
-    #[cfg(target_arch = "x86")]
-    const SIZE: u32 = 32;
-    #[cfg(target_arch = "x86_64")]
-    const SIZE: u32 = 64;
-
-    const ALIGNMENT: usize = std::mem::align_of::<__m256i>();
-
-    let ptr = bytes.as_ptr();
-    let len = bytes.len();
-    let mut last_printed = len;
-    let mut remaining = len;
-
-    // We should only use 32-byte (256-bit) aligned reads w/ AVX2 intrinsics.
-    // Search unaligned bytes via slow method so subsequent haystack reads are always aligned.
-    // Guaranteed to have at least two aligned blocks
-    if len >= ALIGNMENT * 3 - 1 {
-        // Regardless of whether or not the base pointer is aligned to a 32-byte address, we are
-        // reading from an arbitrary offset (determined by the length of the lines) and so we must
-        // first calculate a safe place to begin using SIMD operations from.
-        let align_offset = unsafe { ptr.add(len) }.align_offset(ALIGNMENT);
-        if align_offset != 0 {
-            let aligned_index = len + align_offset - ALIGNMENT;
-            debug_assert!(aligned_index < len && aligned_index > 0);
-            debug_assert!((ptr as usize + aligned_index) % ALIGNMENT == 0);
-
-            // eprintln!("Unoptimized search from {} to {}", aligned_index, last_printed);
-            slow_search_and_print(bytes, aligned_index, len, &mut last_printed, separator, &mut output)?;
-            remaining = aligned_index;
-        } else {
-            // `bytes` end in an aligned block, no need to offset
-            debug_assert!((ptr as usize + len) % ALIGNMENT == 0);
-        }
-
-        let pattern256 = unsafe { _mm256_set1_epi8(separator as i8) };
-        while remaining >= SIZE as usize {
-            let window_end_offset = remaining;
-            unsafe {
-                remaining -= 32;
-                let search256 = _mm256_load_si256(ptr.add(remaining) as *const __m256i);
-                let result256 = _mm256_cmpeq_epi8(search256, pattern256);
-                let part = _mm256_movemask_epi8(result256) as u32;
-                let mut matches;
-
-                // For 32-bit x86 architecture only one part can be loaded. 64-bit x86_64 can load another part
-                // to find the matches.
-                #[cfg(target_arch = "x86")]
-                {
-                    matches = part;
-                }
-                #[cfg(target_arch = "x86_64")]
-                {
-                    remaining -= 32;
-                    let search256 = _mm256_load_si256(ptr.add(remaining) as *const __m256i);
-                    let result256 = _mm256_cmpeq_epi8(search256, pattern256);
-                    matches = ((part as u64) << 32) | _mm256_movemask_epi8(result256) as u32 as u64;
-                }
+#[cfg(feature = "std")]
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("reversal cancelled")
+    }
+}
 
-                while matches != 0 {
-                    // We would count *trailing* zeroes to find new lines in reverse order, but the
-                    // result mask is in little endian (reversed) order, so we do the very
-                    // opposite.
-                    // core::intrinsics::ctlz() is not stabilized, but `u64::leading_zeros()` will
-                    // use it directly if the lzcnt or bmi1 features are enabled.
-                    let leading = matches.leading_zeros();
-                    let offset = window_end_offset - leading as usize;
-
-                    output.write_all(&bytes[offset..last_printed])?;
-                    last_printed = offset;
-
-                    // Clear this match from the matches bitset.
-                    #[cfg(target_arch = "x86")]
-                    {
-                        matches = _bzhi_u32(matches, SIZE - 1 - leading);
-                    }
-                    #[cfg(target_arch = "x86_64")]
-                    {
-                        matches = _bzhi_u64(matches, SIZE - 1 - leading);
-                    }
-                }
-            }
+#[cfg(feature = "std")]
+impl std::error::Error for Cancelled {}
+
+/// Whether `error` means that whatever was reading the output went away, e.g. `tac big.log | head`
+/// once `head` has exited, rather than that reading or writing really failed.
+///
+/// Writing into a pipe or socket closed at the other end fails with
+/// [`ErrorKind::BrokenPipe`](std::io::ErrorKind::BrokenPipe), which is usually reported as is or
+/// wrapped into another I/O error; both are recognized. Callers typically stop reversing without
+/// reporting anything, as a reversal returning such an error has already stopped writing. Pass
+/// [`Error::io`] for an error returned by the library.
+///
+/// ## Example
+///
+/// ```
+/// use std::io::{Error, ErrorKind};
+/// use tac_k_lib::is_broken_pipe;
+///
+/// assert!(is_broken_pipe(&ErrorKind::BrokenPipe.into()));
+/// assert!(is_broken_pipe(&Error::new(ErrorKind::Other, Error::from(ErrorKind::BrokenPipe))));
+/// assert!(!is_broken_pipe(&ErrorKind::NotFound.into()));
+/// ```
+#[cfg(feature = "std")]
+pub fn is_broken_pipe(error: &std::io::Error) -> bool {
+    let mut error = error;
+    loop {
+        if error.kind() == std::io::ErrorKind::BrokenPipe {
+            return true;
+        }
+        let inner = error.get_ref().and_then(|inner| inner.downcast_ref::<std::io::Error>());
+        match inner.or_else(|| Error::of(error).map(Error::io)) {
+            Some(inner) => error = inner,
+            None => return false,
+        }
+    }
+}
+
+/// Fill `buf` from `reader` as far as possible, returning fewer bytes than its length only at the end
+/// of the input.
+#[cfg(feature = "std")]
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut total_read = 0;
+    while total_read < buf.len() {
+        match reader.read(&mut buf[total_read..])? {
+            0 => break,
+            bytes_read => total_read += bytes_read,
         }
     }
+    Ok(total_read)
+}
 
-    if remaining != 0 {
-        // eprintln!("Unoptimized end search from {} to {}", 0, index);
-        slow_search_and_print(bytes, 0, remaining, &mut last_printed, separator, &mut output)?;
+/// Reverse the first `len` bytes of `file` into `outputs` by reading it backwards in chunks, keeping
+/// the length of the input, number of records and bytes written and when reversing started in
+/// `reversed`.
+#[cfg(feature = "std")]
+fn reverse_backwards(
+    outputs: &mut [Output],
+    file: &mut File,
+    len: u64,
+    path: Option<&Path>,
+    options: &ReverseOptions,
+    reversed: &mut Option<(u64, u64, u64, Option<Instant>)>,
+) -> Result<()> {
+    let mut head = vec![];
+    if options.detect_crlf {
+        head.resize(len.min(options::CRLF_WINDOW as u64) as usize, 0);
+        read_full(file, &mut head)?;
     }
+    let detected = options.for_input(&head);
+    let options = &*detected;
+    let chunk_size = options.buffer_size();
+    let lines = count_lines(options, len, |emitter| {
+        chunked::reverse_backwards(file, len, chunk_size, &options.separator, emitter)
+    })?;
+    let reversing = now();
+    let mut emitter = Emitter::fanout(outputs, len, options).label(path).numbered(lines);
+    let result = chunked::reverse_backwards(file, len, chunk_size, &options.separator, &mut emitter)
+        .and_then(|()| emitter.finish());
+    *reversed = Some((len, emitter.records, emitter.written(), reversing));
+    result
+}
 
-    // Regardless of whether or not `index` is zero, as this is predicated on `last_printed`
-    output.write_all(&bytes[..last_printed])?;
+/// `stdin` along with its length, if it is redirected from a regular file, which can then be read
+/// backwards like one. Like a mapping of `stdin`, that is the whole file whatever has already been
+/// read from it.
+#[cfg(any(target_family = "unix", windows))]
+#[cfg(feature = "std")]
+fn seekable_stdin() -> Option<(std::mem::ManuallyDrop<File>, u64)> {
+    let mut file = borrow_stdin();
+    let metadata = file.metadata().ok()?;
+    // Some character devices claim to be seekable, but only regular files have a length.
+    (metadata.is_file() && file.stream_position().is_ok()).then_some((file, metadata.len()))
+}
 
-    Ok(())
+#[cfg(not(any(target_family = "unix", windows)))]
+#[cfg(feature = "std")]
+fn seekable_stdin() -> Option<(std::mem::ManuallyDrop<File>, u64)> {
+    None
 }
 
-#[cfg(target_arch = "aarch64")]
-#[target_feature(enable = "neon")]
-/// This is a NEON/AdvSIMD-optimized newline search function that searches a 16-byte (128-bit) window
-/// instead of scanning character-by-character (once aligned).
-unsafe fn search128(bytes: &[u8], separator: u8, mut output: &mut dyn Write) -> Result<()> {
-    use core::arch::aarch64::*;
+/// `stdin` as a `File`, which must not be closed on drop since `stdin` still owns it.
+#[cfg(target_family = "unix")]
+#[cfg(feature = "std")]
+fn borrow_stdin() -> std::mem::ManuallyDrop<File> {
+    use std::os::fd::{AsRawFd, FromRawFd};
 
-    let ptr = bytes.as_ptr();
-    let mut last_printed = bytes.len();
-    let mut index = last_printed - 1;
-
-    if index >= 64 {
-        // ARMv8 loads do not have alignment *requirements*, but there can be performance penalties
-        // (e.g. seems to be about 2% slowdown on Cortex-A72 with a 500MB file) so let's align.
-        // Search unaligned bytes via slow method so subsequent haystack reads are always aligned.
-        let align_offset = unsafe { ptr.add(index).align_offset(16) };
-        let aligned_index = index + align_offset - 16;
-
-        // eprintln!("Unoptimized search from {} to {}", aligned_index, last_printed);
-        slow_search_and_print(
-            bytes,
-            aligned_index,
-            last_printed,
-            &mut last_printed,
-            separator,
-            &mut output,
-        )?;
-        index = aligned_index;
-
-        let pattern128 = unsafe { vdupq_n_u8(separator) };
-        while index >= 64 {
-            let window_end_offset = index;
-            unsafe {
-                index -= 16;
-                let window = ptr.add(index);
-                let search128 = vld1q_u8(window);
-                let result128_0 = vceqq_u8(search128, pattern128);
-
-                index -= 16;
-                let window = ptr.add(index);
-                let search128 = vld1q_u8(window);
-                let result128_1 = vceqq_u8(search128, pattern128);
-
-                index -= 16;
-                let window = ptr.add(index);
-                let search128 = vld1q_u8(window);
-                let result128_2 = vceqq_u8(search128, pattern128);
-
-                index -= 16;
-                let window = ptr.add(index);
-                let search128 = vld1q_u8(window);
-                let result128_3 = vceqq_u8(search128, pattern128);
-
-                // Bulk movemask as described in
-                // https://branchfree.org/2019/04/01/fitting-my-head-through-the-arm-holes/
-                let mut matches = {
-                    let bit_mask: uint8x16_t = std::mem::transmute([
-                        0x01u8, 0x02, 0x4, 0x8, 0x10, 0x20, 0x40, 0x80, 0x01, 0x02, 0x4, 0x8, 0x10, 0x20, 0x40, 0x80,
-                    ]);
-                    let t0 = vandq_u8(result128_3, bit_mask);
-                    let t1 = vandq_u8(result128_2, bit_mask);
-                    let t2 = vandq_u8(result128_1, bit_mask);
-                    let t3 = vandq_u8(result128_0, bit_mask);
-                    let sum0 = vpaddq_u8(t0, t1);
-                    let sum1 = vpaddq_u8(t2, t3);
-                    let sum0 = vpaddq_u8(sum0, sum1);
-                    let sum0 = vpaddq_u8(sum0, sum0);
-                    vgetq_lane_u64(vreinterpretq_u64_u8(sum0), 0)
-                };
+    std::mem::ManuallyDrop::new(unsafe { File::from_raw_fd(std::io::stdin().as_raw_fd()) })
+}
 
-                while matches != 0 {
-                    // We would count *trailing* zeroes to find new lines in reverse order, but the
-                    // result mask is in little endian (reversed) order, so we do the very
-                    // opposite.
-                    let leading = matches.leading_zeros();
-                    let offset = window_end_offset - leading as usize;
+/// `stdin` as a `File`, which must not be closed on drop since `stdin` still owns it.
+#[cfg(windows)]
+#[cfg(feature = "std")]
+fn borrow_stdin() -> std::mem::ManuallyDrop<File> {
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
 
-                    output.write_all(&bytes[offset..last_printed])?;
-                    last_printed = offset;
+    std::mem::ManuallyDrop::new(unsafe { File::from_raw_handle(std::io::stdin().as_raw_handle()) })
+}
 
-                    // Clear this match from the matches bitset.
-                    matches &= !(1 << (64 - leading - 1));
-                }
+/// Memory-map `file`, which is `len` bytes long, and return the mapping along with its offset in
+/// `file`. That is only the end of `file` holding the records left in by
+/// [`ReverseOptions::max_records`] if the other options allow finding them up front, so that a
+/// large file is not mapped as a whole for a few records.
+#[cfg(feature = "std")]
+fn map_file(file: &File, len: u64, options: &ReverseOptions) -> Result<(Mmap, u64)> {
+    let separator = match (&options.separator, options.max_records) {
+        // Numbering counts every record, the record matching `from_match` may be anywhere, and the
+        // leading fragment written first is at the start of the input.
+        (Separator::Bytes(separator), Some(_))
+            if !options.number
+                && options.from_match.is_none()
+                && !options.detect_crlf
+                && (!options.before || options.leading_fragment != Fragment::First) =>
+        {
+            separator
+        }
+        _ => return Ok((unsafe { Mmap::map(file) }?, 0)),
+    };
+    // The mapping starts by the separator of the record before the ones left in, one more to find.
+    let records = options
+        .max_records
+        .unwrap_or_default()
+        .saturating_add(options.skip_records)
+        .saturating_add(1);
+    let mut window = TAIL_WINDOW;
+    loop {
+        let start = len.saturating_sub(window);
+        let mapped = unsafe { memmap2::MmapOptions::new().offset(start).map(file) }?;
+        // Separators are found from the end, so none but one cut off by the start of the mapping
+        // is missed or found elsewhere than in the whole file.
+        let mut found = 0;
+        let mut tail = None;
+        let result = search::search_separator(&mapped, separator, &mut |end| {
+            found += 1;
+            if found == records {
+                let at = if options.before { end - separator.len() } else { end };
+                tail = Some(start + at as u64);
+                return Err(Stop::error());
+            }
+            Ok(())
+        });
+        match result {
+            Err(e) if !Stop::is(&e) => return Err(e),
+            _ => {}
+        }
+        match tail {
+            Some(tail) if tail == start => return Ok((mapped, start)),
+            Some(tail) => {
+                let mapped = unsafe { memmap2::MmapOptions::new().offset(tail).map(file) }?;
+                return Ok((mapped, tail));
             }
+            None if start == 0 => return Ok((mapped, 0)),
+            None => window = window.saturating_mul(2),
         }
     }
+}
 
-    if index != 0 {
-        // eprintln!("Unoptimized end search from {} to {}", 0, index);
-        slow_search_and_print(bytes, 0, index, &mut last_printed, separator, &mut output)?;
-    }
+/// Memory-map `stdin`, which only succeeds if it is redirected from a file.
+#[cfg(target_family = "unix")]
+#[cfg(feature = "std")]
+fn map_stdin() -> Result<Mmap> {
+    unsafe { Mmap::map(&std::io::stdin()) }
+}
 
-    // Regardless of whether or not `index` is zero, as this is predicated on `last_printed`
-    output.write_all(&bytes[0..last_printed])?;
+/// Memory-map `stdin`, which only succeeds if it is redirected from a file.
+///
+/// Only the handle of a disk file can be mapped, so that of a pipe or a console is turned away
+/// before even trying.
+#[cfg(windows)]
+#[cfg(feature = "std")]
+fn map_stdin() -> Result<Mmap> {
+    let file = borrow_stdin();
+    if !file.metadata()?.is_file() {
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not a file"));
+    }
+    unsafe { Mmap::map(&*file) }
+}
 
-    Ok(())
+#[cfg(not(any(target_family = "unix", windows)))]
+#[cfg(feature = "std")]
+fn map_stdin() -> Result<Mmap> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
 }
 
-#[cfg(test)]
-mod tests {
-    #[allow(unused_imports)]
-    use super::*;
+/// Whether the file described by `metadata` is a stream that cannot be memory-mapped, like a pipe
+/// (including the `/dev/fd/N` paths of process substitutions), a character device or a socket.
+///
+/// Block devices are mapped like regular files.
+#[cfg(feature = "std")]
+fn is_stream(metadata: &std::fs::Metadata) -> bool {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::FileTypeExt;
 
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    #[cfg(target_os = "linux")]
+        !metadata.is_file() && !metadata.file_type().is_block_device()
+    }
+    #[cfg(not(target_family = "unix"))]
+    !metadata.is_file()
+}
+
+/// Name of the network (or FUSE) filesystem `file` resides on, if any.
+///
+/// Such files are read rather than memory-mapped, as a mapping over a flaky mount can hang the
+/// process or kill it with `SIGBUS` when the file becomes unavailable.
+#[cfg(target_os = "linux")]
+#[cfg(feature = "std")]
+fn network_fs(file: &File) -> Option<&'static str> {
+    use std::os::fd::AsRawFd;
+
+    let mut stat = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    if unsafe { libc::fstatfs(file.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // The type of `f_type` differs between architectures, but all magic numbers fit in 32 bits.
+    match unsafe { stat.assume_init() }.f_type as u32 {
+        0x6969 => Some("NFS"),
+        0x517b | 0xfe53_4d42 | 0xff53_4d42 => Some("SMB/CIFS"),
+        0x6573_5546 => Some("FUSE"),
+        0x00c3_6400 => Some("Ceph"),
+        0x0102_1997 => Some("9P"),
+        0x5346_414f => Some("AFS"),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[cfg(feature = "std")]
+fn network_fs(_file: &File) -> Option<&'static str> {
+    None
+}
+
+/// `stdin` reader that optionally gives up once no data has arrived for `timeout`.
+#[cfg(feature = "std")]
+struct StdinReader {
+    stdin: std::io::Stdin,
+    #[cfg_attr(not(target_family = "unix"), allow(dead_code))]
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "std")]
+impl StdinReader {
+    fn new(timeout: Option<Duration>) -> Result<Self> {
+        #[cfg(not(target_family = "unix"))]
+        if timeout.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "stdin timeout is only supported on Unix",
+            ));
+        }
+
+        Ok(StdinReader {
+            stdin: std::io::stdin(),
+            timeout,
+        })
+    }
+
+    #[cfg(target_family = "unix")]
+    fn wait(&self, timeout: Duration) -> Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let mut pollfd = libc::pollfd {
+            fd: self.stdin.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+                -1 => {
+                    let err = std::io::Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("no data received on stdin for {:?}", timeout),
+                    ))
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        #[cfg(target_family = "unix")]
+        if let Some(timeout) = self.timeout {
+            use std::os::fd::{AsRawFd, FromRawFd};
+
+            self.wait(timeout)?;
+            // Bypass the buffering of `Stdin`, otherwise data could be left sitting in its buffer
+            // while we wait for the descriptor to become readable again.
+            let mut stdin = std::mem::ManuallyDrop::new(unsafe { File::from_raw_fd(self.stdin.as_raw_fd()) });
+            return stdin.read(buf);
+        }
+        self.stdin.read(buf)
+    }
+}
+
+/// How [`reverse_file`] would access an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub enum InputKind {
+    /// The input can be memory-mapped and reversed in place.
+    Mapped,
+    /// The input is a file small enough for reading it in one go to be cheaper than mapping it,
+    /// see [`ReverseOptions::mmap_threshold`].
+    Read,
+    /// The input cannot be memory-mapped (e.g. a pipe) and has to be buffered first, in memory up
+    /// to [`InputInfo::buffer_size`] and, depending on the [`SpillPolicy`], in a temporary file
+    /// beyond that.
+    Buffered,
+    /// The input is a file that cannot be memory-mapped (e.g. as it is larger than the address
+    /// space), resides on a network filesystem where mapping it is unsafe, or memory-mapping is
+    /// disabled, see [`ReverseOptions::mmap`]. It is read backwards in chunks of
+    /// [`InputInfo::buffer_size`] bytes instead.
+    Backwards,
+}
+
+/// Description of an input as inspected by [`probe_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub struct InputInfo {
+    /// How the input would be accessed.
+    pub kind: InputKind,
+    /// Length of the input in bytes, if known without consuming it.
+    pub len: Option<u64>,
+    /// Offset of the last occurrence of the separator, if the input could be inspected and
+    /// contains it at all.
+    pub last_separator: Option<u64>,
+    /// Size of the in-memory buffer used before spilling to a temporary file, see
+    /// [`ReverseOptions::spill_policy`].
+    pub buffer_size: usize,
+}
+
+/// Inspect the input [`reverse_file`] would read from `path` without producing any output.
+///
+/// The input is opened and, where possible, mapped or read exactly as [`reverse_file`] would do,
+/// so any error it would run into while opening the input is reported here instead. Inputs that
+/// have to be buffered (e.g. piped `stdin`) are never consumed, so their length and content are
+/// unknown. Use [`ReverseOptions::probe`] to inspect an input as other options would access it.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{probe_file, InputKind};
+///
+/// let path = std::env::temp_dir().join("tac-k-probe.txt");
+/// std::fs::write(&path, "one\ntwo\n").unwrap();
+///
+/// let info = probe_file(Some(&path), b'\n').unwrap();
+/// assert_eq!(info.kind, InputKind::Read);
+/// assert_eq!(info.last_separator, Some(7));
+/// ```
+#[cfg(feature = "std")]
+pub fn probe_file<P: AsRef<Path>>(path: Option<P>, separator: u8) -> std::result::Result<InputInfo, Error> {
+    probe(
+        path.as_ref().map(AsRef::as_ref),
+        &ReverseOptions::new().separator(separator),
+    )
+    .map_err(Error::from)
+}
+
+#[cfg(feature = "std")]
+fn probe(path: Option<&Path>, options: &ReverseOptions) -> Result<InputInfo> {
+    let scanned = |kind, bytes: &[u8]| InputInfo {
+        kind,
+        len: Some(bytes.len() as u64),
+        last_separator: options.separator.rfind(bytes).map(|found| found.start as u64),
+        buffer_size: options.buffer_size(),
+    };
+
+    match path {
+        None => {
+            if options.mmap {
+                if let Ok(mmap) = map_stdin() {
+                    return Ok(scanned(InputKind::Mapped, &mmap));
+                }
+            }
+
+            Ok(InputInfo {
+                kind: InputKind::Buffered,
+                len: None,
+                last_separator: None,
+                buffer_size: options.buffer_size(),
+            })
+        }
+        Some(path) => {
+            #[cfg(feature = "zip")]
+            if let Some((archive_path, name)) = archive::split_member(path) {
+                let mut archive = archive::open(archive_path)?;
+                return Ok(InputInfo {
+                    kind: InputKind::Buffered,
+                    len: Some(archive::member(&mut archive, archive_path, name)?.size()),
+                    last_separator: None,
+                    buffer_size: options.buffer_size(),
+                });
+            }
+            #[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+            if decompress::open(path)?.is_some() {
+                return Ok(InputInfo {
+                    kind: InputKind::Buffered,
+                    len: None,
+                    last_separator: None,
+                    buffer_size: options.buffer_size(),
+                });
+            }
+            let mut file = File::open(path)?;
+            let metadata = file.metadata()?;
+            if metadata.is_file() && metadata.len() < options.mmap_threshold {
+                let mut bytes = Vec::new();
+                try_reserve(&mut bytes, metadata.len() as usize)?;
+                file.read_to_end(&mut bytes)?;
+                return Ok(scanned(InputKind::Read, &bytes));
+            }
+            if is_stream(&metadata) || (!options.mmap && !metadata.is_file()) {
+                return Ok(InputInfo {
+                    kind: InputKind::Buffered,
+                    len: None,
+                    last_separator: None,
+                    buffer_size: options.buffer_size(),
+                });
+            }
+            let backwards = InputInfo {
+                kind: InputKind::Backwards,
+                len: Some(metadata.len()),
+                last_separator: None,
+                buffer_size: options.buffer_size(),
+            };
+            if !options.mmap || network_fs(&file).is_some() {
+                return Ok(backwards);
+            }
+            match unsafe { Mmap::map(&file) } {
+                Ok(mmap) => Ok(scanned(InputKind::Mapped, &mmap)),
+                Err(_) if metadata.is_file() => Ok(backwards),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+/// Make room for `additional` more bytes in `buf`, failing with [`ErrorKind::OutOfMemory`] instead of
+/// aborting the process if they cannot be allocated.
+///
+/// [`ErrorKind::OutOfMemory`]: std::io::ErrorKind::OutOfMemory
+#[cfg(feature = "std")]
+fn try_reserve(buf: &mut Vec<u8>, additional: usize) -> Result<()> {
+    buf.try_reserve(additional).map_err(|e| out_of_memory(additional, e))
+}
+
+/// Error for failing to allocate `additional` bytes because of `e`.
+#[cfg(feature = "std")]
+fn out_of_memory(additional: usize, e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::OutOfMemory,
+        format!("cannot allocate a buffer of {additional} bytes ({e})"),
+    )
+}
+
+/// Widen `range`, clamped to `bytes`, to the start of the first record and the end of the last
+/// record it overlaps, with records terminated by (or starting with, if `before`) `separator`.
+#[cfg(feature = "std")]
+fn snap_range(bytes: &[u8], range: Range<u64>, separator: &[u8], before: bool) -> Range<usize> {
+    let end = range.end.min(bytes.len() as u64) as usize;
+    let start = range.start.min(end as u64) as usize;
+    if start == end {
+        return start..start;
+    }
+    let find = |from: usize| {
+        let from = from.min(bytes.len());
+        bytes[from..]
+            .windows(separator.len())
+            .position(|window| window == separator)
+            .map(|index| from + index)
+    };
+    if before {
+        // A separator starting at `start` may extend past it.
+        let prefix = &bytes[..(start + separator.len()).min(bytes.len())];
+        let start = rfind(prefix, separator).unwrap_or(0);
+        start..find(end).unwrap_or(bytes.len())
+    } else {
+        let start = rfind(&bytes[..start], separator).map_or(0, |index| index + separator.len());
+        // The separator ending the last record may start before `end`.
+        let end = find(end.saturating_sub(separator.len())).map_or(bytes.len(), |index| index + separator.len());
+        start..end
+    }
+}
+
+/// Emit every record of `bytes`, last record first. `base` is the offset of `bytes` in the input.
+#[cfg(feature = "std")]
+fn emit_reversed(bytes: &[u8], base: u64, separator: &Separator, emitter: &mut Emitter) -> Result<()> {
+    if base == 0 {
+        emitter.lead(bytes)?;
+    }
+    let leading = emit_records(bytes, bytes.len(), base, separator, emitter)?;
+    if leading != 0 {
+        emitter.record(&bytes[..leading], base)?;
+    }
+    Ok(())
+}
+
+/// Emit every record of `bytes` that is delimited by a separator, last record first, and return
+/// the length of the leading fragment, which is left unemitted. `base` is the offset of `bytes` in
+/// the input. Only separators within the first `searched` bytes are looked for, the rest being
+/// known not to hold any.
+///
+/// The leading fragment is whatever precedes the first record that starts right after a separator,
+/// or with one, see [`ReverseOptions::before`]. In the latter case, a separator right at the start
+/// of `bytes` is left in the fragment as well, since it might continue in the bytes preceding them.
+#[cfg(feature = "std")]
+fn emit_records(
+    bytes: &[u8],
+    searched: usize,
+    base: u64,
+    separator: &Separator,
+    emitter: &mut Emitter,
+) -> Result<usize> {
+    if let (true, Some(&[separator])) = (emitter.is_direct(), separator.bytes()) {
+        return emitter.emit_direct(bytes, searched, separator);
+    }
+    let before = emitter.before();
+    let mut last_printed = bytes.len();
+    let batch_size = emitter.batch_size();
+    let mut batch = Vec::with_capacity(batch_size);
+    separator.search(&bytes[..searched], &mut |found| {
+        let start = if before { found.start } else { found.end };
+        if start != 0 || !before {
+            batch.push((&bytes[start..last_printed], base + start as u64));
+            last_printed = start;
+            if batch.len() == batch_size {
+                emitter.records(&batch)?;
+                batch.clear();
+            }
+        }
+        Ok(())
+    })?;
+    emitter.records(&batch)?;
+    Ok(last_printed)
+}
+
+/// A search implementation to use instead of the one picked automatically, see
+/// [`ReverseOptions::engine`].
+///
+/// Every engine exists on every target, but only those the machine supports can be used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg(feature = "std")]
+pub enum Engine {
+    /// The implementation picked by [`calibrate_search`] if it has run, and the widest SIMD one
+    /// this machine supports otherwise.
+    #[default]
+    Auto,
+    /// Byte by byte, supported everywhere.
+    Scalar,
+    /// AVX-512 on x86_64, with the `avx512` feature.
+    Avx512,
+    /// AVX2 on x86 and x86_64.
+    Avx2,
+    /// SSE2 on x86 and x86_64.
+    Sse2,
+    /// NEON on aarch64.
+    Neon,
+    /// The vector extension on riscv64 Linux.
+    Rvv,
+    /// SIMD128 on wasm32, if enabled at compile time.
+    Simd128,
+    /// `memchr::memrchr`, with the `memchr` feature.
+    Memchr,
+}
+
+#[cfg(feature = "std")]
+impl Engine {
+    /// Name of the engine, e.g. `"AVX2"`, as reported by [`Capabilities::simd`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Auto => "auto",
+            Engine::Scalar => "scalar",
+            Engine::Avx512 => "AVX-512",
+            Engine::Avx2 => "AVX2",
+            Engine::Sse2 => "SSE2",
+            Engine::Neon => "NEON",
+            Engine::Rvv => "RVV",
+            Engine::Simd128 => "SIMD128",
+            Engine::Memchr => "memchr",
+        }
+    }
+
+    /// Whether this machine supports the engine, always the case for [`Auto`](Engine::Auto).
+    pub fn is_supported(self) -> bool {
+        self == Engine::Auto || self.backend().is_some()
+    }
+
+    /// The backend implementing the engine, if compiled in and supported by this machine.
+    fn backend(self) -> Option<Backend> {
+        let backend = match self {
+            Engine::Scalar => Backend::Scalar,
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Engine::Avx512 => Backend::Avx512,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Engine::Avx2 => Backend::Avx2,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Engine::Sse2 => Backend::Sse2,
+            #[cfg(target_arch = "aarch64")]
+            Engine::Neon => Backend::Neon,
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Engine::Rvv => Backend::Rvv,
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Engine::Simd128 => Backend::Simd128,
+            #[cfg(feature = "memchr")]
+            Engine::Memchr => Backend::Memchr,
+            _ => return None,
+        };
+        backend.supported().then_some(backend)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    use crate::search::PINNED;
+    use std::cell::Cell;
+
+    /// Assert that every search implementation finds the same occurrences of `separator` in `buf`
+    /// as the byte-by-byte one, also when the search is aborted after each of them.
+    fn assert_engines_agree(buf: &[u8], separator: u8) {
+        use crate::search::search;
+
+        let found = |backend: Backend, limit: usize| {
+            let mut found = Vec::new();
+            let _ = backend.search(buf, separator, &mut |offset| {
+                found.push(offset);
+                match found.len() == limit {
+                    true => Err(crate::search::abort()),
+                    false => Ok(()),
+                }
+            });
+            found
+        };
+        let mut expected = Vec::new();
+        search(buf, separator, &mut |offset| {
+            expected.push(offset);
+            Ok(())
+        })
+        .unwrap();
+        for backend in Backend::available() {
+            assert_eq!(found(backend, usize::MAX), expected, "{}", backend.name());
+            // Aborting right after a match found in the middle of a vector.
+            let limits = [1, 2, expected.len() / 2, expected.len()];
+            for limit in limits.into_iter().filter(|limit| (1..=expected.len()).contains(limit)) {
+                assert_eq!(found(backend, limit), expected[..limit], "{} aborted", backend.name());
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
     #[test]
-    fn test_x86_simd() {
+    fn test_simd() {
         let mut file = File::open("/dev/urandom").unwrap();
         let mut buffer = [0; 1023];
-        for _ in 0..100_000 {
-            test(&buffer);
+        for round in 0..100_000 {
+            // Vary where the haystack starts relative to the alignment of SIMD loads.
+            assert_engines_agree(&buffer[round % 64..], b'.');
             file.read_exact(&mut buffer).unwrap();
         }
+    }
 
-        fn test(buf: &[u8]) {
-            let mut slow_result = Vec::new();
-            let mut simd_result = Vec::new();
-            search(buf, b'.', &mut slow_result).unwrap();
-            unsafe { search256(buf, b'.', &mut simd_result).unwrap() };
-            assert_eq!(slow_result, simd_result);
+    #[test]
+    fn test_search_edge_cases() {
+        // Every length up to a few vectors of the widest implementation, at alignments on both sides
+        // of the vector sizes.
+        let mut buffer = vec![0; 64 + 320];
+        for separator in [b'\n', 0, 0xff] {
+            let other = separator.wrapping_add(1);
+            let patterns: [&dyn Fn(usize, usize) -> bool; 5] = [
+                &|_, _| true,
+                &|_, _| false,
+                &|index, len| index == 0 || index + 1 == len,
+                &|index, _| index % 2 == 0,
+                &|index, _| index % 63 == 62,
+            ];
+            for pattern in patterns {
+                for offset in [0, 1, 7, 15, 16, 17, 31, 32, 33, 63] {
+                    for len in 0..=320 {
+                        let buf = &mut buffer[offset..offset + len];
+                        for (index, byte) in buf.iter_mut().enumerate() {
+                            *byte = if pattern(index, len) { separator } else { other };
+                        }
+                        assert_engines_agree(buf, separator);
+                    }
+                }
+            }
         }
     }
+
+    #[test]
+    fn test_small_inputs() {
+        // Every input up to a few vectors long takes the small-input path of some engine, or its
+        // first aligned window: all of them must reverse it like `split_inclusive` does.
+        let mut buffer = vec![0; 16 + 256];
+        for len in 0..=256 {
+            for offset in [0, 1, 15] {
+                let buf = &mut buffer[offset..offset + len];
+                for (index, byte) in buf.iter_mut().enumerate() {
+                    *byte = if index % 5 == 4 || index + 1 == len {
+                        b'\n'
+                    } else {
+                        b'a'
+                    };
+                }
+                for bytes in [&buf[..], &buf[..len.saturating_sub(1)]] {
+                    let mut expected: Vec<&[u8]> = bytes.split_inclusive(|&byte| byte == b'\n').collect();
+                    expected.reverse();
+                    let expected = expected.concat();
+
+                    let mut output = vec![];
+                    reverse_bytes(&mut output, bytes, b'\n').unwrap();
+                    assert_eq!(output, expected, "{len} bytes at {offset}");
+                    for engine in crate::bench::engines() {
+                        let mut output = vec![];
+                        ReverseOptions::new()
+                            .engine(engine)
+                            .run_bytes(&mut output, bytes)
+                            .unwrap();
+                        assert_eq!(output, expected, "{len} bytes at {offset} with {}", engine.name());
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    proptest::proptest! {
+        #[test]
+        fn test_search_differential(
+            bytes in proptest::collection::vec(proptest::sample::select(&b"\n\0\x80\xffa"[..]), 0..600),
+            offset in 0usize..64,
+            separator in proptest::sample::select(&b"\n\0\x80\xff"[..]),
+        ) {
+            let buffer = [&vec![b'a'; offset][..], &bytes].concat();
+            assert_engines_agree(&buffer[offset..], separator);
+        }
+
+        #[test]
+        fn test_reverse_differential(
+            bytes in proptest::collection::vec(proptest::sample::select(&b"\nab"[..]), 0..600),
+        ) {
+            let mut expected: Vec<&[u8]> = bytes.split_inclusive(|&byte| byte == b'\n').collect();
+            expected.reverse();
+            let mut output = vec![];
+            reverse_bytes(&mut output, &bytes, b'\n').unwrap();
+            proptest::prop_assert_eq!(output, expected.concat());
+        }
+
+        #[test]
+        fn test_chunked_differential(
+            bytes in proptest::collection::vec(proptest::sample::select(&b"ab\n"[..]), 0..300),
+            separator in proptest::sample::select(vec![&b"\n"[..], b"a", b"aa", b"aba", b"ab\n"]),
+            before: bool,
+            chunk_size in 1..20usize,
+        ) {
+            let options = ReverseOptions::new().separator_bytes(separator).before(before);
+            let len = bytes.len() as u64;
+            let mut expected = vec![];
+            let mut emitter = Emitter::new(&mut expected, len, &options);
+            emit_reversed(&bytes, 0, &options.separator, &mut emitter).unwrap();
+            drop(emitter);
+            let mut output = vec![];
+            let mut emitter = Emitter::new(&mut output, len, &options);
+            let mut reverser = chunked::ChunkedReverser::new(&options.separator, len);
+            for chunk in bytes.rchunks(chunk_size) {
+                reverser.feed(chunk, &mut emitter).unwrap();
+            }
+            reverser.finish(&mut emitter).unwrap();
+            drop(emitter);
+            proptest::prop_assert_eq!(output, expected);
+        }
+
+        #[cfg(any(feature = "lz4", feature = "encrypt"))]
+        #[test]
+        fn test_record_counter(
+            bytes in proptest::collection::vec(proptest::sample::select(&b"ab\n"[..]), 0..300),
+            separator in proptest::sample::select(vec![&b"\n"[..], b"a", b"ab", b"ab\n", b"aa", b"aba"]),
+            before: bool,
+            chunk_size in 1..20usize,
+        ) {
+            let options = ReverseOptions::new().separator_bytes(separator).before(before);
+            let Some(mut counter) = separator::RecordCounter::new(&options.separator, before) else {
+                // Separators overlapping themselves are counted by reversing the input.
+                proptest::prop_assert!(separator == b"aa" || separator == b"aba");
+                return Ok(());
+            };
+            for chunk in bytes.chunks(chunk_size) {
+                counter.feed(chunk);
+            }
+            let expected = count_lines(&options.clone().number(true), bytes.len() as u64, |emitter| {
+                emit_reversed(&bytes, 0, &options.separator, emitter)
+            });
+            proptest::prop_assert_eq!(counter.records(), expected.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_chunked_long_record() {
+        // Records spanning thousands of chunks, which must not be searched again for every one.
+        let record = b"x".repeat(4 * 1024 * 1024);
+        let input = [&b"one\n"[..], &record, b"\ntwo\n", &record, b"--\n", &record].concat();
+        for (separator, before) in [(&b"\n"[..], false), (b"\n", true), (b"--\n", false), (b"--\n", true)] {
+            let options = ReverseOptions::new().separator_bytes(separator).before(before);
+            let len = input.len() as u64;
+            let mut expected = vec![];
+            let mut emitter = Emitter::new(&mut expected, len, &options);
+            emit_reversed(&input, 0, &options.separator, &mut emitter).unwrap();
+            drop(emitter);
+            let mut output = vec![];
+            let mut emitter = Emitter::new(&mut output, len, &options);
+            let mut reverser = chunked::ChunkedReverser::new(&options.separator, len);
+            for chunk in input.rchunks(1000) {
+                reverser.feed(chunk, &mut emitter).unwrap();
+            }
+            reverser.finish(&mut emitter).unwrap();
+            drop(emitter);
+            assert!(output == expected, "{separator:?}, before: {before}");
+        }
+    }
+
+    #[cfg(any(feature = "lz4", feature = "encrypt"))]
+    #[test]
+    fn test_chunked() {
+        let inputs: [(&[u8], &[u8]); 2] = [
+            (b"one\ntwo\n\nthree four\nfive\nsix seven eight\nnine", b"\n"),
+            (b"one\r\ntwo\r\n\r\nthree\rfour\r\nfive\n\r\nsix", b"\r\n"),
+        ];
+        for ((input, separator), before) in inputs.into_iter().flat_map(|input| [(input, false), (input, true)]) {
+            let options = ReverseOptions::new().separator_bytes(separator).before(before);
+            let len = input.len() as u64;
+            let mut expected = Vec::new();
+            emit_reversed(
+                input,
+                0,
+                &options.separator,
+                &mut Emitter::new(&mut expected, len, &options),
+            )
+            .unwrap();
+
+            for chunk_size in 1..input.len() + 1 {
+                let mut result = Vec::new();
+                let mut emitter = Emitter::new(&mut result, len, &options);
+                let mut reverser = chunked::ChunkedReverser::new(&options.separator, len);
+                for chunk in input.rchunks(chunk_size) {
+                    reverser.feed(chunk, &mut emitter).unwrap();
+                }
+                reverser.finish(&mut emitter).unwrap();
+                drop(emitter);
+                assert_eq!(expected, result, "chunk size {chunk_size}, before: {before}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_byte_separator() {
+        let reverse = |input: &[u8], separator: &[u8]| {
+            let options = ReverseOptions::new().separator_bytes(separator);
+            let mut result = Vec::new();
+            let mut emitter = Emitter::new(&mut result, input.len() as u64, &options);
+            emit_reversed(input, 0, &options.separator, &mut emitter).unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+            result
+        };
+        assert_eq!(reverse(b"a\r\nb\nc\r\n", b"\r\n"), b"b\nc\r\na\r\n");
+        assert_eq!(reverse(b"x---y--z---", b"---"), b"y--z---x---");
+        // Of overlapping occurrences, the last one wins.
+        assert_eq!(reverse(b"baaa", b"aa"), b"baaa");
+        assert_eq!(reverse(b"baaab", b"aa"), b"bbaaa");
+    }
+
+    #[test]
+    fn test_detect_crlf() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-crlf-{}.txt", std::process::id()));
+        let options = ReverseOptions::new()
+            .detect_crlf(true)
+            .fragment(Fragment::Last)
+            .characters(Characters::Bytes);
+        let reversed = |input: &[u8]| {
+            std::fs::write(&path, input).unwrap();
+            let mut mapped = vec![];
+            options.run(&mut mapped, Some(&path)).unwrap();
+            let mut backwards = vec![];
+            let unmapped = options.clone().mmap(false).mmap_threshold(0).max_buf_size(4);
+            unmapped.run(&mut backwards, Some(&path)).unwrap();
+            let mut buffered = vec![];
+            unmapped.run_reader(&mut buffered, &mut &input[..]).unwrap();
+            assert_eq!(mapped, backwards);
+            assert_eq!(mapped, buffered);
+            mapped
+        };
+
+        assert_eq!(reversed(b"ab\r\ncd\r\nef"), b"dc\r\nba\r\nfe");
+        assert_eq!(reversed(b"ab\ncd\r\nef"), b"\rdc\nba\nfe");
+        assert_eq!(reversed(b"ab\r\rcd"), b"dc\r\rba");
+        std::fs::remove_file(&path).unwrap();
+
+        let mut result = vec![];
+        let options = ReverseOptions::new().detect_crlf(true).separator(b';');
+        options.run_bytes(&mut result, b"a\r\nb;c;").unwrap();
+        assert_eq!(result, b"c;a\r\nb;");
+    }
+
+    #[test]
+    #[cfg(feature = "encoding")]
+    fn test_encoding() {
+        use encoding_rs::{UTF_16LE, UTF_8};
+
+        let utf16le = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let utf16be = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_be_bytes).collect() };
+        let path = std::env::temp_dir().join(format!("tac-k-test-encoding-{}.txt", std::process::id()));
+        let reversed = |options: &ReverseOptions, input: &[u8]| {
+            std::fs::write(&path, input).unwrap();
+            let mut result = vec![];
+            options.run(&mut result, Some(&path)).unwrap();
+            let mut spilled = vec![];
+            let spilling = options.clone().max_buf_size(16);
+            spilling.run_reader(&mut spilled, &mut &input[..]).unwrap();
+            assert_eq!(result, spilled);
+            result
+        };
+
+        let options = ReverseOptions::new().encoding(UTF_16LE);
+        let input = [&b"\xff\xfe"[..], &utf16le("un\r\ndeux\r\ntrois \u{1F600}\r\n")].concat();
+        assert_eq!(reversed(&options, &input), utf16le("trois \u{1F600}\r\ndeux\r\nun\r\n"));
+        let options = options.utf8_output(true).detect_crlf(true).characters(Characters::Utf8);
+        assert_eq!(
+            reversed(&options, &input),
+            "\u{1F600} siort\r\nxued\r\nnu\r\n".as_bytes()
+        );
+
+        // The byte order mark tells UTF-16 apart, and is what the output is encoded in.
+        let options = ReverseOptions::new().encoding(UTF_8).number(true);
+        let input = [&b"\xfe\xff"[..], &utf16be("α\nβ")].concat();
+        assert_eq!(reversed(&options, &input), utf16be("     2\tβ     1\tα\n"));
+        assert_eq!(
+            reversed(&options, b"a\xffb\nc\n"),
+            "     2\tc\n     1\ta\u{FFFD}b\n".as_bytes()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "regex", any(feature = "lz4", feature = "encrypt")))]
+    fn test_regex_separator() {
+        let input = b"one;;two;\nthree;;;four";
+        let cases: [(bool, &[u8]); 2] = [(false, b"\nthree;;;two;one;;four"), (true, b";;;four;\nthree;;twoone")];
+        let regex = regex::bytes::Regex::new(";+|x*").unwrap();
+        for (before, expected) in cases {
+            let options = ReverseOptions::new()
+                .separator_regex(regex.clone())
+                .fragment(Fragment::Last)
+                .before(before);
+            let len = input.len() as u64;
+            for chunk_size in 1..input.len() + 1 {
+                let mut result = Vec::new();
+                let mut emitter = Emitter::new(&mut result, len, &options);
+                let mut reverser = chunked::ChunkedReverser::new(&options.separator, len);
+                for chunk in input.rchunks(chunk_size) {
+                    reverser.feed(chunk, &mut emitter).unwrap();
+                }
+                reverser.finish(&mut emitter).unwrap();
+                emitter.finish().unwrap();
+                drop(emitter);
+                assert_eq!(result, expected, "chunk size {chunk_size}, before: {before}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let path = std::env::temp_dir().join(format!(".tac-test-overwrite-{}", std::process::id()));
+        std::fs::write(&path, b"sensitive\ndata\n").unwrap();
+        spill::overwrite(&path).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content, [0; 15]);
+    }
+
+    #[test]
+    fn test_spill_file_in_dir() {
+        let dir = std::env::temp_dir();
+        let first = SpillFile::in_dir(&dir).unwrap().path.unwrap();
+        let second = SpillFile::in_dir(&dir).unwrap().path.unwrap();
+        #[cfg(target_family = "unix")]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&first).unwrap().permissions());
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+
+        assert_ne!(first, second);
+        #[cfg(target_family = "unix")]
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_progress() {
+        let input = b"0123456789abcde\n".repeat(4 * 1024 * 1024);
+        let len = input.len() as u64;
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = ReverseOptions::new().on_progress({
+            let reports = reports.clone();
+            move |done, total| reports.lock().unwrap().push((done, total))
+        });
+
+        let mut sink = std::io::sink();
+        for (options, count) in [(options.clone(), 4), (options.progress_interval(len / 8), 8)] {
+            reports.lock().unwrap().clear();
+            let mut emitter = Emitter::new(&mut sink, len, &options);
+            emit_reversed(&input, 0, &options.separator, &mut emitter).unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+
+            let reports = reports.lock().unwrap();
+            assert_eq!(reports.len(), count);
+            assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+            assert_eq!(reports.last(), Some(&(len, len)));
+        }
+    }
+
+    #[test]
+    fn test_allocation_failure() {
+        let mut buf = vec![0u8];
+        let err = try_reserve(&mut buf, usize::MAX).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::OutOfMemory);
+        assert_eq!(buf, [0]);
+    }
+
+    #[test]
+    fn test_check_utf8() {
+        // A three-byte sequence split across the chunks the input is validated in.
+        let mut input = b"x\n".repeat(512 * 1024);
+        input.pop();
+        input.extend_from_slice("\u{20ac}\nabc\n".as_bytes());
+        let newline = Separator::Bytes(vec![b'\n']);
+        let check = |input: &[u8]| utf8::check_reader(&mut &input[..], &newline, false).unwrap();
+        assert_eq!(check(&input), None);
+
+        let len = input.len() as u64;
+        input.extend_from_slice(b"de\xe2\x82");
+        let truncated = Some(InvalidUtf8 {
+            offset: len + 2,
+            record_offset: len,
+        });
+        assert_eq!(check(&input), truncated);
+        input.push(b'\n');
+        assert_eq!(check(&input), truncated);
+        assert_eq!(utf8::check_bytes(&input, &newline, false), truncated);
+    }
+
+    #[test]
+    fn test_until_match() {
+        let reversed = |input: &[u8], options: ReverseOptions| {
+            let mut result = vec![];
+            options.run_bytes(&mut result, input).unwrap();
+            result
+        };
+        let until = |pattern: &str, inclusive| ReverseOptions::new().until_match(pattern, inclusive);
+        let input = b"one\ntwo\nthree\nfour\n";
+
+        assert_eq!(reversed(input, until("two", true)), b"four\nthree\ntwo\n");
+        assert_eq!(reversed(input, until("two", false)), b"four\nthree\n");
+        // The first record reached is the last one of the input.
+        assert_eq!(reversed(input, until("four", true)), b"four\n");
+        assert_eq!(reversed(input, until("four", false)), b"");
+        // Without a match, every record is written.
+        assert_eq!(reversed(input, until("five", true)), b"four\nthree\ntwo\none\n");
+        assert_eq!(reversed(input, until("five", false)), b"four\nthree\ntwo\none\n");
+        // Separators are not part of what is matched.
+        assert_eq!(reversed(input, until("e\n", true)), b"four\nthree\ntwo\none\n");
+        assert_eq!(reversed(b"one\ntwo", until("two", true)), b"two");
+    }
+
+    #[test]
+    fn test_from_match() {
+        let reversed = |input: &[u8], options: ReverseOptions| {
+            let mut result = vec![];
+            options.run_bytes(&mut result, input).unwrap();
+            result
+        };
+        let from = |pattern: &str, inclusive| ReverseOptions::new().from_match(pattern, inclusive);
+        let input = b"one\ntwo\nthree\nfour\n";
+
+        assert_eq!(reversed(input, from("three", true)), b"three\ntwo\none\n");
+        assert_eq!(reversed(input, from("three", false)), b"two\none\n");
+        assert_eq!(reversed(input, from("four", false)), b"three\ntwo\none\n");
+        assert_eq!(reversed(input, from("one", false)), b"");
+        // Nothing is written if the marker never occurs, separators not being part of what is
+        // matched.
+        assert_eq!(reversed(input, from("five", true)), b"");
+        assert_eq!(reversed(input, from("e\nt", true)), b"");
+        // Records are only skipped from the marker on, which counts if it is written.
+        assert_eq!(reversed(input, from("three", true).skip_records(1)), b"two\none\n");
+        assert_eq!(reversed(input, from("three", false).skip_records(1)), b"one\n");
+        assert_eq!(
+            reversed(input, from("four", true).skip_records(1).max_records(1)),
+            b"three\n"
+        );
+        assert_eq!(reversed(input, from("five", true).skip_records(1)), b"");
+    }
+
+    #[test]
+    fn test_context() {
+        let input = b"1\n2\n3\n4\n5\n6\n7\n8\n";
+        let reversed = |options: ReverseOptions| {
+            let mut result = vec![];
+            options.run_bytes(&mut result, input).unwrap();
+            result
+        };
+        let until = |pattern: &str, inclusive| ReverseOptions::new().until_match(pattern, inclusive);
+        let from = |pattern: &str, inclusive| ReverseOptions::new().from_match(pattern, inclusive);
+
+        assert_eq!(reversed(until("5", true).context(0, 2)), b"8\n7\n6\n5\n4\n3\n");
+        assert_eq!(reversed(until("5", false).context(0, 2)), b"8\n7\n6\n4\n3\n");
+        assert_eq!(reversed(until("7", true).context(0, 10)), b"8\n7\n6\n5\n4\n3\n2\n1\n");
+        assert_eq!(
+            reversed(until("5", true).context(0, 2).max_records(5)),
+            b"8\n7\n6\n5\n4\n"
+        );
+        assert_eq!(reversed(from("4", true).context(2, 0)), b"6\n5\n4\n3\n2\n1\n");
+        assert_eq!(reversed(from("4", false).context(2, 0)), b"6\n5\n3\n2\n1\n");
+        assert_eq!(reversed(from("7", true).context(5, 0)), b"8\n7\n6\n5\n4\n3\n2\n1\n");
+        assert_eq!(reversed(from("9", true).context(5, 0)), b"");
+        // Context records keep their line numbers.
+        assert_eq!(
+            reversed(from("3", true).context(1, 0).number(true)),
+            b"     4\t4\n     3\t3\n     2\t2\n     1\t1\n"
+        );
+        assert_eq!(
+            reversed(from("6", true).until_match("3", true).context(1, 1)),
+            b"7\n6\n5\n4\n3\n2\n"
+        );
+
+        let filtered = |accept: &'static [&[u8]], preceding, following| {
+            let options = ReverseOptions::new().context(preceding, following);
+            let (mut all, mut filtered) = (vec![], vec![]);
+            let mut outputs = [
+                Output::new(&mut all),
+                Output::new(&mut filtered).filter(|record| accept.contains(&record)),
+            ];
+            let mut emitter = Emitter::fanout(&mut outputs, input.len() as u64, &options);
+            emit_reversed(input, 0, &options.separator, &mut emitter).unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+            drop(outputs);
+            assert_eq!(all, b"8\n7\n6\n5\n4\n3\n2\n1\n");
+            filtered
+        };
+        assert_eq!(filtered(&[b"6", b"2"], 1, 1), b"7\n6\n5\n3\n2\n1\n");
+        assert_eq!(filtered(&[b"5"], 2, 0), b"7\n6\n5\n");
+        assert_eq!(filtered(&[b"5"], 0, 2), b"5\n4\n3\n");
+        // Overlapping context is written once.
+        assert_eq!(filtered(&[b"7", b"5"], 1, 1), b"8\n7\n6\n5\n4\n");
+        assert_eq!(filtered(&[b"9"], 2, 2), b"");
+    }
+
+    #[test]
+    fn test_fragment() {
+        let reversed = |input: &[u8], fragment| {
+            let options = ReverseOptions::new().fragment(fragment);
+            let mut output = vec![];
+            let mut emitter = Emitter::new(&mut output, input.len() as u64, &options);
+            emit_reversed(input, 0, &options.separator, &mut emitter).unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+            output
+        };
+
+        assert_eq!(reversed(b"one\ntwo\nthree", Fragment::First), b"threetwo\none\n");
+        assert_eq!(reversed(b"one\ntwo\nthree", Fragment::Last), b"two\none\nthree");
+        assert_eq!(reversed(b"one\ntwo\nthree", Fragment::Drop), b"two\none\n");
+        assert_eq!(reversed(b"one\ntwo\n", Fragment::Drop), b"two\none\n");
+        assert_eq!(reversed(b"one", Fragment::Last), b"one");
+    }
+
+    #[test]
+    fn test_leading_fragment() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-leading-{}.txt", std::process::id()));
+        let reversed = |input: &[u8], options: ReverseOptions| {
+            let options = options.before(true);
+            std::fs::write(&path, input).unwrap();
+            let mut mapped = vec![];
+            options.run(&mut mapped, Some(&path)).unwrap();
+            let unmapped = options.clone().mmap(false).mmap_threshold(0).max_buf_size(4);
+            let mut backwards = vec![];
+            unmapped.run(&mut backwards, Some(&path)).unwrap();
+            let mut spilled = vec![];
+            unmapped.run_reader(&mut spilled, &mut &input[..]).unwrap();
+            let mut parallel = vec![];
+            options
+                .clone()
+                .threads(2)
+                .chunk_size(4)
+                .run_bytes(&mut parallel, input)
+                .unwrap();
+            assert_eq!(mapped, backwards);
+            assert_eq!(mapped, spilled);
+            assert_eq!(mapped, parallel);
+            #[cfg(feature = "lz4")]
+            {
+                let mut blocks = vec![];
+                unmapped
+                    .compress_spill(true)
+                    .run_reader(&mut blocks, &mut &input[..])
+                    .unwrap();
+                assert_eq!(mapped, blocks);
+            }
+            mapped
+        };
+        let first = || ReverseOptions::new().leading_fragment(Fragment::First);
+
+        assert_eq!(reversed(b"zero\none\ntwo", ReverseOptions::new()), b"\ntwo\nonezero");
+        assert_eq!(reversed(b"zero\none\ntwo", first()), b"zero\ntwo\none");
+        let drop = ReverseOptions::new().leading_fragment(Fragment::Drop);
+        assert_eq!(reversed(b"zero\none\ntwo", drop), b"\ntwo\none");
+        // There is none if the input starts with a separator.
+        assert_eq!(reversed(b"\none\ntwo", first()), b"\ntwo\none");
+        assert_eq!(reversed(b"zero", first()), b"zero");
+        assert_eq!(reversed(b"zero the fragment\none", first()), b"zero the fragment\none");
+        // A separator straddling the chunks read.
+        let crlf = || first().separator_bytes(b"\r\n");
+        assert_eq!(reversed(b"zer\r\none\r\ntwo", crlf()), b"zer\r\ntwo\r\none");
+        // Written first, it is the first record to be counted or numbered.
+        assert_eq!(reversed(b"zero\none\ntwo", first().max_records(2)), b"zero\ntwo");
+        assert_eq!(reversed(b"zero\none\ntwo", first().skip_records(1)), b"\ntwo\none");
+        assert_eq!(
+            reversed(b"zero\none\ntwo", first().number(true)),
+            b"     1\tzero     3\t\ntwo     2\t\none"
+        );
+        assert_eq!(reversed(b"zero\none\ntwo", first().until_match(b"zero", true)), b"zero");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trailing_separator() {
+        let reversed = |input: &[u8], fragment, trailing| {
+            let options = ReverseOptions::new().fragment(fragment).trailing_separator(trailing);
+            let mut output = vec![];
+            options.run_bytes(&mut output, input).unwrap();
+            let mut filtered = vec![];
+            let mut outputs = [Output::new(&mut filtered).filter(|_| true)];
+            let mut emitter = Emitter::fanout(&mut outputs, input.len() as u64, &options);
+            emit_reversed(input, 0, &options.separator, &mut emitter).unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+            drop(outputs);
+            assert_eq!(output, filtered);
+            output
+        };
+        let ensure = TrailingSeparator::Ensure;
+        let strip = TrailingSeparator::Strip;
+
+        assert_eq!(
+            reversed(b"one\ntwo\nthree", Fragment::First, ensure),
+            b"three\ntwo\none\n"
+        );
+        assert_eq!(
+            reversed(b"one\ntwo\nthree", Fragment::Last, ensure),
+            b"two\none\nthree\n"
+        );
+        assert_eq!(reversed(b"one\ntwo\nthree", Fragment::Drop, ensure), b"two\none\n");
+        assert_eq!(reversed(b"one\ntwo\n", Fragment::First, ensure), b"two\none\n");
+        assert_eq!(reversed(b"one\ntwo\n", Fragment::First, strip), b"two\none");
+        assert_eq!(reversed(b"one\ntwo\nthree", Fragment::Last, strip), b"two\none\nthree");
+        assert_eq!(reversed(b"\none\n", Fragment::First, strip), b"one\n");
+
+        let mut output = vec![];
+        ReverseOptions::new()
+            .trailing_separator(ensure)
+            .max_buf_size(4)
+            .run_reader(&mut output, &mut &b"one\ntwo\nthree"[..])
+            .unwrap();
+        assert_eq!(output, b"three\ntwo\none\n");
+    }
+
+    #[test]
+    fn test_number() {
+        let input: Vec<u8> = (1..=20_000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        let expected: Vec<u8> = (1..=20_000)
+            .rev()
+            .flat_map(|i| format!("{i:>6}\t{i}\n").into_bytes())
+            .collect();
+        let path = std::env::temp_dir().join(format!("tac-k-test-number-{}.txt", std::process::id()));
+        std::fs::write(&path, &input).unwrap();
+        let options = ReverseOptions::new().number(true);
+
+        let run = |options: ReverseOptions| {
+            let mut output = vec![];
+            options.run(&mut output, Some(&path)).unwrap();
+            output
+        };
+        let mapped = run(options.clone());
+        let backwards = run(options.clone().mmap(false).mmap_threshold(0).max_buf_size(1024));
+        let limited = run(options.clone().skip_records(1).max_records(2));
+        let mut buffered = vec![];
+        options
+            .clone()
+            .max_buf_size(1024)
+            .run_reader(&mut buffered, &mut &input[..])
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // Counted while spilling them, rather than by reading the blocks back twice.
+        #[cfg(feature = "lz4")]
+        for separator in [&b"\n"[..], b"0\n", b"00"] {
+            let options = options.clone().separator_bytes(separator);
+            let mut spilled = vec![];
+            options
+                .clone()
+                .max_buf_size(1024)
+                .compress_spill(true)
+                .run_reader(&mut spilled, &mut &input[..])
+                .unwrap();
+            let mut expected = vec![];
+            options.run_bytes(&mut expected, &input).unwrap();
+            assert!(spilled == expected, "{separator:?}");
+        }
+        let mut fragment = vec![];
+        options
+            .clone()
+            .fragment(Fragment::Last)
+            .run_bytes(&mut fragment, b"one\n\ntwo")
+            .unwrap();
+
+        assert_eq!(mapped, expected);
+        assert_eq!(backwards, expected);
+        assert_eq!(buffered, expected);
+        assert_eq!(limited, b" 19999\t19999\n 19998\t19998\n");
+        assert_eq!(fragment, b"     2\t\n     1\tone\n     3\ttwo");
+    }
+
+    #[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+    #[test]
+    fn test_run_spliced() {
+        use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+
+        let path = std::env::temp_dir().join(format!("tac-k-test-spliced-{}.txt", std::process::id()));
+        let input: Vec<u8> = (0..100_000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        std::fs::write(&path, &input).unwrap();
+        let mut expected = vec![];
+        reverse_bytes(&mut expected, &input, b'\n').unwrap();
+
+        for options in [ReverseOptions::new(), ReverseOptions::new().mmap(false)] {
+            let mut fds = [0; 2];
+            assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+            let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+            let reading = std::thread::spawn(move || {
+                let mut output = vec![];
+                File::from(reader).read_to_end(&mut output).map(|_| output)
+            });
+            options.run_spliced(writer.as_fd(), Some(&path)).unwrap();
+            drop(writer);
+            assert_eq!(reading.join().unwrap().unwrap(), expected);
+        }
+
+        // Not a pipe.
+        let output_path = path.with_extension("out");
+        let output = File::create(&output_path).unwrap();
+        ReverseOptions::new().run_spliced(output.as_fd(), Some(&path)).unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), expected);
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mmap_advice() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-advice-{}.txt", std::process::id()));
+        let input: Vec<u8> = (0..200_000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        std::fs::write(&path, &input).unwrap();
+        let mut expected = vec![];
+        reverse_bytes(&mut expected, &input, b'\n').unwrap();
+
+        for advice in [
+            MmapAdvice::Normal,
+            MmapAdvice::WillNeed,
+            MmapAdvice::Backwards(0),
+            MmapAdvice::Backwards(4096),
+            MmapAdvice::default(),
+        ] {
+            let mut output = vec![];
+            ReverseOptions::new()
+                .mmap_advice(advice)
+                .run(&mut output, Some(&path))
+                .unwrap();
+            assert!(output == expected, "{advice:?}");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_vectored_writes() {
+        /// Accepts at most 7 bytes per write, counting the writes.
+        struct Trickle {
+            output: Vec<u8>,
+            writes: usize,
+        }
+
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.write_vectored(&[std::io::IoSlice::new(buf)])
+            }
+
+            fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> Result<usize> {
+                self.writes += 1;
+                let mut written = 0;
+                for buf in bufs {
+                    let len = buf.len().min(7 - written);
+                    self.output.extend_from_slice(&buf[..len]);
+                    written += len;
+                }
+                Ok(written)
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        // The default options write each record as it is found instead (see `test_direct`).
+        let input: Vec<u8> = (0..1000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        for options in [
+            ReverseOptions::new().label(":"),
+            ReverseOptions::new().max_records(100),
+            ReverseOptions::new().until_match("500", true),
+            ReverseOptions::new().from_match("900", false).skip_records(3),
+        ] {
+            let mut expected = vec![];
+            options.run_bytes(&mut expected, &input).unwrap();
+            let mut trickle = Trickle {
+                output: vec![],
+                writes: 0,
+            };
+            options.run_bytes(&mut trickle, &input).unwrap();
+            assert_eq!(trickle.output, expected);
+            // Writes are only cut short at the end of a batch, never at a record boundary.
+            assert!(trickle.writes <= expected.len().div_ceil(7) + 1000 / emit::BATCH_SIZE + 1);
+        }
+    }
+
+    #[test]
+    fn test_line_buffered() {
+        /// Notes how much had been written at every flush.
+        #[derive(Default)]
+        struct Flushes {
+            output: Vec<u8>,
+            flushed: Vec<usize>,
+        }
+
+        impl Write for Flushes {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.output.write(buf)
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                self.flushed.push(self.output.len());
+                Ok(())
+            }
+        }
+
+        let input: Vec<u8> = (0..1000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        for options in [
+            ReverseOptions::new(),
+            ReverseOptions::new().label(":"),
+            ReverseOptions::new().number(true),
+            ReverseOptions::new().max_records(100),
+        ] {
+            let mut flushes = Flushes::default();
+            options
+                .clone()
+                .line_buffered(true)
+                .run_bytes(&mut flushes, &input)
+                .unwrap();
+            let mut expected = vec![];
+            options.run_bytes(&mut expected, &input).unwrap();
+            assert_eq!(flushes.output, expected);
+            // Every record is flushed as soon as it has been written.
+            let ends = (1..=expected.len()).filter(|&end| expected[end - 1] == b'\n');
+            assert!(
+                ends.into_iter().all(|end| flushes.flushed.contains(&end)),
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_records() {
+        let reversed = |input: &[u8], options: ReverseOptions| {
+            let mut output = vec![];
+            let mut emitter = Emitter::new(&mut output, input.len() as u64, &options);
+            match emit_reversed(input, 0, &options.separator, &mut emitter).and_then(|_| emitter.finish()) {
+                Err(e) if !Stop::is(&e) => panic!("{e}"),
+                _ => {}
+            }
+            drop(emitter);
+            output
+        };
+
+        assert_eq!(reversed(b"1\n2\n3\n", ReverseOptions::new().max_records(2)), b"3\n2\n");
+        assert_eq!(
+            reversed(b"1\n2\n3\n", ReverseOptions::new().max_records(5)),
+            b"3\n2\n1\n"
+        );
+        assert_eq!(reversed(b"1\n2\n3\n", ReverseOptions::new().max_records(0)), b"");
+        let options = ReverseOptions::new().max_records(1).fragment(Fragment::Last);
+        assert_eq!(reversed(b"1\n2\n3", options), b"2\n");
+        let options = ReverseOptions::new().skip_records(1).max_records(1);
+        assert_eq!(reversed(b"1\n2\n3\n", options), b"2\n");
+        assert_eq!(reversed(b"1\n2\n3\n", ReverseOptions::new().skip_records(5)), b"");
+    }
+
+    #[test]
+    fn test_tail_mapping() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-tail-{}.txt", std::process::id()));
+        // Short records, then one long enough that the window has to grow past it.
+        let mut input: Vec<u8> = (0..100_000).flat_map(|i| format!("{i}--\naa").into_bytes()).collect();
+        input.extend(std::iter::repeat_n(b'x', 3 * TAIL_WINDOW as usize));
+        input.extend(b"--\nlast--\naaa");
+        std::fs::write(&path, &input).unwrap();
+
+        for separator in [&b"\n"[..], b"--\n", b"aa"] {
+            for max in [0, 1, 2, 3, 50, 200_000] {
+                let options = ReverseOptions::new().separator_bytes(separator).max_records(max);
+                for options in [
+                    options.clone(),
+                    options.clone().before(true),
+                    options.clone().skip_records(2),
+                    options.clone().fragment(Fragment::Last),
+                ] {
+                    let mut expected = vec![];
+                    let mut emitter = Emitter::new(&mut expected, input.len() as u64, &options);
+                    match emit_reversed(&input, 0, &options.separator, &mut emitter).and_then(|_| emitter.finish()) {
+                        Err(e) if !Stop::is(&e) => panic!("{e}"),
+                        _ => {}
+                    }
+                    drop(emitter);
+                    let mut output = vec![];
+                    options.run(&mut output, Some(&path)).unwrap();
+                    assert!(output == expected, "{options:?}");
+                }
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let path = std::env::temp_dir().join("tac-k-test-stats.txt");
+        std::fs::write(&path, "one\ntwo\nthree").unwrap();
+        let stats = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = ReverseOptions::new().on_stats({
+            let stats = stats.clone();
+            move |s| stats.lock().unwrap().push(s.clone())
+        });
+
+        let returned = options.run_with_stats(&mut std::io::sink(), Some(&path)).unwrap();
+        options
+            .clone()
+            .max_records(1)
+            .run(&mut std::io::sink(), Some(&path))
+            .unwrap();
+        let input = b"one\ntwo\n".repeat(16);
+        options
+            .max_buf_size(16)
+            .run_reader(&mut std::io::sink(), &mut &input[..])
+            .unwrap();
+
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0], returned);
+        assert_eq!(stats[0].path.as_deref(), Some(&*path));
+        assert_eq!((stats[0].bytes, stats[0].bytes_written, stats[0].records), (13, 13, 3));
+        assert_eq!((stats[1].bytes, stats[1].bytes_written, stats[1].records), (13, 5, 1));
+        assert_eq!(stats[0].engine.name(), search_backend());
+        assert!(!stats[0].spilled && stats[2].spilled);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_fifo() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::env::temp_dir().join(format!("tac-k-test-fifo-{}", std::process::id()));
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let writer = std::thread::spawn({
+            let path = path.clone();
+            move || std::fs::write(path, "one\ntwo\n").unwrap()
+        });
+
+        let mut result = vec![];
+        let reversed = reverse_file(&mut result, Some(&path), b'\n');
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        reversed.unwrap();
+        assert_eq!(result, b"two\none\n");
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn test_zip_member() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-{}.zip", std::process::id()));
+        let mut archive = zip::ZipWriter::new(File::create(&path).unwrap());
+        archive.start_file("logs/app.log", Default::default()).unwrap();
+        archive.write_all(b"one\ntwo\nthree").unwrap();
+        archive.finish().unwrap();
+
+        let member = |name: &str| format!("{}:{name}", path.display());
+        let mut result = vec![];
+        reverse_file(&mut result, Some(member("logs/app.log")), b'\n').unwrap();
+        let probed = probe_file(Some(member("logs/app.log")), b'\n').unwrap();
+        let missing = reverse_file(&mut vec![], Some(member("app.log")), b'\n').unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result, b"threetwo\none\n");
+        assert_eq!((probed.kind, probed.len), (InputKind::Buffered, Some(13)));
+        assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+    fn test_decompress() {
+        // `one\ntwo\n` and `three\n` compressed separately and concatenated, with a skippable frame in
+        // between for zstd.
+        let inputs: &[(&str, &[u8])] = &[
+            #[cfg(feature = "gzip")]
+            (
+                "gz",
+                b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\xcb\xcf\x4b\xe5\x2a\x29\xcf\xe7\x02\x00\xe6\x0b\xba\x12\
+                \x08\x00\x00\x00\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\x2b\xc9\x28\x4a\x4d\xe5\x02\x00\xd8\xc5\
+                \x46\xff\x06\x00\x00\x00",
+            ),
+            #[cfg(feature = "zstd")]
+            (
+                "zst",
+                b"\x28\xb5\x2f\xfd\x20\x08\x41\x00\x00\x6f\x6e\x65\x0a\x74\x77\x6f\x0a\x50\x2a\x4d\x18\x02\x00\x00\
+                \x00\xaa\xbb\x28\xb5\x2f\xfd\x20\x06\x31\x00\x00\x74\x68\x72\x65\x65\x0a",
+            ),
+            #[cfg(feature = "xz")]
+            (
+                "xz",
+                b"\xfd\x37\x7a\x58\x5a\x00\x00\x00\xff\x12\xd9\x41\x04\xc0\x0c\x08\x21\x01\x16\x00\x00\x00\x00\x00\
+                \x00\x00\x00\x00\xac\x77\xaa\xa4\x01\x00\x07\x6f\x6e\x65\x0a\x74\x77\x6f\x0a\x00\x00\x01\x20\x08\
+                \xbb\x19\xd9\xbb\x06\x72\x9e\x7a\x01\x00\x00\x00\x00\x00\x59\x5a\xfd\x37\x7a\x58\x5a\x00\x00\x00\
+                \xff\x12\xd9\x41\x04\xc0\x0a\x06\x21\x01\x16\x00\x00\x00\x00\x00\x00\x00\x00\x00\xaa\x30\x8e\xa6\
+                \x01\x00\x05\x74\x68\x72\x65\x65\x0a\x00\x00\x00\x00\x01\x1e\x06\xc1\x2f\xa4\x1d\x06\x72\x9e\x7a\
+                \x01\x00\x00\x00\x00\x00\x59\x5a",
+            ),
+        ];
+
+        for (extension, compressed) in inputs {
+            let path = std::env::temp_dir().join(format!("tac-k-test-decompress-{}.{extension}", std::process::id()));
+            std::fs::write(&path, compressed).unwrap();
+            let mut result = vec![];
+            let reversed = reverse_file(&mut result, Some(&path), b'\n');
+            let probed = probe_file(Some(&path), b'\n');
+            std::fs::remove_file(&path).unwrap();
+
+            reversed.unwrap();
+            assert_eq!(result, b"three\ntwo\none\n", "{extension}");
+            assert_eq!(probed.unwrap().kind, InputKind::Buffered, "{extension}");
+        }
+    }
+
+    #[test]
+    fn test_reverse_reader() {
+        let input = b"one\ntwo\nthree".repeat(1024);
+        let mut expected = vec![];
+        emit_reversed(
+            &input,
+            0,
+            &Separator::Bytes(vec![b'\n']),
+            &mut Emitter::new(&mut expected, input.len() as u64, &ReverseOptions::new()),
+        )
+        .unwrap();
+
+        let mut in_memory = vec![];
+        reverse_reader(&mut in_memory, &mut &input[..], b'\n').unwrap();
+        let mut in_place = vec![];
+        reverse_bytes(&mut in_place, &input, b'\n').unwrap();
+        // Small enough a buffer for the input to be spilled.
+        let mut spilled = vec![];
+        ReverseOptions::new()
+            .max_buf_size(1024)
+            .run_reader(&mut spilled, &mut &input[..])
+            .unwrap();
+
+        assert_eq!(in_memory, expected);
+        assert_eq!(spilled, expected);
+        assert_eq!(in_place, expected);
+    }
+
+    #[test]
+    fn test_count_records() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-count-{}.txt", std::process::id()));
+        std::fs::write(&path, b"one\ntwo\n".repeat(64 * 1024)).unwrap();
+        let mapped = count_records(Some(&path), b'\n').unwrap();
+        let backwards = ReverseOptions::new()
+            .mmap(false)
+            .mmap_threshold(0)
+            .count(Some(&path))
+            .unwrap();
+        let limited = ReverseOptions::new().max_records(5).count(Some(&path)).unwrap();
+        std::fs::write(&path, b"one\ntwo").unwrap();
+        let unterminated = count_records(Some(&path), b'\n').unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mapped, 128 * 1024);
+        assert_eq!(backwards, 128 * 1024);
+        assert_eq!(limited, 5);
+        assert_eq!(unterminated, 2);
+    }
+
+    #[test]
+    fn test_reverse_into() {
+        struct Records(Vec<Vec<u8>>);
+
+        impl Sink for Records {
+            type Error = usize;
+
+            fn record(&mut self, record: &[u8]) -> std::result::Result<(), usize> {
+                if self.0.len() == 3 {
+                    return Err(self.0.len());
+                }
+                self.0.push(record.to_vec());
+                Ok(())
+            }
+        }
+
+        for input in [&b""[..], b"\n", b"one", b"one\n\ntwo", b"one\ntwo\n"] {
+            let mut records = Records(vec![]);
+            reverse_into(input, b'\n', &mut records).unwrap();
+            assert!(records.0.iter().map(Vec::as_slice).eq(rev_records(input, b'\n')));
+        }
+        let mut records = Records(vec![]);
+        assert_eq!(reverse_into(b"1\n2\n3\n4\n5\n", b'\n', &mut records), Err(3));
+        assert_eq!(records.0, [b"5\n", b"4\n", b"3\n"]);
+    }
+
+    #[test]
+    fn test_bench() {
+        for line_len in [0, 1, 80, 1 << 20] {
+            let input = bench::synthetic_input(10_000, line_len, 7);
+            assert_eq!(input.len(), 10_000);
+            assert_eq!(input.last(), Some(&b'\n'));
+            let lines = input.iter().filter(|&&byte| byte == b'\n').count();
+            assert_eq!(bench::search(Engine::Auto, &input, b'\n'), Some(lines));
+            assert!(line_len == 0 || lines < 10_000);
+            let times = bench::compare_engines(&input, b'\n', 1);
+            assert_eq!(times[0].0, Engine::Scalar);
+            assert_eq!(times.len(), bench::engines().len());
+        }
+        assert_eq!(bench::synthetic_input(100, 8, 1), bench::synthetic_input(100, 8, 1));
+    }
+
+    #[test]
+    fn test_direct() {
+        let input = bench::synthetic_input(100_000, 20, 3);
+        let mut expected = vec![];
+        bench::reverse_plain(&mut expected, &input, b'\n').unwrap();
+        let is_direct =
+            |options: &ReverseOptions| Emitter::new(&mut std::io::sink(), input.len() as u64, options).is_direct();
+
+        // The default options write records as they are found, and must stay that way.
+        let options = ReverseOptions::new();
+        assert!(is_direct(&options));
+        let mut output = vec![];
+        let mut emitter = Emitter::new(&mut output, input.len() as u64, &options);
+        emit_reversed(&input, 0, &options.separator, &mut emitter).unwrap();
+        emitter.finish().unwrap();
+        assert_eq!(
+            emitter.records as usize,
+            input.iter().filter(|&&byte| byte == b'\n').count()
+        );
+        assert_eq!(emitter.written(), input.len() as u64);
+        drop(emitter);
+        assert_eq!(output, expected);
+
+        for options in [
+            ReverseOptions::new().max_records(3),
+            ReverseOptions::new().skip_records(1),
+            ReverseOptions::new().until_match("a", true),
+            ReverseOptions::new().before(true),
+            ReverseOptions::new().number(true),
+            ReverseOptions::new().output_format(OutputFormat::Json),
+            ReverseOptions::new().trailing_separator(TrailingSeparator::Strip),
+            ReverseOptions::new().on_progress(|_, _| {}),
+        ] {
+            assert!(!is_direct(&options), "{options:?}");
+        }
+
+        // So does fanning out into a single unfiltered output, whatever it writes into (e.g. `--tee`).
+        let options = ReverseOptions::new();
+        let fanout = |outputs: &mut [Output]| Emitter::fanout(outputs, input.len() as u64, &options).is_direct();
+        let (mut first, mut second) = (std::io::sink(), std::io::sink());
+        assert!(fanout(&mut [Output::new(&mut first)]));
+        assert!(!fanout(&mut [Output::new(&mut first).filter(|_| true)]));
+        assert!(!fanout(&mut [Output::new(&mut first), Output::new(&mut second)]));
+    }
+
+    #[test]
+    fn test_engine() {
+        let input = b"one\ntwo\n".repeat(100);
+        let mut expected = vec![];
+        reverse_bytes(&mut expected, &input, b'\n').unwrap();
+        let engines = [
+            Engine::Auto,
+            Engine::Scalar,
+            Engine::Avx512,
+            Engine::Avx2,
+            Engine::Sse2,
+            Engine::Neon,
+            Engine::Rvv,
+            Engine::Simd128,
+            Engine::Memchr,
+        ];
+        for engine in engines {
+            let mut output = vec![];
+            let result = ReverseOptions::new().engine(engine).run_bytes(&mut output, &input);
+            if engine.is_supported() {
+                result.unwrap();
+                assert_eq!(output, expected, "{}", engine.name());
+            } else {
+                assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+            }
+        }
+        assert_eq!(PINNED.with(Cell::get), None);
+    }
+
+    #[test]
+    fn test_reverse_range() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-range-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\r\ntwo\r\nthree\r\nfour\r\n").unwrap();
+        let reversed = |range: Range<u64>, options: ReverseOptions| {
+            let mut output = vec![];
+            options
+                .separator_bytes(b"\r\n")
+                .run_range(&mut output, &path, range)
+                .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(reversed(5..10, ReverseOptions::new()), "two\r\n");
+        assert_eq!(reversed(5..11, ReverseOptions::new()), "three\r\ntwo\r\n");
+        // Both ends in the middle of a separator.
+        assert_eq!(reversed(4..9, ReverseOptions::new()), "two\r\none\r\n");
+        assert_eq!(reversed(10..100, ReverseOptions::new()), "four\r\nthree\r\n");
+        assert_eq!(reversed(3..3, ReverseOptions::new()), "");
+        assert_eq!(reversed(5..6, ReverseOptions::new().before(true)), "\r\ntwo");
+        assert_eq!(reversed(0..1, ReverseOptions::new().before(true)), "one");
+        assert_eq!(
+            reversed(5..6, ReverseOptions::new().output_format(OutputFormat::Ndjson)),
+            "{\"offset\":5,\"record\":\"two\"}\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"\0\t\n\r\\").unwrap(), b"\0\t\n\r\\");
+        assert_eq!(unescape(r"a\x2C\xffb").unwrap(), b"a,\xffb");
+        assert_eq!(unescape("。").unwrap(), "。".as_bytes());
+        for invalid in [r"\", r"\q", r"\x", r"\x1", r"\x+f", r"\xg0"] {
+            assert_eq!(unescape(invalid).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_characters() {
+        let input = b"ab\xc3\xa9\ncd\xff\xe2\x80\n\xf0\x9f\x98\x80x";
+        let rev = |characters| {
+            let mut output = vec![];
+            reverse_characters(&mut output, &input[..], b'\n', characters).unwrap();
+            output
+        };
+        let both = |options: ReverseOptions| {
+            let mut output = vec![];
+            options.run_bytes(&mut output, input).unwrap();
+            output
+        };
+
+        assert_eq!(rev(Characters::Utf8), b"\xc3\xa9ba\n\xe2\x80\xffdc\nx\xf0\x9f\x98\x80");
+        assert_eq!(rev(Characters::Bytes), b"\xa9\xc3ba\n\x80\xe2\xffdc\nx\x80\x98\x9f\xf0");
+        assert_eq!(
+            both(ReverseOptions::new().characters(Characters::Bytes)),
+            b"x\x80\x98\x9f\xf0\x80\xe2\xffdc\n\xa9\xc3ba\n"
+        );
+        assert_eq!(
+            both(ReverseOptions::new().characters(Characters::Utf8).before(true)),
+            b"\nx\xf0\x9f\x98\x80\n\xe2\x80\xffdc\xc3\xa9ba"
+        );
+    }
+
+    #[test]
+    fn test_open() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-open-{}.txt", std::process::id()));
+        let input = b"one\ntwo\n".repeat(64 * 1024);
+        std::fs::write(&path, &input).unwrap();
+        let mapped = ReverseOptions::new().open(Some(&path)).unwrap();
+        let read = ReverseOptions::new()
+            .mmap_threshold(u64::MAX)
+            .open(Some(&path))
+            .unwrap();
+        let unmapped = ReverseOptions::new().mmap(false).open(Some(&path)).unwrap();
+        let mut reversed = vec![];
+        ReverseOptions::new()
+            .mmap(false)
+            .max_buf_size(1024)
+            .run(&mut reversed, Some(&path))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(mapped.is_mapped() && !read.is_mapped() && !unmapped.is_mapped());
+        assert_eq!(&unmapped[..], &input[..]);
+        assert_eq!(reversed, b"two\none\n".repeat(64 * 1024));
+        assert_eq!((&mapped[..], &read[..]), (&input[..], &input[..]));
+        assert!(read.rev_records(b'\n').eq(rev_records(&input, b'\n')));
+    }
+
+    #[test]
+    fn test_parallel() {
+        let input = b"one\ntwo\n\nthree\n".repeat(2 * 1024 * 1024 / 15);
+        let short = &input[..1000];
+        let input = [&input[..], b"four"].concat();
+        let reverse = |input: &[u8], before, threads, chunk_size| {
+            let mut reversed = vec![];
+            let options = ReverseOptions::new().before(before);
+            let mut emitter = Emitter::new(&mut reversed, input.len() as u64, &options);
+            match threads {
+                1 => emit_reversed(input, 0, &options.separator, &mut emitter),
+                threads => parallel::emit_reversed(input, 0, b'\n', threads, chunk_size, &options, &mut emitter),
+            }
+            .unwrap();
+            emitter.finish().unwrap();
+            drop(emitter);
+            reversed
+        };
+
+        for before in [false, true] {
+            let expected = reverse(&input, before, 1, 0);
+            assert_eq!(reverse(&input, before, 2, 1024 * 1024), expected);
+            assert_eq!(reverse(&input, before, 5, 1024 * 1024), expected);
+            assert_eq!(reverse(&input, before, 3, 4095), expected);
+            // Chunks that split every record, down to a byte.
+            let expected = reverse(short, before, 1, 0);
+            for chunk_size in [1, 2, 7] {
+                assert_eq!(reverse(short, before, 4, chunk_size), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        assert_eq!(cache::parse_size("48K"), Some(48 * 1024));
+        assert_eq!(cache::parse_size("32M"), Some(32 * 1024 * 1024));
+        assert_eq!(cache::parse_size("512"), Some(512));
+        assert_eq!(cache::parse_size("1T"), None);
+        assert_eq!(cache::parse_size(""), None);
+
+        const MIB: usize = 1024 * 1024;
+        let sizes = |l2, l3, page| cache::CacheSizes { l2, l3, page };
+        // All the chunks in flight fit in the L3 cache.
+        assert_eq!(parallel::chunk_size(3, sizes(Some(MIB), Some(32 * MIB), 4096)), 8 * MIB);
+        assert_eq!(
+            parallel::chunk_size(3, sizes(Some(MIB), Some(30 * MIB + 1), 4096)),
+            30 * MIB / 4
+        );
+        // But are no smaller than the L2 cache, nor out of bounds.
+        assert_eq!(
+            parallel::chunk_size(63, sizes(Some(2 * MIB), Some(32 * MIB), 4096)),
+            2 * MIB
+        );
+        assert_eq!(parallel::chunk_size(63, sizes(None, Some(32 * MIB), 4096)), MIB);
+        assert_eq!(parallel::chunk_size(1, sizes(None, Some(512 * MIB), 4096)), 64 * MIB);
+        assert_eq!(
+            parallel::chunk_size(8, sizes(Some(3 * MIB / 2), None, 1 << 16)),
+            3 * MIB / 2
+        );
+        assert_eq!(parallel::chunk_size(8, sizes(None, None, 4096)), 16 * MIB);
+        // Chunks start on a page.
+        assert_eq!(
+            parallel::chunk_size(2, sizes(None, Some(10 * MIB), 1 << 16)) % (1 << 16),
+            0
+        );
+
+        let options = ReverseOptions::new();
+        assert_eq!(options.clone().chunk_size(1000).search_chunk_size(4), 1000);
+        assert_eq!(options.search_chunk_size(4) % cache::sizes().page, 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_numa() {
+        assert_eq!(numa::parse_list("0"), Some(vec![0]));
+        assert_eq!(numa::parse_list("0-3,8,10-11"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+        assert_eq!(numa::parse_list(""), Some(vec![]));
+        assert_eq!(numa::parse_list("0-x"), None);
+
+        // Binding to the only node there is, if the pages can be looked up at all, leaves the
+        // thread free to run.
+        let cpus = (0..std::thread::available_parallelism().unwrap().get() * 4).collect();
+        let bytes = vec![1; 1024 * 1024];
+        std::thread::spawn(move || {
+            numa::bind_to(&[cpus], &bytes);
+            assert_eq!(bytes.iter().map(|&b| b as usize).sum::<usize>(), bytes.len());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reverse_backwards() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-backwards-{}.txt", std::process::id()));
+        let input = b"one\n--\ntwo\n--\nthree".repeat(1000);
+        std::fs::write(&path, &input).unwrap();
+        let reverse = |options: ReverseOptions| {
+            let mut reversed = vec![];
+            options.run(&mut reversed, Some(&path)).unwrap();
+            reversed
+        };
+
+        for separator in [&b"\n"[..], b"--\n", b"three"] {
+            for before in [false, true] {
+                let mapped = ReverseOptions::new()
+                    .separator_bytes(separator)
+                    .before(before)
+                    .mmap_threshold(0);
+                // Chunks too small to hold a separator, let alone a record.
+                let backwards = mapped.clone().mmap(false).max_buf_size(2);
+                assert_eq!(reverse(backwards), reverse(mapped));
+            }
+        }
+        let probed = ReverseOptions::new()
+            .mmap(false)
+            .mmap_threshold(0)
+            .probe(Some(&path))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            (probed.kind, probed.len),
+            (InputKind::Backwards, Some(input.len() as u64))
+        );
+    }
+
+    #[test]
+    fn test_throughput() {
+        let start = std::time::Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut throughput = Throughput::new(Duration::from_secs(10));
+
+        throughput.update_at(at(0), 0, 1000);
+        assert_eq!(throughput.eta(), None);
+        throughput.update_at(at(5), 50, 1000);
+        assert_eq!(throughput.bytes_per_sec(), Some(10.0));
+        assert_eq!(throughput.eta(), Some(Duration::from_secs(95)));
+
+        // Only the last 10 seconds count once the throughput changes.
+        throughput.update_at(at(15), 250, 1000);
+        throughput.update_at(at(25), 450, 1000);
+        assert_eq!(throughput.bytes_per_sec(), Some(20.0));
+        assert_eq!(throughput.eta(), Some(Duration::from_millis(27500)));
+
+        // A new input starts over.
+        throughput.update_at(at(26), 10, 20);
+        assert_eq!(throughput.eta(), None);
+        throughput.update_at(at(27), 20, 20);
+        assert_eq!(throughput.eta(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_write_error_aborts_scan() {
+        struct Failing(usize);
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> Result<usize> {
+                self.0 += 1;
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let input = b"line\n".repeat(1000);
+        let options = ReverseOptions::new();
+        let mut output = Failing(0);
+        let mut emitter = Emitter::new(&mut output, input.len() as u64, &options);
+        let err = emit_reversed(&input, 0, &options.separator, &mut emitter).unwrap_err();
+        drop(emitter);
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(&err));
+        assert_eq!(output.0, 1);
+    }
+
+    #[test]
+    fn test_error() {
+        let dir = std::env::temp_dir().join(format!("tac-k-test-error-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input.txt");
+        std::fs::write(&path, b"one\ntwo\n".repeat(1024)).unwrap();
+
+        let missing = reverse_file(&mut vec![], Some(dir.join("missing.txt")), b'\n').unwrap_err();
+        let written = reverse_file(&mut [0; 4].as_mut_slice(), Some(&path), b'\n').unwrap_err();
+        let spilled = ReverseOptions::new()
+            .max_buf_size(16)
+            .spill_policy(SpillPolicy::TempFileIn(dir.join("missing")))
+            .run_reader(&mut vec![], &mut &b"one\ntwo\n".repeat(16)[..])
+            .unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(missing, Error::Open { .. }));
+        assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(missing.path(), Some(dir.join("missing.txt").as_path()));
+        assert!(matches!(written, Error::Write { .. }));
+        assert_eq!(written.kind(), std::io::ErrorKind::WriteZero);
+        assert!(matches!(spilled, Error::Spill { path: None, .. }));
+
+        // The underlying error is the source, not part of the message, and survives a round trip
+        // through an I/O error.
+        assert_eq!(missing.to_string(), "failed to open");
+        let source = std::error::Error::source(&missing).unwrap().to_string();
+        assert_eq!(source, missing.io().to_string());
+        let carried = std::io::Error::from(missing);
+        assert!(matches!(Error::of(&carried), Some(Error::Open { .. })));
+        assert!(matches!(Error::from(carried), Error::Open { .. }));
+        let other = Error::from(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        assert!(matches!(other, Error::Other(_)));
+        assert!(std::error::Error::source(&other).is_none());
+        assert_eq!(std::io::Error::from(other).kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_cancel() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        /// Sets the flag once `limit` bytes have been written, like another thread would.
+        struct Cancelling {
+            written: Vec<u8>,
+            flushed: usize,
+            limit: usize,
+            cancel: Arc<AtomicBool>,
+        }
+
+        impl Write for Cancelling {
+            fn write(&mut self, buf: &[u8]) -> Result<usize> {
+                self.written.extend_from_slice(buf);
+                if self.written.len() >= self.limit {
+                    self.cancel.store(true, Ordering::Relaxed);
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<()> {
+                self.flushed = self.written.len();
+                Ok(())
+            }
+        }
+
+        let input = b"line\n".repeat(1000);
+        let path = std::env::temp_dir().join(format!("tac-k-test-cancel-{}.txt", std::process::id()));
+        std::fs::write(&path, &input).unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ReverseOptions::new().cancel_on(cancel.clone());
+        let mut output = Cancelling {
+            written: vec![],
+            flushed: 0,
+            limit: 20,
+            cancel: cancel.clone(),
+        };
+        let summary = options.run_all(&mut output, &[Some(&path), Some(&path)]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((summary.inputs.len(), summary.skipped), (1, 1));
+        assert!(summary.errors().all(|(_, e)| Cancelled::is(e.io())));
+        assert!(output.written.len() >= 20 && output.written.len() < input.len());
+        assert_eq!(output.flushed, output.written.len());
+        cancel.store(false, Ordering::Relaxed);
+        assert!(options.run_bytes(&mut vec![], &input).is_ok());
+    }
+
+    #[test]
+    fn test_run_all() {
+        let path = std::env::temp_dir().join(format!("tac-k-test-run-all-{}.txt", std::process::id()));
+        std::fs::write(&path, b"one\ntwo\n").unwrap();
+        let missing = path.with_extension("missing");
+        let paths = [Some(&missing), Some(&path), None, Some(&path)];
+
+        let mut output = vec![];
+        let options = ReverseOptions::new();
+        let summary = options.run_all(&mut output, &paths[..2]);
+        assert!(!summary.is_success());
+        assert_eq!((summary.succeeded(), summary.failed(), summary.skipped), (1, 1, 0));
+        assert_eq!(
+            summary.errors().map(|(path, _)| path).collect::<Vec<_>>(),
+            [Some(missing.as_path())]
+        );
+        assert_eq!(output, b"two\none\n");
+
+        // Once the writer fails, the inputs left are skipped.
+        let mut output = [0; 4];
+        let summary = options.run_all(&mut &mut output[..], &paths[1..]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((summary.succeeded(), summary.failed(), summary.skipped), (0, 1, 2));
+        assert_eq!(summary.inputs[0].0.as_deref(), Some(path.as_path()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_run_all_parallel() {
+        let dir = std::env::temp_dir().join(format!("tac-k-test-run-all-parallel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut paths = vec![];
+        for i in 0..20 {
+            let path = dir.join(format!("{i}.txt"));
+            std::fs::write(&path, format!("{i}\n{i}{i}\n").repeat(i)).unwrap();
+            paths.push(Some(path));
+        }
+        // Too large to be reversed ahead, and missing.
+        let large = dir.join("large.txt");
+        std::fs::write(&large, b"one\ntwo\n".repeat(parallel::MAX_BUFFERED_FILE as usize / 4)).unwrap();
+        paths.insert(7, Some(large));
+        paths.insert(3, Some(dir.join("missing.txt")));
+
+        let run = |options: ReverseOptions| {
+            let mut output = vec![];
+            let summary = options.run_all(&mut output, &paths);
+            let results: Vec<_> = summary.inputs.iter().map(|(_, result)| result.is_ok()).collect();
+            (output, results, summary.skipped)
+        };
+        let expected = run(ReverseOptions::new().threads(1));
+        assert_eq!(expected.1.iter().filter(|ok| !**ok).count(), 1);
+        assert_eq!(run(ReverseOptions::new().threads(3)), expected);
+        assert_eq!(run(ReverseOptions::new().threads(64)), expected);
+        #[cfg(feature = "rayon")]
+        {
+            let pool = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+            assert_eq!(run(ReverseOptions::new().thread_pool(pool)), expected);
+            // Waiting on a thread of the pool runs the files queued on it.
+            let single = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+            let options = ReverseOptions::new().thread_pool(single.clone()).threads(4);
+            assert_eq!(single.install(|| run(options)), expected);
+        }
+
+        // Once the writer fails, the inputs left are skipped, even those already reversed.
+        let mut output = [0; 100];
+        let summary = ReverseOptions::new().threads(4).run_all(&mut &mut output[..], &paths);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(summary.inputs.len() + summary.skipped, paths.len());
+        assert!(summary.inputs.last().unwrap().1.is_err());
+        assert_eq!(summary.failed(), 2);
+    }
+
+    #[test]
+    fn test_rfind() {
+        fn naive(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+            let mut result = Vec::new();
+            let mut end = haystack.len();
+            while let Some(index) = haystack[..end].windows(needle.len()).rposition(|w| w == needle) {
+                result.push(index);
+                end = index;
+            }
+            result
+        }
+
+        let mut file = File::open("/dev/urandom").unwrap();
+        let mut buffer = [0; 300];
+        for _ in 0..10_000 {
+            file.read_exact(&mut buffer).unwrap();
+            // Narrow the alphabet so that needles actually occur.
+            let haystack: Vec<u8> = buffer.iter().map(|b| b'a' + b % 3).collect();
+            for needle in [&b"ab"[..], b"aa", b"cab", b"c"] {
+                let expected = naive(&haystack, needle);
+                assert_eq!(rfind(&haystack, needle), expected.first().copied());
+                assert_eq!(rfind_iter(&haystack, needle).collect::<Vec<_>>(), expected);
+            }
+            let mut indices = vec![];
+            for_each_separator_rev(&haystack, b'c', |index| indices.push(index));
+            assert_eq!(indices, naive(&haystack, b"c"));
+        }
+        assert_eq!(rfind(b"abc", b""), Some(3));
+        assert_eq!(rfind_iter(b"ab", b"").collect::<Vec<_>>(), [2, 1, 0]);
+    }
+
+    #[test]
+    fn test_offsets_output() {
+        let offsets = |input: &[u8], options: ReverseOptions| {
+            let mut result = vec![];
+            options
+                .output_format(OutputFormat::Offsets)
+                .run_bytes(&mut result, input)
+                .unwrap();
+            String::from_utf8(result).unwrap()
+        };
+
+        assert_eq!(offsets(b"", ReverseOptions::new()), "");
+        assert_eq!(
+            offsets(b"one\ntwo\n\nthree", ReverseOptions::new()),
+            "9\t5\n8\t1\n4\t4\n0\t4\n"
+        );
+        assert_eq!(
+            offsets(b"\none\ntwo", ReverseOptions::new().before(true)),
+            "4\t4\n0\t4\n"
+        );
+    }
+
+    #[test]
+    fn test_json_output() {
+        let path = std::env::temp_dir().join(format!(".tac-test-json-{}", std::process::id()));
+        let run = |input: &[u8], options: ReverseOptions| {
+            std::fs::write(&path, input).unwrap();
+            let mut result = vec![];
+            options.run(&mut result, Some(&path)).unwrap();
+            String::from_utf8(result).unwrap()
+        };
+        let json = || ReverseOptions::new().output_format(OutputFormat::Json);
+
+        assert_eq!(run(b"", json()), "[]\n");
+        assert_eq!(
+            run(b"a\tb\n\xffc\\\x01\nlast", json()),
+            "[\n{\"offset\":9,\"record\":\"last\"},\n\
+             {\"offset\":4,\"record\":\"\u{fffd}c\\\\\\u0001\"},\n\
+             {\"offset\":0,\"record\":\"a\\tb\"}\n]\n"
+        );
+        // The array is closed even when stopping early.
+        assert_eq!(
+            run(b"a\nb\nc\n", json().until_match("b", false)),
+            "[\n{\"offset\":4,\"record\":\"c\"}\n]\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_framed_output() {
+        let framed = |input: &[u8], options: ReverseOptions| {
+            let mut result = vec![];
+            options
+                .output_format(OutputFormat::Framed)
+                .run_bytes(&mut result, input)
+                .unwrap();
+            result
+        };
+        let frame = |content: &[u8]| [&(content.len() as u32).to_be_bytes()[..], content].concat();
+
+        assert_eq!(framed(b"", ReverseOptions::new()), b"");
+        // The unterminated last record is framed like the others, and an empty one has a length
+        // of zero.
+        assert_eq!(
+            framed(b"one\n\nlast", ReverseOptions::new()),
+            [&b"\0\0\0\x04last"[..], b"\0\0\0\0", b"\0\0\0\x03one"].concat()
+        );
+        assert_eq!(
+            framed(b"one\n\n", ReverseOptions::new()),
+            [frame(b""), frame(b"one")].concat()
+        );
+        // Four bytes, most significant first.
+        let long = vec![b'x'; 0x1_0203];
+        let mut input = long.clone();
+        input.push(b'\n');
+        assert_eq!(framed(&input, ReverseOptions::new())[..4], [0, 1, 2, 3]);
+        assert_eq!(framed(&input, ReverseOptions::new()), frame(&long));
+        assert_eq!(
+            framed(b"\none\ntwo", ReverseOptions::new().before(true)),
+            [frame(b"two"), frame(b"one")].concat()
+        );
+    }
+
+    #[test]
+    fn test_escaped_output() {
+        let vectors = [
+            (&b""[..], ""),
+            (b"plain text", "plain text"),
+            (b"a\tb\nc", "a\\tb\\nc"),
+            (b"back\\slash", "back\\\\slash"),
+            (b"\0", "\\x00"),
+            (b"\x07\x08\x0b\x0c\r\x1b\x7f", "\\a\\b\\v\\f\\r\\x1b\\x7f"),
+            (b"caf\xc3\xa9 \xff", "caf\\xc3\\xa9 \\xff"),
+        ];
+        for (input, expected) in vectors {
+            let mut output = vec![];
+            format::write_escaped_record(&mut output, input).unwrap();
+            assert_eq!(output, expected.as_bytes());
+        }
+
+        // Every record on a line of its own, without its separator.
+        let mut result = vec![];
+        ReverseOptions::new()
+            .output_format(OutputFormat::Escaped)
+            .run_bytes(&mut result, b"a\tb\n\n\xff\0")
+            .unwrap();
+        assert_eq!(result, b"\\xff\\x00\n\na\\tb\n");
+    }
+
+    #[test]
+    fn test_base64() {
+        // Test vectors from RFC 4648, plus one spanning several encoding pieces.
+        let long = vec![0xff; 2000];
+        let long_encoded = "/".repeat(2000 / 3 * 4) + "//8=";
+        let vectors = [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (&long, &long_encoded),
+        ];
+        for (input, expected) in vectors {
+            let mut output = vec![];
+            format::write_base64_record(&mut output, input).unwrap();
+            assert_eq!(output, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "allocator_api")]
+    fn test_buffer_allocator() {
+        use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+        use std::ptr::NonNull;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Clone, Default)]
+        struct Counting(Arc<AtomicUsize>);
+
+        unsafe impl Allocator for Counting {
+            fn allocate(&self, layout: Layout) -> std::result::Result<NonNull<[u8]>, AllocError> {
+                self.0.fetch_add(layout.size(), Ordering::Relaxed);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let path = std::env::temp_dir().join("tac-k-test-buffer-allocator.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let allocator = Counting::default();
+        let mut result = vec![];
+        ReverseOptions::new()
+            .buffer_allocator(allocator.clone())
+            .run(&mut result, Some(&path))
+            .unwrap();
+
+        assert_eq!(result, b"two\none\n");
+        assert_eq!(allocator.0.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_async() {
+        fn assert_send<T: Send>(future: T) -> T {
+            future
+        }
+
+        let options = ReverseOptions::new().until_match("one", true);
+        let mut input = &b"zero\none\ntwo\n"[..];
+        let mut result = vec![];
+        let future = assert_send(options.run_async(&mut input, &mut result));
+        futures_executor::block_on(future).unwrap();
+        assert_eq!(result, b"two\none\n");
+
+        // Files are reversed on a thread of their own, in chunks.
+        let path = std::env::temp_dir().join(format!("tac-k-test-async-{}.txt", std::process::id()));
+        let input: Vec<u8> = (0..100_000).flat_map(|i| format!("{i}\n").into_bytes()).collect();
+        std::fs::write(&path, &input).unwrap();
+        let mut expected = vec![];
+        reverse_file(&mut expected, Some(&path), b'\n').unwrap();
+        let mut result = vec![];
+        let options = ReverseOptions::new();
+        let future = assert_send(options.run_file_async(&mut result, Some(&path)));
+        futures_executor::block_on(future).unwrap();
+        assert_eq!(result, expected);
+
+        // A failing writer stops the reversal.
+        let mut output = [0; 10];
+        let mut output = futures_util::io::Cursor::new(&mut output[..]);
+        let err = futures_executor::block_on(reverse_file_async(&mut output, Some(&path), b'\n')).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    }
 }
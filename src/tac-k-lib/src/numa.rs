@@ -0,0 +1,96 @@
+use std::mem::{size_of, zeroed};
+
+/// Number of pages of a chunk looked up to find the node holding it.
+const SAMPLES: usize = 16;
+
+/// The CPUs of every NUMA node of this machine, indexed by node, or `None` if it has a single
+/// node, or they could not be found out.
+pub(crate) fn nodes() -> Option<&'static [Vec<usize>]> {
+    static NODES: std::sync::OnceLock<Option<Vec<Vec<usize>>>> = std::sync::OnceLock::new();
+
+    NODES
+        .get_or_init(|| {
+            let read = |path: &str| std::fs::read_to_string(path).ok();
+            let online = parse_list(read("/sys/devices/system/node/online")?.trim())?;
+            let mut nodes = vec![Vec::new(); online.iter().max()? + 1];
+            for &node in &online {
+                nodes[node] = parse_list(read(&format!("/sys/devices/system/node/node{node}/cpulist"))?.trim())?;
+            }
+            (online.len() > 1).then_some(nodes)
+        })
+        .as_deref()
+}
+
+/// Parse a list of CPUs or nodes like `0-3,8,10-11`, as sysfs reports them.
+pub(crate) fn parse_list(list: &str) -> Option<Vec<usize>> {
+    let mut items = Vec::new();
+    for range in list.split(',').filter(|range| !range.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        items.extend(first.parse::<usize>().ok()?..=last.parse().ok()?);
+    }
+    Some(items)
+}
+
+/// The node holding most of the sampled pages of `bytes`, part of a memory mapping, as reported
+/// by `move_pages`. Pages not in memory yet have no node, and neither has `bytes` if the system
+/// call is not allowed, e.g. in a container without `CAP_SYS_NICE`.
+fn node_of(bytes: &[u8], nodes: usize) -> Option<usize> {
+    let page_size = crate::cache::sizes().page;
+    let pages: Vec<*const libc::c_void> = (0..SAMPLES)
+        .map(|sample| {
+            let address = bytes.as_ptr() as usize + bytes.len() / SAMPLES * sample;
+            (address - address % page_size) as *const libc::c_void
+        })
+        .collect();
+    let mut status = [-1; SAMPLES];
+    // Without target nodes, `move_pages` only reports where every page is.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0,
+            SAMPLES,
+            pages.as_ptr(),
+            std::ptr::null::<libc::c_int>(),
+            status.as_mut_ptr(),
+            0,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let mut counts = vec![0; nodes];
+    for node in status.into_iter().filter_map(|node| usize::try_from(node).ok()) {
+        if let Some(count) = counts.get_mut(node) {
+            *count += 1;
+        }
+    }
+    (0..nodes)
+        .filter(|&node| counts[node] != 0)
+        .max_by_key(|&node| counts[node])
+}
+
+/// Bind the calling thread to the CPUs of the node among `nodes` that holds `bytes`, part of a
+/// memory mapping, so that searching them does not read memory across nodes. The thread is left
+/// as it is if the pages of `bytes` are not in memory yet, as it then faults them in on its own
+/// node, or if none of the CPUs of that node is allowed.
+pub(crate) fn bind_to(nodes: &[Vec<usize>], bytes: &[u8]) {
+    let Some(node) = node_of(bytes, nodes.len()) else {
+        return;
+    };
+    unsafe {
+        let mut allowed: libc::cpu_set_t = zeroed();
+        if libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut allowed) != 0 {
+            return;
+        }
+        let mut cpus: libc::cpu_set_t = zeroed();
+        for &cpu in &nodes[node] {
+            if cpu < libc::CPU_SETSIZE as usize && libc::CPU_ISSET(cpu, &allowed) {
+                libc::CPU_SET(cpu, &mut cpus);
+            }
+        }
+        // Only an optimization: failing to bind the thread is of no consequence.
+        if libc::CPU_COUNT(&cpus) != 0 {
+            let _ = libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &cpus);
+        }
+    }
+}
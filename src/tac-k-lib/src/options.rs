@@ -0,0 +1,1483 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::characters::Characters;
+use crate::separator::Separator;
+use crate::spill::{SpillFile, SpillHook, SpillPolicy};
+use crate::{Cancelled, Engine, Error, MmapAdvice, Output, OutputFormat, Stats};
+
+/// Number of bytes at the start of an input searched for its first line ending, see
+/// [`ReverseOptions::detect_crlf`].
+pub(crate) const CRLF_WINDOW: usize = 64 * 1024;
+
+/// Hook reporting progress, see [`ReverseOptions::on_progress`].
+pub type ProgressHook = dyn FnMut(u64, u64) + Send;
+
+/// Hook receiving diagnostic messages, see [`ReverseOptions::on_trace`].
+pub type TraceHook = dyn Fn(&str) + Send + Sync;
+
+/// Hook receiving the figures of every reversed input, see [`ReverseOptions::on_stats`].
+pub type StatsHook = dyn Fn(&Stats) + Send + Sync;
+
+/// Where a record lacking its separator is written, see [`ReverseOptions::fragment`] for the one
+/// at the end of an input and [`ReverseOptions::leading_fragment`] for the one at its start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fragment {
+    /// Before every other record, which is where reversing puts the one at the end of an input,
+    /// running into the next record if the output is raw.
+    #[default]
+    First,
+    /// After every other record, which is where reversing puts the one at the start of an input.
+    Last,
+    /// Not at all.
+    Drop,
+}
+
+/// What becomes of the separator ending the last record written, see
+/// [`ReverseOptions::trailing_separator`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSeparator {
+    /// Records are written as they are in the input.
+    #[default]
+    Keep,
+    /// The unterminated record at the end of the input, if any, is followed by the separator.
+    Ensure,
+    /// The last record is written without its separator.
+    Strip,
+}
+
+/// Configuration for reversing an input, for when the defaults of [`reverse_file`] do not fit.
+///
+/// [`reverse_file`]: crate::reverse_file
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::ReverseOptions;
+/// use std::time::Duration;
+///
+/// let mut result = vec![];
+/// ReverseOptions::new()
+///     .separator(b'.')
+///     .stdin_timeout(Some(Duration::from_secs(10)))
+///     .run(&mut result, Some("Cargo.toml"))
+///     .unwrap();
+///
+/// assert!(std::str::from_utf8(&result).is_ok());
+/// ```
+#[derive(Clone)]
+pub struct ReverseOptions {
+    pub(crate) separator: Separator,
+    pub(crate) before: bool,
+    pub(crate) detect_crlf: bool,
+    pub(crate) stdin_timeout: Option<Duration>,
+    pub(crate) spill_hook: Option<Arc<SpillHook>>,
+    pub(crate) spill_policy: Option<SpillPolicy>,
+    pub(crate) secure_delete: bool,
+    pub(crate) progress_hook: Option<Arc<Mutex<ProgressHook>>>,
+    pub(crate) progress_interval: u64,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+    pub(crate) trace_hook: Option<Arc<TraceHook>>,
+    pub(crate) stats_hook: Option<Arc<StatsHook>>,
+    pub(crate) until_match: Option<(Vec<u8>, bool)>,
+    pub(crate) from_match: Option<(Vec<u8>, bool)>,
+    pub(crate) context: (u64, u64),
+    pub(crate) max_records: Option<u64>,
+    pub(crate) skip_records: u64,
+    pub(crate) output_format: OutputFormat,
+    pub(crate) characters: Option<Characters>,
+    pub(crate) label: Option<Vec<u8>>,
+    pub(crate) number: bool,
+    pub(crate) fragment: Fragment,
+    pub(crate) leading_fragment: Fragment,
+    pub(crate) trailing_separator: TrailingSeparator,
+    pub(crate) line_buffered: bool,
+    pub(crate) mmap_threshold: u64,
+    pub(crate) mmap: bool,
+    pub(crate) mmap_advice: MmapAdvice,
+    pub(crate) max_buf_size: usize,
+    pub(crate) calibrate_search: bool,
+    pub(crate) engine: Engine,
+    pub(crate) threads: usize,
+    pub(crate) chunk_size: usize,
+    #[cfg(feature = "rayon")]
+    pub(crate) thread_pool: Option<Arc<rayon::ThreadPool>>,
+    #[cfg(feature = "encoding")]
+    pub(crate) encoding: Option<&'static encoding_rs::Encoding>,
+    #[cfg(feature = "encoding")]
+    pub(crate) utf8_output: bool,
+    #[cfg(feature = "lz4")]
+    pub(crate) compress_spill: bool,
+    #[cfg(feature = "encrypt")]
+    pub(crate) encrypt_spill: bool,
+    #[cfg(feature = "allocator_api")]
+    pub(crate) buffer_allocator: Option<Arc<dyn allocator_api2::alloc::Allocator + Send + Sync>>,
+}
+
+impl std::fmt::Debug for ReverseOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut f = f.debug_struct("ReverseOptions");
+        f.field("separator", &self.separator)
+            .field("before", &self.before)
+            .field("detect_crlf", &self.detect_crlf)
+            .field("stdin_timeout", &self.stdin_timeout)
+            .field("spill_hook", &self.spill_hook.as_ref().map(|_| ".."))
+            .field("spill_policy", &self.spill_policy)
+            .field("secure_delete", &self.secure_delete)
+            .field("progress_hook", &self.progress_hook.as_ref().map(|_| ".."))
+            .field("progress_interval", &self.progress_interval)
+            .field("cancel", &self.cancel)
+            .field("trace_hook", &self.trace_hook.as_ref().map(|_| ".."))
+            .field("stats_hook", &self.stats_hook.as_ref().map(|_| ".."))
+            .field("until_match", &self.until_match)
+            .field("from_match", &self.from_match)
+            .field("context", &self.context)
+            .field("max_records", &self.max_records)
+            .field("skip_records", &self.skip_records)
+            .field("output_format", &self.output_format)
+            .field("characters", &self.characters)
+            .field("label", &self.label)
+            .field("number", &self.number)
+            .field("fragment", &self.fragment)
+            .field("leading_fragment", &self.leading_fragment)
+            .field("trailing_separator", &self.trailing_separator)
+            .field("line_buffered", &self.line_buffered)
+            .field("mmap_threshold", &self.mmap_threshold)
+            .field("mmap", &self.mmap)
+            .field("mmap_advice", &self.mmap_advice)
+            .field("max_buf_size", &self.max_buf_size)
+            .field("calibrate_search", &self.calibrate_search)
+            .field("engine", &self.engine)
+            .field("threads", &self.threads)
+            .field("chunk_size", &self.chunk_size);
+        #[cfg(feature = "rayon")]
+        f.field("thread_pool", &self.thread_pool);
+        #[cfg(feature = "encoding")]
+        f.field("encoding", &self.encoding)
+            .field("utf8_output", &self.utf8_output);
+        #[cfg(feature = "lz4")]
+        f.field("compress_spill", &self.compress_spill);
+        #[cfg(feature = "encrypt")]
+        f.field("encrypt_spill", &self.encrypt_spill);
+        #[cfg(feature = "allocator_api")]
+        f.field("buffer_allocator", &self.buffer_allocator.as_ref().map(|_| ".."));
+        f.finish()
+    }
+}
+
+impl Default for ReverseOptions {
+    fn default() -> Self {
+        ReverseOptions {
+            separator: Separator::Bytes(vec![b'\n']),
+            before: false,
+            detect_crlf: false,
+            stdin_timeout: None,
+            spill_hook: None,
+            spill_policy: None,
+            secure_delete: false,
+            progress_hook: None,
+            progress_interval: crate::emit::PROGRESS_INTERVAL,
+            cancel: None,
+            trace_hook: None,
+            stats_hook: None,
+            until_match: None,
+            from_match: None,
+            context: (0, 0),
+            max_records: None,
+            skip_records: 0,
+            output_format: OutputFormat::Raw,
+            characters: None,
+            label: None,
+            number: false,
+            fragment: Fragment::First,
+            leading_fragment: Fragment::Last,
+            trailing_separator: TrailingSeparator::Keep,
+            line_buffered: false,
+            mmap_threshold: crate::DEFAULT_MMAP_THRESHOLD,
+            mmap: true,
+            mmap_advice: MmapAdvice::default(),
+            max_buf_size: crate::MAX_BUF_SIZE,
+            calibrate_search: false,
+            engine: Engine::Auto,
+            threads: 0,
+            chunk_size: 0,
+            #[cfg(feature = "rayon")]
+            thread_pool: None,
+            #[cfg(feature = "encoding")]
+            encoding: None,
+            #[cfg(feature = "encoding")]
+            utf8_output: false,
+            #[cfg(feature = "lz4")]
+            compress_spill: false,
+            #[cfg(feature = "encrypt")]
+            encrypt_spill: false,
+            #[cfg(feature = "allocator_api")]
+            buffer_allocator: None,
+        }
+    }
+}
+
+impl ReverseOptions {
+    /// Create the default options: newline-separated, no timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `separator` to partition the content into lines instead of `b'\n'`.
+    pub fn separator(mut self, separator: u8) -> Self {
+        self.separator = Separator::Bytes(vec![separator]);
+        self
+    }
+
+    /// Use the character `separator`, e.g. `'。'`, to partition UTF-8 content into records instead
+    /// of `b'\n'`.
+    ///
+    /// This is [`separator_bytes`](Self::separator_bytes) with the UTF-8 encoding of `separator`.
+    /// UTF-8 being self-synchronizing, that encoding never matches the bytes of other characters
+    /// or straddles two of them, so records are never split in the middle of a code point. The
+    /// separator is a code point rather than a grapheme cluster though: combining marks following
+    /// it start the next record.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-separator-char.txt");
+    /// std::fs::write(&path, "一。二。").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .separator_char('。')
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(String::from_utf8(result).unwrap(), "二。一。");
+    /// ```
+    pub fn separator_char(self, separator: char) -> Self {
+        self.separator_bytes(separator.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    /// Use the byte string `separator`, e.g. `b"\r\n"`, to partition the content into records
+    /// instead of `b'\n'`.
+    ///
+    /// Separators are searched for from the end of the input, so of two overlapping occurrences
+    /// (e.g. in `"aaa"` for `"aa"`) only the last one separates records.
+    ///
+    /// ## Panics
+    ///
+    /// If `separator` is empty.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-separator-bytes.txt");
+    /// std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .separator_bytes(b"\r\n")
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"two\r\none\r\n");
+    /// ```
+    pub fn separator_bytes(mut self, separator: impl AsRef<[u8]>) -> Self {
+        assert!(!separator.as_ref().is_empty(), "the separator must not be empty");
+        self.separator = Separator::Bytes(separator.as_ref().to_vec());
+        self
+    }
+
+    /// Partition the content into records on the matches of `regex` instead of on `b'\n'`, like
+    /// GNU `tac --regex`. Empty matches are ignored.
+    ///
+    /// Each record is followed by the match that ends it, which stays attached to it in the
+    /// output. Matches are found forwards, from the start of the input, so the whole input is
+    /// searched before the first record is written.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::regex::bytes::Regex;
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-separator-regex.txt");
+    /// std::fs::write(&path, "one;;two;three;;;").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .separator_regex(Regex::new(";+").unwrap())
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"three;;;two;one;;");
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn separator_regex(mut self, regex: regex::bytes::Regex) -> Self {
+        self.separator = Separator::Regex(regex);
+        self
+    }
+
+    /// Attach every separator to the start of the record following it rather than to the end of
+    /// the one preceding it, like GNU `tac --before`.
+    ///
+    /// Each record then keeps the separator that precedes it in the input. The unterminated
+    /// fragment [`fragment`](Self::fragment) is about does not exist in this mode; the record
+    /// without a separator is the first one of the input, which is written last unless
+    /// [`leading_fragment`](Self::leading_fragment) says otherwise.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-before.txt");
+    /// std::fs::write(&path, "one\ntwo\nthree").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .before(true)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"\nthree\ntwoone");
+    /// ```
+    pub fn before(mut self, before: bool) -> Self {
+        self.before = before;
+        self
+    }
+
+    /// Partition an input into records on `b"\r\n"` rather than on `b'\n'` if its first line, within
+    /// its first 64 KiB, ends with `\r\n`, e.g. a log written on Windows. Records then keep no
+    /// stray `\r` at their end, whichever [`fragment`](Self::fragment) or
+    /// [`characters`](Self::characters) asks for. Inputs are told apart one by one, and only if the
+    /// separator is a newline, the default.
+    ///
+    /// [`separator_bytes`](Self::separator_bytes) with `b"\r\n"` does the same for every input.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{Characters, ReverseOptions};
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .detect_crlf(true)
+    ///     .characters(Characters::Utf8)
+    ///     .run_bytes(&mut result, b"one\r\ntwo\r\n")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"owt\r\neno\r\n");
+    /// ```
+    pub fn detect_crlf(mut self, detect_crlf: bool) -> Self {
+        self.detect_crlf = detect_crlf;
+        self
+    }
+
+    /// Fail with [`ErrorKind::TimedOut`] if buffering `stdin` sees no new data for longer than
+    /// `timeout`, instead of waiting indefinitely on a pipe that never produces any.
+    ///
+    /// This has no effect when `stdin` can be memory-mapped. It is only supported on Unix; elsewhere
+    /// buffering `stdin` with a timeout fails with [`ErrorKind::Unsupported`].
+    ///
+    /// [`ErrorKind::TimedOut`]: std::io::ErrorKind::TimedOut
+    /// [`ErrorKind::Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn stdin_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.stdin_timeout = timeout;
+        self
+    }
+
+    /// Read files smaller than `threshold` bytes into memory instead of memory-mapping them.
+    ///
+    /// For small files, a single `read` is cheaper than setting up a mapping and faulting its
+    /// pages in. Defaults to 64 KiB; `0` maps every file.
+    pub fn mmap_threshold(mut self, threshold: u64) -> Self {
+        self.mmap_threshold = threshold;
+        self
+    }
+
+    /// Whether inputs may be memory-mapped at all, which they are by default whenever possible.
+    ///
+    /// Without mapping, regular files are read backwards in chunks of
+    /// [`max_buf_size`](Self::max_buf_size) bytes, like files on network filesystems always are,
+    /// and other inputs are buffered like `stdin` is. This avoids the `SIGBUS` a mapped file being
+    /// truncated by another process would cause.
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// How the kernel is advised to read memory-mapped inputs ahead. Defaults to
+    /// [`MmapAdvice::Backwards`] with a 16 MiB window, which reads cold inputs backwards in large
+    /// requests rather than a few pages at a time.
+    pub fn mmap_advice(mut self, advice: MmapAdvice) -> Self {
+        self.mmap_advice = advice;
+        self
+    }
+
+    /// Keep up to `size` bytes of an input that has to be buffered in memory before spilling it to a
+    /// temporary file, instead of 4 MiB. The buffer only grows as large as the input needs.
+    ///
+    /// [`SpillPolicy::InMemoryUpTo`] takes precedence over this.
+    pub fn max_buf_size(mut self, size: usize) -> Self {
+        self.max_buf_size = size;
+        self
+    }
+
+    /// Pick the search implementation by timing every one this machine supports before the first
+    /// input is reversed, instead of by instruction set alone, see
+    /// [`calibrate_search`](crate::calibrate_search).
+    pub fn calibrate_search(mut self, calibrate: bool) -> Self {
+        self.calibrate_search = calibrate;
+        self
+    }
+
+    /// Search inputs with the given implementation rather than the one picked automatically, to
+    /// benchmark or debug them. Reversing fails with [`ErrorKind::Unsupported`] if this machine
+    /// does not support it, see [`Engine::is_supported`].
+    ///
+    /// [`ErrorKind::Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Reverse inputs on up to `threads` threads, `0` meaning one per available CPU, or with the
+    /// `rayon` feature, per thread of the pool given to `thread_pool`.
+    ///
+    /// Mapped and buffered inputs larger than a few MiB are split into chunks that are searched in
+    /// parallel, while the records found are written from the calling thread, in order. This pays
+    /// off for inputs of many GiB, whose single-threaded search is bound by the memory bandwidth of
+    /// one core. Only single-byte separators are searched in parallel. On Linux machines with
+    /// several NUMA nodes, the thread searching a chunk already in memory is bound to the CPUs of
+    /// the node holding it, while one faulting a chunk in places it on its own node.
+    ///
+    /// Of several inputs, as given to [`run_all`](Self::run_all), files of a few MiB at most are
+    /// reversed into memory ahead of being written, several at a time, unless
+    /// [`line_buffered`](Self::line_buffered). Defaults to `0`; `1` does everything on the calling
+    /// thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Search inputs on several [`threads`](Self::threads) in chunks of `chunk_size` bytes, `0`
+    /// meaning a size picked after the CPU caches and the page size of this machine. Defaults to
+    /// `0`.
+    ///
+    /// The chunks picked are small enough for all those in flight to stay in the shared L3 cache
+    /// until their records are written, but no smaller than the L2 cache of a core, 1 MiB, or
+    /// larger than 64 MiB. If the size of the caches cannot be found out, they are 16 MiB.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Run the work [`threads`](Self::threads) spreads over several threads as jobs of `pool`
+    /// rather than on threads of its own, e.g. to share the threads of an application that already
+    /// uses rayon.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use tac_k_lib::{rayon, ReverseOptions};
+    ///
+    /// let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+    /// let path = std::env::temp_dir().join("tac-k-thread-pool.txt");
+    /// std::fs::write(&path, "one\ntwo\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// let summary = ReverseOptions::new().thread_pool(pool).run_all(&mut result, &[Some(&path), Some(&path)]);
+    ///
+    /// assert!(summary.is_success());
+    /// assert_eq!(result, b"two\none\ntwo\none\n");
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Use `hook` to create the file that buffered `stdin` is spilled into once it outgrows the
+    /// in-memory buffer, instead of [`SpillFile::in_temp_dir`]. It is not used if a
+    /// [`spill_policy`](Self::spill_policy) is set.
+    ///
+    /// The hook is called at most once per reversal. If it returns a path, that path is removed
+    /// once the input has been reversed, whether successfully or not.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{ReverseOptions, SpillFile};
+    /// use std::fs::OpenOptions;
+    ///
+    /// let options = ReverseOptions::new().spill_file(|| {
+    ///     let path = std::env::temp_dir().join("my-app-spill");
+    ///     let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+    ///     Ok(SpillFile { file, path: Some(path) })
+    /// });
+    /// ```
+    pub fn spill_file<F>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> std::io::Result<SpillFile> + Send + Sync + 'static,
+    {
+        self.spill_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Decide what happens to buffered `stdin` once it outgrows the in-memory buffer, overriding
+    /// [`spill_file`](Self::spill_file).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{ReverseOptions, SpillPolicy};
+    ///
+    /// // Keep up to 64 MiB of stdin in memory and never touch the disk.
+    /// let options = ReverseOptions::new().spill_policy(SpillPolicy::InMemoryUpTo(64 * 1024 * 1024));
+    /// ```
+    pub fn spill_policy(mut self, policy: SpillPolicy) -> Self {
+        self.spill_policy = Some(policy);
+        self
+    }
+
+    /// Overwrite the spill file with zeroes before removing it.
+    ///
+    /// This is best-effort only: journaling and copy-on-write filesystems, snapshots and SSD wear
+    /// levelling may all keep copies of the original data around. It only applies to spill files
+    /// with a path, see [`SpillFile::path`].
+    pub fn secure_delete(mut self, secure_delete: bool) -> Self {
+        self.secure_delete = secure_delete;
+        self
+    }
+
+    /// Call `hook` with the number of bytes processed so far and the total length of the input
+    /// periodically while reversing, and once more when done.
+    ///
+    /// The hook is called from the scan loop itself, so it should return quickly, at most every
+    /// [`progress_interval`](Self::progress_interval) bytes. Note that buffering `stdin` happens
+    /// before the input's length is known and is not reported.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .on_progress(|done, total| eprintln!("{done}/{total} bytes"))
+    ///     .run(&mut result, Some("Cargo.toml"))
+    ///     .unwrap();
+    /// ```
+    pub fn on_progress<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(u64, u64) + Send + 'static,
+    {
+        self.progress_hook = Some(Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    /// Set the minimum number of bytes processed between two calls of the
+    /// [`on_progress`](Self::on_progress) hook, 16 MiB by default.
+    ///
+    /// Smaller intervals give smoother feedback on slow inputs at the cost of calling the hook more
+    /// often. An interval of `0` calls it after every record.
+    pub fn progress_interval(mut self, bytes: u64) -> Self {
+        self.progress_interval = bytes;
+        self
+    }
+
+    /// Abort reversing once `flag` is set, e.g. from another thread, failing with an error
+    /// carrying [`Cancelled`].
+    ///
+    /// The flag is checked before every record and while buffering an input, and what has been
+    /// written so far is flushed before returning. Inputs following the cancelled one are skipped.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use tac_k_lib::{Cancelled, ReverseOptions};
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let options = ReverseOptions::new().cancel_on(cancel.clone());
+    ///
+    /// cancel.store(true, Ordering::Relaxed);
+    /// let e = options.run(&mut vec![], Some("Cargo.toml")).unwrap_err();
+    /// assert!(Cancelled::is(e.io()));
+    /// ```
+    pub fn cancel_on(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Call `hook` with a human-readable message at every decision affecting how the input is read
+    /// and reversed: whether it is memory-mapped or buffered and why, where it is spilled to and
+    /// which SIMD implementation scans it.
+    ///
+    /// With the `log` feature, the same messages are also logged at the debug level.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .on_trace(|message| eprintln!("tac: {message}"))
+    ///     .run(&mut result, Some("Cargo.toml"))
+    ///     .unwrap();
+    /// ```
+    pub fn on_trace<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.trace_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the [`Stats`] of every input once it has been reversed successfully: its
+    /// length, the number of records written and how long reading and reversing it took.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .on_stats(|stats| eprintln!("{} records in {:?}", stats.records, stats.reverse_time))
+    ///     .run(&mut result, Some("Cargo.toml"))
+    ///     .unwrap();
+    /// ```
+    pub fn on_stats<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Stats) + Send + Sync + 'static,
+    {
+        self.stats_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Create an empty buffer for reading inputs into.
+    pub(crate) fn new_buffer(&self) -> crate::buffer::Buffer {
+        #[cfg(feature = "allocator_api")]
+        return crate::buffer::Buffer::new_in(crate::buffer::BufferAllocator(self.buffer_allocator.clone()));
+        #[cfg(not(feature = "allocator_api"))]
+        Vec::new()
+    }
+
+    /// The options to reverse an input starting with `head` with, which separate records on
+    /// `b"\r\n"` if [`detect_crlf`](Self::detect_crlf) finds it uses them.
+    pub(crate) fn for_input(&self, head: &[u8]) -> Cow<'_, ReverseOptions> {
+        let crlf = self.detect_crlf
+            && self.separator.bytes() == Some(b"\n")
+            && head[..head.len().min(CRLF_WINDOW)]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .is_some_and(|newline| head[..newline].ends_with(b"\r"));
+        if !crlf {
+            return Cow::Borrowed(self);
+        }
+        self.trace(|| "the input has CRLF line endings, separating records on \\r\\n".into());
+        Cow::Owned(self.clone().separator_bytes(b"\r\n"))
+    }
+
+    /// Whether inputs are decoded before being reversed, see [`encoding`](Self::encoding).
+    pub(crate) fn decoding(&self) -> bool {
+        #[cfg(feature = "encoding")]
+        return self.encoding.is_some();
+        #[cfg(not(feature = "encoding"))]
+        false
+    }
+
+    /// The options to reverse an input found to be in `encoding` with, which a byte order mark may
+    /// have picked over the one of the options.
+    #[cfg(feature = "encoding")]
+    pub(crate) fn decoded_from(&self, encoding: Option<&'static encoding_rs::Encoding>) -> Cow<'_, ReverseOptions> {
+        match encoding {
+            Some(encoding) if self.encoding != Some(encoding) => {
+                self.trace(|| format!("the input is in {} after its byte order mark", encoding.name()));
+                Cow::Owned(ReverseOptions {
+                    encoding: Some(encoding),
+                    ..self.clone()
+                })
+            }
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Number of bytes of buffered `stdin` kept in memory before spilling it.
+    pub(crate) fn buffer_size(&self) -> usize {
+        match self.spill_policy {
+            Some(SpillPolicy::InMemoryUpTo(limit)) => limit,
+            _ => self.max_buf_size,
+        }
+    }
+
+    /// Number of threads to search inputs on, see [`threads`](Self::threads).
+    pub(crate) fn search_threads(&self) -> usize {
+        // Threads cannot be spawned on `wasm32-unknown-unknown`.
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        return 1;
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        match self.threads {
+            0 => {
+                #[cfg(feature = "rayon")]
+                if let Some(pool) = &self.thread_pool {
+                    return pool.current_num_threads();
+                }
+                std::thread::available_parallelism().map_or(1, usize::from)
+            }
+            threads => threads,
+        }
+    }
+
+    /// Size of the chunks to search inputs in on `threads` threads, see
+    /// [`chunk_size`](Self::chunk_size).
+    pub(crate) fn search_chunk_size(&self, threads: usize) -> usize {
+        match self.chunk_size {
+            0 => crate::parallel::chunk_size(threads, crate::cache::sizes()),
+            chunk_size => chunk_size,
+        }
+    }
+
+    /// Fail with [`Cancelled`] if the flag given to [`cancel_on`](Self::cancel_on) is set.
+    #[inline]
+    pub(crate) fn check_cancelled(&self) -> std::io::Result<()> {
+        match &self.cancel {
+            Some(flag) if flag.load(Ordering::Relaxed) => Err(Cancelled::error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pass the message built by `message` to the trace hook, if any, and with the `log` feature
+    /// to the `log` crate at the debug level.
+    pub(crate) fn trace(&self, message: impl FnOnce() -> String) {
+        #[cfg(feature = "log")]
+        let log = log::log_enabled!(log::Level::Debug);
+        #[cfg(not(feature = "log"))]
+        let log = false;
+        if self.trace_hook.is_none() && !log {
+            return;
+        }
+
+        let message = message();
+        #[cfg(feature = "log")]
+        if log {
+            log::debug!("{message}");
+        }
+        if let Some(hook) = &self.trace_hook {
+            hook(&message);
+        }
+    }
+
+    /// Stop once `max` records have been emitted, i.e. only emit the last `max` records of the
+    /// input.
+    ///
+    /// Only the end of a file holding those records is memory-mapped, found by mapping ever larger
+    /// windows from its end, so this touches little more than the records emitted however large
+    /// the file. That takes a fixed separator, and neither [`number`](Self::number),
+    /// [`from_match`](Self::from_match) nor [`detect_crlf`](Self::detect_crlf), which need the
+    /// whole file; it is then mapped as a whole, but still only faulted in as far back as it is
+    /// scanned.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-max-records.txt");
+    /// std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new().max_records(2).run(&mut result, Some(&path)).unwrap();
+    ///
+    /// assert_eq!(result, b"three\ntwo\n");
+    /// ```
+    pub fn max_records(mut self, max: u64) -> Self {
+        self.max_records = Some(max);
+        self
+    }
+
+    /// Skip the last `skip` records of the input before emitting any, e.g. to page backwards
+    /// through it together with [`max_records`](Self::max_records).
+    ///
+    /// Skipped records are still scanned but never written, nor matched against
+    /// [`until_match`](Self::until_match).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-skip-records.txt");
+    /// std::fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .skip_records(1)
+    ///     .max_records(2)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"three\ntwo\n");
+    /// ```
+    pub fn skip_records(mut self, skip: u64) -> Self {
+        self.skip_records = skip;
+        self
+    }
+
+    /// Stop once a record containing `pattern` is reached, emitting that record too if `inclusive`.
+    ///
+    /// As records are emitted from the end of the input, this yields everything after the last
+    /// occurrence of `pattern` (e.g. a restart marker in a log) without scanning any further back.
+    /// The trailing separator of a record is not part of what is matched.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-until-match.log");
+    /// std::fs::write(&path, "started\nrestarted\nfoo\nbar\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .until_match("restarted", false)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"bar\nfoo\n");
+    /// ```
+    pub fn until_match(mut self, pattern: impl AsRef<[u8]>, inclusive: bool) -> Self {
+        self.until_match = Some((pattern.as_ref().to_vec(), inclusive));
+        self
+    }
+
+    /// Skip records until one containing `pattern` is reached, emitting that record too if
+    /// `inclusive`, and every record after it.
+    ///
+    /// Together with [`until_match`](Self::until_match), this extracts the region between two
+    /// markers, closest to the end of the input. The trailing separator of a record is not part of
+    /// what is matched.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-from-match.log");
+    /// std::fs::write(&path, "foo\nbegin\nbar\nend\nbaz\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .from_match("end", false)
+    ///     .until_match("begin", false)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"bar\n");
+    /// ```
+    pub fn from_match(mut self, pattern: impl AsRef<[u8]>, inclusive: bool) -> Self {
+        self.from_match = Some((pattern.as_ref().to_vec(), inclusive));
+        self
+    }
+
+    /// Also write up to `preceding` records before, and `following` records after, the records
+    /// matching [`from_match`](Self::from_match), [`until_match`](Self::until_match) or the filter
+    /// of an [`Output`](crate::Output), like `grep -B` and `grep -A` do.
+    ///
+    /// Before and after are in the order records are written, last record of the input first:
+    /// these are the records otherwise skipped before the one matching `from_match`, those
+    /// otherwise left out after the one matching `until_match`, and those around every record a
+    /// filter accepts. They are written whether or not the matching record is, and the ones after
+    /// `until_match` count towards [`max_records`](Self::max_records).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-context.log");
+    /// std::fs::write(&path, "started\nrestarted\nfoo\nbar\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .until_match("restarted", false)
+    ///     .context(0, 1)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"bar\nfoo\nstarted\n");
+    /// ```
+    pub fn context(mut self, preceding: u64, following: u64) -> Self {
+        self.context = (preceding, following);
+        self
+    }
+
+    /// Write every record in `format` instead of as it is.
+    ///
+    /// The JSON formats describe each record by its offset in the input and its content without the
+    /// trailing separator, with invalid UTF-8 replaced by U+FFFD. [`OutputFormat::Framed`] keeps
+    /// the content byte for byte and is unambiguous whatever bytes records contain, while
+    /// [`OutputFormat::Base64`] and [`OutputFormat::Escaped`] make it safe for text-only transports.
+    /// [`OutputFormat::Offsets`] leaves the content out altogether, to index the records of an input.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{OutputFormat, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-output-format.txt");
+    /// std::fs::write(&path, "foo\n\"bar\"\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .output_format(OutputFormat::Ndjson)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     result,
+    ///     b"{\"offset\":4,\"record\":\"\\\"bar\\\"\"}\n{\"offset\":0,\"record\":\"foo\"}\n"
+    /// );
+    /// ```
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Reverse the `characters` of every record as well, i.e. reverse the input as a whole like
+    /// `tac | rev` would, see [`reverse_characters`](crate::reverse_characters) to only reverse
+    /// those.
+    ///
+    /// Each record is reversed without its separator, which stays in place. Filters and output
+    /// formats see the reversed records.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{Characters, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-characters.txt");
+    /// std::fs::write(&path, "abc\ndef\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .characters(Characters::Utf8)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"fed\ncba\n");
+    /// ```
+    pub fn characters(mut self, characters: Characters) -> Self {
+        self.characters = Some(characters);
+        self
+    }
+
+    /// Prefix every record with the path of its input, `(standard input)` for `stdin`, followed by
+    /// `separator`, like `grep -H` does, so that records from several inputs reversed into the same
+    /// writer can still be told apart.
+    ///
+    /// The prefix is part of the line written by [`OutputFormat::Base64`] and
+    /// [`OutputFormat::Escaped`], but left out of the JSON and framed formats.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-label.txt");
+    /// std::fs::write(&path, "one\ntwo\n").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .label(":")
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// let path = path.display();
+    /// assert_eq!(result, format!("{path}:two\n{path}:one\n").as_bytes());
+    /// ```
+    pub fn label(mut self, separator: impl AsRef<[u8]>) -> Self {
+        self.label = Some(separator.as_ref().to_vec());
+        self
+    }
+
+    /// Prefix every record with its line number, counted from the start of the input, right-aligned
+    /// and followed by a tab like `cat -n` writes it, after the [`label`](Self::label) if any.
+    ///
+    /// Records are numbered before [`max_records`](Self::max_records) and the like leave any out,
+    /// so the numbers are those of the records in the input. Counting them takes an additional
+    /// scan of the input, which is read twice if it is neither memory-mapped nor buffered. A spill
+    /// stored as compressed or encrypted blocks is counted while it is stored instead, unless the
+    /// separator is a regular expression or can overlap itself, like `--`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .number(true)
+    ///     .run_bytes(&mut result, b"one\ntwo\n")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"     2\ttwo\n     1\tone\n");
+    /// ```
+    pub fn number(mut self, number: bool) -> Self {
+        self.number = number;
+        self
+    }
+
+    /// Choose where the unterminated record at the end of the input, i.e. the part following the
+    /// last separator, is written. There is none if the input ends with a separator.
+    ///
+    /// [`Fragment::Last`] writes it once every other record has been written, so it is left out if
+    /// reversing stops early, see [`until_match`](Self::until_match).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{Fragment, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-fragment.txt");
+    /// std::fs::write(&path, "one\ntwo\nthree").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .fragment(Fragment::Last)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"two\none\nthree");
+    /// ```
+    pub fn fragment(mut self, fragment: Fragment) -> Self {
+        self.fragment = fragment;
+        self
+    }
+
+    /// Choose where the record at the start of the input that no separator precedes is written
+    /// with [`before`](Self::before), i.e. the part preceding the first separator. There is none if
+    /// the input starts with a separator, nor without `before`, where the first record of the input
+    /// ends with a separator like the others.
+    ///
+    /// It is written last by default, where reversing puts it. [`Fragment::First`] writes it
+    /// before every other record instead, so it counts towards [`max_records`](Self::max_records)
+    /// and the like as the first one; the start of the input is then read ahead of its end.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{Fragment, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-leading-fragment.txt");
+    /// std::fs::write(&path, "header\n#one\n#two").unwrap();
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .separator_bytes(b"\n#")
+    ///     .before(true)
+    ///     .leading_fragment(Fragment::First)
+    ///     .run(&mut result, Some(&path))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"header\n#two\n#one");
+    /// ```
+    pub fn leading_fragment(mut self, fragment: Fragment) -> Self {
+        self.leading_fragment = fragment;
+        self
+    }
+
+    /// Choose what becomes of the separator ending the last record written of each input.
+    ///
+    /// [`TrailingSeparator::Ensure`] appends the separator to the unterminated record at the end of
+    /// the input, wherever [`fragment`](Self::fragment) writes it, so that it neither runs into the
+    /// next record nor leaves the output unterminated. [`TrailingSeparator::Strip`] leaves the
+    /// separator off the last record written instead, for consumers adding one of their own. Both
+    /// only apply to fixed separators following records, and `Strip` to the raw output format.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{ReverseOptions, TrailingSeparator};
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .trailing_separator(TrailingSeparator::Ensure)
+    ///     .run_bytes(&mut result, b"one\ntwo")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, b"two\none\n");
+    /// ```
+    pub fn trailing_separator(mut self, trailing_separator: TrailingSeparator) -> Self {
+        self.trailing_separator = trailing_separator;
+        self
+    }
+
+    /// Write and flush every record as soon as it is found, for an output read while it is being
+    /// written such as a terminal. By default, records are gathered into writes of up to 64 of
+    /// them, so that an unbuffered output costs one system call per batch rather than per record.
+    pub fn line_buffered(mut self, line_buffered: bool) -> Self {
+        self.line_buffered = line_buffered;
+        self
+    }
+
+    /// Decode inputs from `encoding`, e.g. [`UTF_16LE`](encoding_rs::UTF_16LE) for most Windows
+    /// logs, into UTF-8 before reversing them, so that records are separated on logical lines
+    /// rather than on bytes, and encode the output back into it unless
+    /// [`utf8_output`](Self::utf8_output) is set.
+    ///
+    /// A byte order mark at the start of an input overrides `encoding` and is left out, so
+    /// [`UTF_8`](encoding_rs::UTF_8) tells UTF-16 inputs with one apart. Malformed input is replaced
+    /// with U+FFFD. Decoded inputs are buffered like `stdin`, and separators apply to the decoded
+    /// text. The output is written without a byte order mark.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::encoding_rs::UTF_16LE;
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let utf16 = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .encoding(UTF_16LE)
+    ///     .run_bytes(&mut result, &utf16("one\ntwo\n"))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result, utf16("two\none\n"));
+    /// ```
+    #[cfg(feature = "encoding")]
+    pub fn encoding(mut self, encoding: &'static encoding_rs::Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Write the output in UTF-8 rather than in the encoding inputs are decoded from, see
+    /// [`encoding`](Self::encoding).
+    #[cfg(feature = "encoding")]
+    pub fn utf8_output(mut self, utf8_output: bool) -> Self {
+        self.utf8_output = utf8_output;
+        self
+    }
+
+    /// LZ4-compress buffered `stdin` as it is spilled, and decompress it block by block while
+    /// reversing. This trades a little CPU time for much less disk I/O when spilling large inputs to
+    /// slow storage.
+    #[cfg(feature = "lz4")]
+    pub fn compress_spill(mut self, compress: bool) -> Self {
+        self.compress_spill = compress;
+        self
+    }
+
+    /// Encrypt buffered `stdin` as it is spilled with XChaCha20, using a random key that only ever
+    /// lives in memory, and decrypt it block by block while reversing. Sensitive input therefore
+    /// never reaches the disk in plaintext.
+    ///
+    /// Note that this only protects confidentiality; the spill file is not authenticated.
+    #[cfg(feature = "encrypt")]
+    pub fn encrypt_spill(mut self, encrypt: bool) -> Self {
+        self.encrypt_spill = encrypt;
+        self
+    }
+
+    /// Allocate the buffers that small files and `stdin` are read into with `allocator` instead of
+    /// the global allocator, e.g. to account for or cap the memory used by reversals.
+    ///
+    /// Memory-mapped inputs and spill files are not allocated, and the allocator is not used for
+    /// anything else.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::allocator_api2::alloc::Global;
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let mut result = vec![];
+    /// ReverseOptions::new()
+    ///     .buffer_allocator(Global)
+    ///     .run(&mut result, Some("Cargo.toml"))
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "allocator_api")]
+    pub fn buffer_allocator<A>(mut self, allocator: A) -> Self
+    where
+        A: allocator_api2::alloc::Allocator + Send + Sync + 'static,
+    {
+        self.buffer_allocator = Some(Arc::new(allocator));
+        self
+    }
+
+    /// Write the reversed content from `path` into `writer`, last line first.
+    ///
+    /// See [`reverse_file`](crate::reverse_file) for the meaning of `path`.
+    pub fn run<W: Write, P: AsRef<Path>>(&self, writer: &mut W, path: Option<P>) -> Result<(), Error> {
+        crate::reverse(
+            &mut [Output::new(writer)],
+            crate::Input::Path(path.as_ref().map(AsRef::as_ref)),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(drop)
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed content from `path` into `writer`, like [`run`](Self::run), returning
+    /// what became of the input, see [`reverse_file_with_stats`](crate::reverse_file_with_stats).
+    pub fn run_with_stats<W: Write, P: AsRef<Path>>(&self, writer: &mut W, path: Option<P>) -> Result<Stats, Error> {
+        crate::reverse(
+            &mut [Output::new(writer)],
+            crate::Input::Path(path.as_ref().map(AsRef::as_ref)),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed content read from `reader` into `writer`, last line first.
+    ///
+    /// See [`reverse_reader`](crate::reverse_reader) for how `reader` is buffered.
+    pub fn run_reader<W: Write, R: Read>(&self, writer: &mut W, reader: &mut R) -> Result<(), Error> {
+        crate::reverse(
+            &mut [Output::new(writer)],
+            crate::Input::Reader(reader),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(drop)
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed records of the file at `path` that overlap `range` into `writer`, last
+    /// line first, see [`reverse_range`](crate::reverse_range).
+    ///
+    /// The file is opened like [`open`](Self::open) does. Offsets written by
+    /// [`output_format`](Self::output_format) are offsets in the whole file.
+    ///
+    /// ## Errors
+    ///
+    /// A range cannot be snapped to the matches of a regular expression, so this fails with
+    /// [`std::io::ErrorKind::InvalidInput`] if the separator is a regular expression.
+    pub fn run_range<W: Write, P: AsRef<Path>>(&self, writer: &mut W, path: P, range: Range<u64>) -> Result<(), Error> {
+        let Some(separator) = self.separator.bytes() else {
+            return Err(Error::Other(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "byte ranges cannot be snapped to a regex separator",
+            )));
+        };
+        let path = path.as_ref();
+        let contents = self.open(Some(path))?;
+        let range = crate::snap_range(&contents, range, separator, self.before);
+        crate::reverse(
+            &mut [Output::new(writer)],
+            crate::Input::Range(path, &contents[range.clone()], range.start as u64),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(drop)
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed content of `bytes` into `writer`, last line first, see
+    /// [`reverse_bytes`](crate::reverse_bytes).
+    pub fn run_bytes<W: Write>(&self, writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+        crate::reverse(
+            &mut [Output::new(writer)],
+            crate::Input::Bytes(bytes),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(drop)
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed content from `path` into the pipe `pipe`, like [`run`](Self::run), but
+    /// without copying the records if possible.
+    ///
+    /// If the input is memory-mapped and its records are written as they are, the pages holding them
+    /// are handed over to the pipe with `vmsplice`. Otherwise, or if `pipe` is not a pipe, this
+    /// falls back to writing into it as usual. The reader of the pipe may see later changes to the
+    /// input, which must not be modified until it has been read.
+    #[cfg(all(feature = "linux-zerocopy", target_os = "linux"))]
+    pub fn run_spliced<P: AsRef<Path>>(&self, pipe: std::os::fd::BorrowedFd<'_>, path: Option<P>) -> Result<(), Error> {
+        crate::splice::run(self, pipe, path.as_ref().map(AsRef::as_ref))
+    }
+
+    /// Write the reversed content from `path` into each of `outputs`, in a single pass over the
+    /// input.
+    ///
+    /// Every output receives the records its filter accepts, in the configured format. An error
+    /// from any of them (including [`Stop`](crate::Stop)) ends the reversal for all of them.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{Output, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-fanout.log");
+    /// std::fs::write(&path, "ok\nERROR 1\nok\nERROR 2\n").unwrap();
+    ///
+    /// let mut all = vec![];
+    /// let mut errors = vec![];
+    /// ReverseOptions::new()
+    ///     .run_fanout(
+    ///         &mut [
+    ///             Output::new(&mut all),
+    ///             Output::new(&mut errors).filter(|record| record.starts_with(b"ERROR")),
+    ///         ],
+    ///         Some(&path),
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(all, b"ERROR 2\nok\nERROR 1\nok\n");
+    /// assert_eq!(errors, b"ERROR 2\nERROR 1\n");
+    /// ```
+    pub fn run_fanout<P: AsRef<Path>>(&self, outputs: &mut [Output], path: Option<P>) -> Result<(), Error> {
+        crate::reverse(
+            outputs,
+            crate::Input::Path(path.as_ref().map(AsRef::as_ref)),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(drop)
+        .map_err(Error::from)
+    }
+
+    /// Write the reversed content from each of `paths` into `writer` in turn, returning the result
+    /// of every input.
+    ///
+    /// See [`reverse_files`](crate::reverse_files) for how failures are handled.
+    pub fn run_files<W: Write, P: AsRef<Path>>(&self, writer: &mut W, paths: &[Option<P>]) -> Vec<Result<(), Error>> {
+        let summary = self.run_all(writer, paths);
+        summary.inputs.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Write the reversed content from each of `paths` into `writer` in turn, like
+    /// [`run_files`](Self::run_files), summarizing what became of every input.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-run-all.txt");
+    /// std::fs::write(&path, "one\ntwo\n").unwrap();
+    /// let missing = path.with_extension("missing");
+    ///
+    /// let mut result = vec![];
+    /// let summary = ReverseOptions::new().run_all(&mut result, &[Some(&missing), Some(&path)]);
+    ///
+    /// assert_eq!(result, b"two\none\n");
+    /// assert_eq!((summary.succeeded(), summary.failed()), (1, 1));
+    /// assert_eq!(summary.errors().next().unwrap().0, Some(missing.as_path()));
+    /// ```
+    pub fn run_all<W: Write, P: AsRef<Path>>(&self, writer: &mut W, paths: &[Option<P>]) -> crate::Summary {
+        crate::reverse_all(writer, paths.iter().map(|path| path.as_ref().map(AsRef::as_ref)), self)
+    }
+
+    /// Count the records [`run`](Self::run) would write for the input at `path`, without writing
+    /// any, see [`count_records`](crate::count_records).
+    pub fn count<P: AsRef<Path>>(&self, path: Option<P>) -> Result<u64, Error> {
+        crate::reverse(
+            &mut [Output::new(&mut std::io::sink())],
+            crate::Input::Path(path.as_ref().map(AsRef::as_ref)),
+            self,
+            &mut self.new_buffer(),
+        )
+        .map(|stats| stats.records)
+        .map_err(Error::from)
+    }
+
+    /// Inspect the input [`run`](Self::run) would read from `path` without producing any output,
+    /// see [`probe_file`](crate::probe_file).
+    pub fn probe<P: AsRef<Path>>(&self, path: Option<P>) -> Result<crate::InputInfo, Error> {
+        crate::probe(path.as_ref().map(AsRef::as_ref), self).map_err(Error::from)
+    }
+
+    /// Read or map the whole input at `path` (`stdin` if `None`), to process its records one by one
+    /// instead of writing them into a writer.
+    ///
+    /// Inputs are mapped or read as [`run`](Self::run) would, except that inputs which cannot be
+    /// mapped are read into memory in full, without ever being spilled.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::ReverseOptions;
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-open.txt");
+    /// std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    ///
+    /// let contents = ReverseOptions::new().open(Some(&path)).unwrap();
+    /// let lengths: Vec<_> = contents.rev_records(b'\n').map(<[u8]>::len).collect();
+    ///
+    /// assert_eq!(lengths, [6, 4, 4]);
+    /// ```
+    pub fn open<P: AsRef<Path>>(&self, path: Option<P>) -> Result<crate::Contents, Error> {
+        crate::contents::open(path.as_ref().map(AsRef::as_ref), self).map_err(Error::from)
+    }
+
+    /// Check that the input at `path` (`stdin` if `None`) is valid UTF-8 without producing any
+    /// output, returning where the first invalid sequence is otherwise.
+    ///
+    /// Inputs are read or mapped as [`run`](Self::run) would, but never spilled: inputs that
+    /// cannot be mapped are validated as they are read.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use tac_k_lib::{InvalidUtf8, ReverseOptions};
+    ///
+    /// let path = std::env::temp_dir().join("tac-k-check-utf8.txt");
+    /// std::fs::write(&path, b"one\ntw\xffo\n").unwrap();
+    ///
+    /// let invalid = ReverseOptions::new().check_utf8(Some(&path)).unwrap();
+    /// assert_eq!(invalid, Some(InvalidUtf8 { offset: 6, record_offset: 4 }));
+    /// ```
+    pub fn check_utf8<P: AsRef<Path>>(&self, path: Option<P>) -> Result<Option<crate::InvalidUtf8>, Error> {
+        crate::utf8::check(path.as_ref().map(AsRef::as_ref), self).map_err(Error::from)
+    }
+
+    /// Write the reversed content read from `reader` into `writer`, last line first.
+    ///
+    /// See [`reverse_async`](crate::reverse_async) for how the input is buffered. Options about
+    /// `stdin` and spilling do not apply.
+    #[cfg(feature = "async")]
+    pub async fn run_async<R, W>(&self, reader: &mut R, writer: &mut W) -> Result<(), Error>
+    where
+        R: futures_util::io::AsyncRead + Unpin + ?Sized,
+        W: futures_util::io::AsyncWrite + Unpin + ?Sized,
+    {
+        crate::async_io::reverse(reader, writer, self)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Write the reversed content from the file at `path` (`stdin` if `None`) into `writer`, last
+    /// line first, like [`run`](Self::run) but without blocking the executor.
+    ///
+    /// See [`reverse_file_async`](crate::reverse_file_async) for how the input is reversed.
+    #[cfg(feature = "async")]
+    pub async fn run_file_async<W, P>(&self, writer: &mut W, path: Option<P>) -> Result<(), Error>
+    where
+        W: futures_util::io::AsyncWrite + Unpin + ?Sized,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().map(|path| path.as_ref().to_path_buf());
+        crate::async_io::reverse_file(writer, path, self.clone())
+            .await
+            .map_err(Error::from)
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use std::io::{Result, Write};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver};
+
+use crate::cache::CacheSizes;
+use crate::emit::{Emitter, Output};
+use crate::{Cancelled, Input, ReverseOptions, Summary};
+
+/// Size of the chunks an input is split into to be searched in parallel if the size of the caches
+/// is unknown.
+const DEFAULT_CHUNK_SIZE: usize = 16 * 1024 * 1024; // 16 MiB
+
+/// Bounds of the size of the chunks sized after the caches: searching a chunk on a thread of its
+/// own is not worth it for less, nor are more needed to keep a thread busy.
+const MIN_CHUNK_SIZE: usize = 1024 * 1024; // 1 MiB
+const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024; // 64 MiB
+
+/// Size of the largest file [`reverse_all`] reverses into memory ahead of writing it.
+pub(crate) const MAX_BUFFERED_FILE: u64 = 4 * 1024 * 1024; // 4 MiB
+
+/// Work handed over to another thread by [`scope`].
+type Job<'env> = Box<dyn FnOnce() + Send + 'env>;
+
+/// Call `body` with a function that runs jobs on other threads, and return once all of them are
+/// done. They run on threads of their own, or on the pool given to
+/// [`ReverseOptions::thread_pool`].
+fn scope<'env, R>(options: &ReverseOptions, body: impl FnOnce(&mut dyn FnMut(Job<'env>)) -> R) -> R {
+    #[cfg(feature = "rayon")]
+    if let Some(pool) = &options.thread_pool {
+        return pool.in_place_scope(|scope| body(&mut |job: Job<'env>| scope.spawn(move |_| job())));
+    }
+    #[cfg(not(feature = "rayon"))]
+    let _ = options;
+    std::thread::scope(|scope| {
+        body(&mut |job| {
+            scope.spawn(job);
+        })
+    })
+}
+
+/// Wait for what a job of [`scope`] sends through `receiver`, or `None` if it panicked, which
+/// [`scope`] resumes once all jobs are done.
+fn wait<T>(receiver: &Receiver<T>) -> Option<T> {
+    // The job may be queued on the pool of the very thread waiting for it, which then has to run it.
+    #[cfg(feature = "rayon")]
+    while rayon::current_thread_index().is_some() {
+        match receiver.try_recv() {
+            Ok(value) => return Some(value),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => return None,
+            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                if rayon::yield_now() != Some(rayon::Yield::Executed) {
+                    std::thread::yield_now();
+                }
+            }
+        }
+    }
+    receiver.recv().ok()
+}
+
+/// Size of the chunks to search on `threads` threads with caches of `sizes`.
+///
+/// The chunks being searched and the one whose records are being emitted all fit in the shared L3
+/// cache, so that their records are written from the cache rather than read from memory again,
+/// yet each of them is at least as large as the L2 cache of a core. Chunks are a multiple of the
+/// page size, so that those of a mapping start on a page.
+pub(crate) fn chunk_size(threads: usize, sizes: CacheSizes) -> usize {
+    let size = match (sizes.l3, sizes.l2) {
+        (Some(l3), l2) => (l3 / (threads + 1)).max(l2.unwrap_or(0)),
+        (None, Some(l2)) => l2,
+        (None, None) => DEFAULT_CHUNK_SIZE,
+    };
+    let size = size.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+    (size - size % sizes.page).max(sizes.page)
+}
+
+/// Emit every record of `bytes` separated by `separator`, last record first, like
+/// [`emit_reversed`](crate::emit_reversed), but search for separators on up to `threads` threads.
+///
+/// `bytes` is split into chunks of `chunk_size` bytes that are searched from the end of the input
+/// to its start, keeping `threads` of them in flight while the records of the last one found are
+/// emitted. `base` is the offset of `bytes` in the input.
+pub(crate) fn emit_reversed(
+    bytes: &[u8],
+    base: u64,
+    separator: u8,
+    threads: usize,
+    chunk_size: usize,
+    options: &ReverseOptions,
+    emitter: &mut Emitter,
+) -> Result<()> {
+    if base == 0 {
+        emitter.lead(bytes)?;
+    }
+    let before = emitter.before();
+    let mut last_printed = bytes.len();
+    // The backend may be pinned on this thread only.
+    let backend = crate::search::backend();
+    // The records are read ahead as they are emitted, which is too late for the chunks searched.
+    let read_ahead = emitter.prefetching();
+    #[cfg(target_os = "linux")]
+    let nodes = crate::numa::nodes();
+    // The threads of a pool given to `thread_pool` are not ours to move.
+    #[cfg(all(target_os = "linux", feature = "rayon"))]
+    let nodes = nodes.filter(|_| options.thread_pool.is_none());
+    scope(options, |spawn| {
+        let mut chunks = (0..bytes.len()).step_by(chunk_size).rev().map(|start| {
+            let chunk = &bytes[start..bytes.len().min(start + chunk_size)];
+            if read_ahead {
+                crate::advice::will_need(chunk);
+            }
+            let (sender, receiver) = sync_channel(1);
+            spawn(Box::new(move || {
+                #[cfg(target_os = "linux")]
+                if let Some(nodes) = nodes {
+                    crate::numa::bind_to(nodes, chunk);
+                }
+                // Offsets right after every separator in the chunk, last one first.
+                let mut found = Vec::new();
+                let result = backend.search(chunk, separator, &mut |offset| {
+                    found.push(start + offset);
+                    Ok(())
+                });
+                // Nobody is waiting any more if emitting failed.
+                let _ = sender.send(result.map(|()| found));
+            }));
+            receiver
+        });
+        let batch_size = emitter.batch_size();
+        let mut pending: VecDeque<_> = chunks.by_ref().take(threads.max(1)).collect();
+        while let Some(searching) = pending.pop_front() {
+            let Some(found) = wait(&searching) else { break };
+            let found = found?;
+            pending.extend(chunks.next());
+            let mut batch = Vec::with_capacity(batch_size);
+            for end in found {
+                let start = if before { end - 1 } else { end };
+                if start != 0 || !before {
+                    batch.push((&bytes[start..last_printed], base + start as u64));
+                    last_printed = start;
+                    if batch.len() == batch_size {
+                        emitter.records(&batch)?;
+                        batch.clear();
+                    }
+                }
+            }
+            emitter.records(&batch)?;
+        }
+        Ok::<_, std::io::Error>(())
+    })?;
+    if last_printed != 0 {
+        emitter.record(&bytes[..last_printed], base)?;
+    }
+    Ok(())
+}
+
+/// Write the reversed content of each of `paths` into `writer` in turn, like
+/// [`reverse_all`](crate::reverse_all), but reverse up to `threads` files of at most
+/// [`MAX_BUFFERED_FILE`] bytes at once, each into memory, while the ones before them are written.
+/// Other inputs are reversed on the calling thread when their turn comes.
+pub(crate) fn reverse_all(
+    writer: &mut dyn Write,
+    paths: &[Option<&Path>],
+    threads: usize,
+    options: &ReverseOptions,
+) -> Summary {
+    let mut buf = options.new_buffer();
+    let mut summary = Summary::default();
+    let mut paths = paths.iter().copied();
+    scope(options, |spawn| {
+        let mut pending = VecDeque::new();
+        loop {
+            while pending.len() < threads {
+                let Some(path) = paths.next() else { break };
+                let small = path
+                    .and_then(|path| std::fs::metadata(path).ok())
+                    .is_some_and(|metadata| metadata.is_file() && metadata.len() <= MAX_BUFFERED_FILE);
+                if !small {
+                    pending.push_back((path, None));
+                    continue;
+                }
+                let (sender, receiver) = sync_channel(1);
+                spawn(Box::new(move || {
+                    let mut reversed = Vec::new();
+                    let result = crate::reverse(
+                        &mut [Output::new(&mut reversed)],
+                        Input::Path(path),
+                        options,
+                        &mut options.new_buffer(),
+                    );
+                    // Nobody is waiting any more if writing failed.
+                    let _ = sender.send((reversed, result.map(drop)));
+                }));
+                pending.push_back((path, Some(receiver)));
+            }
+            let Some((path, reversing)) = pending.pop_front() else {
+                break;
+            };
+            let (result, failed) = match reversing {
+                None => {
+                    let mut outputs = [Output::new(&mut *writer)];
+                    let result = crate::reverse(&mut outputs, Input::Path(path), options, &mut buf);
+                    (result.map(drop), outputs[0].failed)
+                }
+                Some(receiver) => {
+                    let Some((reversed, result)) = wait(&receiver) else {
+                        break;
+                    };
+                    let written = writer.write_all(&reversed);
+                    let failed = written.is_err();
+                    (
+                        crate::error::context(written, |source| crate::Error::Write { source }).and(result),
+                        failed,
+                    )
+                }
+            };
+            let cancelled = result.as_ref().is_err_and(Cancelled::is);
+            summary
+                .inputs
+                .push((path.map(Path::to_path_buf), result.map_err(crate::Error::from)));
+            if failed || cancelled {
+                summary.skipped = pending.len();
+                break;
+            }
+        }
+    });
+    summary.skipped += paths.count();
+    summary
+}
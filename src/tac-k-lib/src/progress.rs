@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Rolling estimate of the throughput of a reversal and of the time it has left, fed from the
+/// progress hook, see [`ReverseOptions::on_progress`].
+///
+/// Only the progress reported over the last `window` is taken into account, so the estimate
+/// follows changes in speed, e.g. once a mapping's pages are cached or the output starts blocking.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use tac_k_lib::{ReverseOptions, Throughput};
+///
+/// let mut throughput = Throughput::new(Duration::from_secs(10));
+/// let mut result = vec![];
+/// ReverseOptions::new()
+///     .on_progress(move |done, total| {
+///         throughput.update(done, total);
+///         if let Some(eta) = throughput.eta() {
+///             eprintln!("{done}/{total} bytes, done in {eta:?}");
+///         }
+///     })
+///     .run(&mut result, Some("Cargo.toml"))
+///     .unwrap();
+/// ```
+///
+/// [`ReverseOptions::on_progress`]: crate::ReverseOptions::on_progress
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    window: Duration,
+    /// Reported progress, oldest first, along with when it was reported.
+    samples: VecDeque<(Instant, u64)>,
+    /// Total length of the input being reversed.
+    total: u64,
+}
+
+impl Throughput {
+    /// Estimate the throughput over the last `window`.
+    pub fn new(window: Duration) -> Self {
+        Throughput {
+            window,
+            samples: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    /// Record that `done` of `total` bytes have been processed by now.
+    ///
+    /// Progress going backwards or a different `total` means a new input is being reversed, which
+    /// starts a new estimate.
+    pub fn update(&mut self, done: u64, total: u64) {
+        self.update_at(Instant::now(), done, total);
+    }
+
+    /// Record that `done` of `total` bytes had been processed at `now`, see [`Throughput::update`].
+    pub fn update_at(&mut self, now: Instant, done: u64, total: u64) {
+        if total != self.total || self.samples.back().is_some_and(|&(_, last)| done < last) {
+            self.samples.clear();
+            self.total = total;
+        }
+        self.samples.push_back((now, done));
+        // Keep the newest sample older than the window as well, so the window is fully covered.
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|&(time, _)| now.saturating_duration_since(time) >= self.window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Number of bytes processed per second over the window, if enough progress has been recorded
+    /// to tell.
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let (&(first_time, first), &(last_time, last)) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = last_time.saturating_duration_since(first_time).as_secs_f64();
+        if elapsed == 0.0 {
+            return None;
+        }
+        Some((last - first) as f64 / elapsed)
+    }
+
+    /// Time left until the whole input is processed at the current throughput, if it can be
+    /// estimated.
+    pub fn eta(&self) -> Option<Duration> {
+        let &(_, done) = self.samples.back()?;
+        let rate = self.bytes_per_sec()?;
+        if done >= self.total {
+            return Some(Duration::ZERO);
+        }
+        if rate <= 0.0 {
+            return None;
+        }
+        Duration::try_from_secs_f64((self.total - done) as f64 / rate).ok()
+    }
+}
@@ -0,0 +1,68 @@
+/// Iterate over the records of `bytes` separated by `separator`, last record first.
+///
+/// Records are yielded exactly as [`reverse_file`](crate::reverse_file) would write them, i.e.
+/// including their trailing separator, if any. Like [`rfind_iter`](crate::rfind_iter), each step
+/// resumes the SIMD-accelerated backwards scan where the previous one stopped.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::rev_records;
+///
+/// let records: Vec<_> = rev_records(b"one\ntwo\nthree", b'\n').collect();
+/// assert_eq!(records, [&b"three"[..], b"two\n", b"one\n"]);
+/// ```
+pub fn rev_records(bytes: &[u8], separator: u8) -> RevRecords<'_> {
+    RevRecords {
+        bytes,
+        separator,
+        end: bytes.len(),
+    }
+}
+
+/// Iterator returned by [`rev_records`].
+#[derive(Debug, Clone)]
+pub struct RevRecords<'a> {
+    bytes: &'a [u8],
+    separator: u8,
+    /// End of the part of the input not yielded yet.
+    end: usize,
+}
+
+impl<'a> Iterator for RevRecords<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.end == 0 {
+            return None;
+        }
+        // The last byte is either the record's own separator or part of the leading fragment.
+        let start = crate::rfind(&self.bytes[..self.end - 1], &[self.separator]).map_or(0, |index| index + 1);
+        let record = &self.bytes[start..self.end];
+        self.end = start;
+        Some(record)
+    }
+}
+
+/// Extension trait adding [`rev_records`] as a method to byte strings, in the style of `bstr`.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::RevRecordsExt;
+///
+/// let last = b"one\ntwo\n".rev_records(b'\n').next();
+/// assert_eq!(last, Some(&b"two\n"[..]));
+/// ```
+#[cfg(feature = "bstr")]
+pub trait RevRecordsExt {
+    /// Iterate over the records separated by `separator`, last record first, see [`rev_records`].
+    fn rev_records(&self, separator: u8) -> RevRecords<'_>;
+}
+
+#[cfg(feature = "bstr")]
+impl RevRecordsExt for [u8] {
+    fn rev_records(&self, separator: u8) -> RevRecords<'_> {
+        rev_records(self, separator)
+    }
+}
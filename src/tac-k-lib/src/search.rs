@@ -0,0 +1,851 @@
+#[cfg(feature = "std")]
+use std::cell::Cell;
+
+#[cfg(feature = "std")]
+use crate::Engine;
+
+/// Whether the CPU supports every target feature given, detected at runtime by `$detect` with
+/// `std`, and going by the features enabled at compile time without.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+macro_rules! detected {
+    ($detect:ident, $($feature:tt),+) => {{
+        #[cfg(feature = "std")]
+        let detected = $(std::arch::$detect!($feature))&&+;
+        #[cfg(not(feature = "std"))]
+        let detected = cfg!(all($(target_feature = $feature),+));
+        detected
+    }};
+}
+
+/// Callback of the search functions, invoked with the index right after every separator found, from
+/// the end of the haystack to its start. Returning an error aborts the search.
+pub(crate) type OnMatch<'a> = dyn FnMut(usize) -> Result<(), Error> + 'a;
+
+/// Error aborting a search: any I/O error with `std`, and merely the fact that it was aborted
+/// without.
+#[cfg(feature = "std")]
+pub(crate) type Error = std::io::Error;
+#[cfg(not(feature = "std"))]
+pub(crate) type Error = Abort;
+
+/// A search aborted by its callback, see [`Error`].
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub(crate) struct Abort;
+
+/// An error aborting a search that carries nothing else, for callers that keep their own.
+#[cfg(feature = "std")]
+pub(crate) fn abort() -> Error {
+    std::io::ErrorKind::Other.into()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn abort() -> Error {
+    Abort
+}
+
+/// A search implementation, see [`search_auto`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+    Avx512,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+    #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+    Rvv,
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    Simd128,
+    #[cfg(feature = "memchr")]
+    Memchr,
+    Scalar,
+}
+
+/// The backend chosen by [`calibrate_search`], if it has run.
+#[cfg(feature = "std")]
+static CALIBRATED: std::sync::OnceLock<Backend> = std::sync::OnceLock::new();
+
+impl Backend {
+    /// The fastest backend this machine supports, going by its instruction set alone.
+    #[inline]
+    fn detect() -> Backend {
+        #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+        if Backend::Avx512.supported() {
+            return Backend::Avx512;
+        }
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if Backend::Avx2.supported() {
+            return Backend::Avx2;
+        }
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if Backend::Sse2.supported() {
+            return Backend::Sse2;
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        if Backend::Neon.supported() {
+            return Backend::Neon;
+        }
+
+        #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+        if Backend::Rvv.supported() {
+            return Backend::Rvv;
+        }
+
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+        if Backend::Simd128.supported() {
+            return Backend::Simd128;
+        }
+
+        #[cfg(feature = "memchr")]
+        if Backend::Memchr.supported() {
+            return Backend::Memchr;
+        }
+
+        Backend::Scalar
+    }
+
+    /// Whether this machine supports the instructions of the backend.
+    #[inline]
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn supported(self) -> bool {
+        match self {
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Backend::Avx512 => detected!(is_x86_feature_detected, "avx512f", "avx512bw", "lzcnt"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => detected!(is_x86_feature_detected, "avx2", "lzcnt", "bmi2"),
+            // Part of the x86_64 baseline, but not of every 32-bit x86 CPU.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => detected!(is_x86_feature_detected, "sse2"),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => detected!(is_aarch64_feature_detected, "neon"),
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Backend::Rvv => rvv_detected(),
+            // WebAssembly has no runtime detection, the module is rejected if SIMD is unsupported.
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Backend::Simd128 => true,
+            #[cfg(feature = "memchr")]
+            Backend::Memchr => true,
+            Backend::Scalar => true,
+        }
+    }
+
+    /// Every backend this machine supports, presumably fastest first.
+    #[cfg(feature = "std")]
+    pub(crate) fn available() -> Vec<Backend> {
+        [
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Backend::Avx512,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2,
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon,
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Backend::Rvv,
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Backend::Simd128,
+            #[cfg(feature = "memchr")]
+            Backend::Memchr,
+            Backend::Scalar,
+        ]
+        .into_iter()
+        .filter(|backend| backend.supported())
+        .collect()
+    }
+
+    /// The engine selecting this backend, see [`Engine::backend`].
+    #[cfg(feature = "std")]
+    pub(crate) fn engine(self) -> Engine {
+        match self {
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Backend::Avx512 => Engine::Avx512,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => Engine::Avx2,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => Engine::Sse2,
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => Engine::Neon,
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Backend::Rvv => Engine::Rvv,
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Backend::Simd128 => Engine::Simd128,
+            #[cfg(feature = "memchr")]
+            Backend::Memchr => Engine::Memchr,
+            Backend::Scalar => Engine::Scalar,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Backend::Avx512 => "AVX-512",
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => "AVX2",
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => "SSE2",
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => "NEON",
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Backend::Rvv => "RVV",
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Backend::Simd128 => "SIMD128",
+            #[cfg(feature = "memchr")]
+            Backend::Memchr => "memchr",
+            Backend::Scalar => "scalar",
+        }
+    }
+
+    pub(crate) fn search(self, bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+        match self {
+            // Only returned by `available` when the CPU supports the required features.
+            #[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+            Backend::Avx512 => unsafe { search512(bytes, separator, on_match) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe { search256(bytes, separator, on_match) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => unsafe { search128_x86(bytes, separator, on_match) },
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => unsafe { search128(bytes, separator, on_match) },
+            #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+            Backend::Rvv => unsafe { search_rvv(bytes, separator, on_match) },
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            Backend::Simd128 => search128_wasm(bytes, separator, on_match),
+            #[cfg(feature = "memchr")]
+            Backend::Memchr => search_memchr(bytes, separator, on_match),
+            Backend::Scalar => search(bytes, separator, on_match),
+        }
+    }
+}
+
+/// Time every search implementation this machine supports on a small sample and use the fastest one
+/// from then on, returning its name.
+///
+/// By default the widest SIMD implementation available is used, which is not the fastest on every
+/// CPU, e.g. when AVX2 lowers the clock speed or NEON is emulated. Calibrating takes well under a
+/// millisecond, and only happens on the first call: the winner is kept for the rest of the process.
+///
+/// ## Example
+///
+/// ```
+/// let backend = tac_k_lib::calibrate_search();
+/// assert_eq!(tac_k_lib::capabilities().simd, backend);
+/// ```
+#[cfg(feature = "std")]
+pub fn calibrate_search() -> &'static str {
+    CALIBRATED
+        .get_or_init(|| {
+            // Pseudo-random bytes with a separator every 64 bytes or so, like a typical text file.
+            let mut state = 0x2545_f491_4f6c_dd1d_u64;
+            let sample: Vec<u8> = (0..64 * 1024)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    if state.is_multiple_of(64) {
+                        b'\n'
+                    } else {
+                        b'a' + (state % 26) as u8
+                    }
+                })
+                .collect();
+            let time = |backend: Backend| {
+                (0..5)
+                    .map(|_| {
+                        let start = crate::now();
+                        let mut matches = 0;
+                        let _ = backend.search(std::hint::black_box(&sample), b'\n', &mut |_| {
+                            matches += 1;
+                            Ok(())
+                        });
+                        std::hint::black_box(matches);
+                        start.map(|start| start.elapsed())
+                    })
+                    .min()
+                    .unwrap_or_default()
+            };
+            // Ties go to the backend that would have been picked anyway.
+            Backend::available()
+                .into_iter()
+                .min_by_key(|&backend| time(backend))
+                .unwrap_or(Backend::Scalar)
+        })
+        .name()
+}
+
+/// Name of the search implementation [`search_auto`] picks on this machine.
+pub(crate) fn search_backend() -> &'static str {
+    backend().name()
+}
+
+/// The search implementation to use: the one pinned on this thread by [`ReverseOptions::engine`],
+/// or the one picked by [`calibrate_search`] if it has run.
+#[inline]
+pub(crate) fn backend() -> Backend {
+    #[cfg(feature = "std")]
+    if let Some(backend) = PINNED.with(Cell::get).or_else(|| CALIBRATED.get().copied()) {
+        return backend;
+    }
+    // Neither can be set without `std`.
+    Backend::detect()
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The backend pinned by a [`PinnedEngine`] on this thread.
+    pub(crate) static PINNED: Cell<Option<Backend>> = const { Cell::new(None) };
+}
+
+/// Makes [`backend`] return the backend of an [`Engine`] on this thread, until dropped.
+#[cfg(feature = "std")]
+pub(crate) struct PinnedEngine {
+    previous: Option<Backend>,
+}
+
+#[cfg(feature = "std")]
+impl PinnedEngine {
+    /// Pin `engine`, which fails with [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported)
+    /// if this machine does not support it. [`Engine::Auto`] keeps the current backend.
+    pub(crate) fn new(engine: Engine) -> std::io::Result<PinnedEngine> {
+        let backend = match engine {
+            Engine::Auto => None,
+            engine => Some(engine.backend().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!("the {} search is not supported on this machine", engine.name()),
+                )
+            })?),
+        };
+        let previous = PINNED.with(|pinned| pinned.replace(backend.or(pinned.get())));
+        Ok(PinnedEngine { previous })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for PinnedEngine {
+    fn drop(&mut self) {
+        PINNED.with(|pinned| pinned.set(self.previous));
+    }
+}
+
+pub(crate) fn search_auto(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    backend().search(bytes, separator, on_match)
+}
+
+/// Like [`search_auto`], but for a separator of any length: the haystack is scanned for its last
+/// byte, and every candidate is verified. Occurrences overlapping one found later in the haystack
+/// are skipped.
+#[cfg(feature = "std")]
+pub(crate) fn search_separator(bytes: &[u8], separator: &[u8], on_match: &mut OnMatch) -> Result<(), Error> {
+    let (&anchor, _) = separator.split_last().expect("the separator must not be empty");
+    if separator.len() == 1 {
+        return search_auto(bytes, anchor, on_match);
+    }
+    // Start of the last occurrence reported so far.
+    let mut end = bytes.len();
+    search_auto(bytes, anchor, &mut |offset| {
+        if offset <= end && bytes[..offset].ends_with(separator) {
+            end = offset - separator.len();
+            on_match(offset)?;
+        }
+        Ok(())
+    })
+}
+
+/// This is the default, naïve byte search
+#[inline(always)]
+pub(crate) fn search(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    slow_search(bytes, 0, bytes.len(), separator, on_match)
+}
+
+/// A portable search built on `memchr::memrchr`, which is vectorized on more targets than the
+/// hand-written SIMD implementations cover, and still much faster than [`search`] elsewhere.
+#[cfg(feature = "memchr")]
+fn search_memchr(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    for index in memchr::memrchr_iter(separator, bytes) {
+        on_match(index + 1)?;
+    }
+
+    Ok(())
+}
+
+#[inline(always)]
+/// Search a range index-by-index and report every match to `on_match`. Primarily used to search
+/// before/after the aligned portion of a range.
+fn slow_search(bytes: &[u8], start: usize, end: usize, separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    for index in (start..end).rev() {
+        if bytes[index] == separator {
+            on_match(index + 1)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[target_feature(enable = "lzcnt")]
+#[target_feature(enable = "bmi2")]
+// Intrinsics are safe to call from a `target_feature` function, but some share an `unsafe` block
+// with the loads through raw pointers that are not.
+#[allow(unused_unsafe)]
+/// This is an AVX2-optimized newline search function that searches a 32-byte (256-bit) window
+/// instead of scanning character-by-character (once aligned). This is a *safe* function, but must
+/// be adorned with `unsafe` to guarantee it's not called without first checking for AVX2 support.
+///
+/// We need to explicitly enable lzcnt support for u32::leading_zeros() to use the `lzcnt`
+/// instruction instead of an extremely slow combination of branching + BSR.
+///
+/// BMI2 is explicitly opted into to inline the BZHI instruction; otherwise a call to the intrinsic
+/// function is added and not inlined.
+unsafe fn search256(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    #[cfg(target_arch = "x86")]
+    const SIZE: u32 = 32;
+    #[cfg(target_arch = "x86_64")]
+    const SIZE: u32 = 64;
+
+    const ALIGNMENT: usize = core::mem::align_of::<__m256i>();
+
+    let ptr = bytes.as_ptr();
+    let len = bytes.len();
+
+    // Too short to hold two aligned blocks once the end is aligned: not worth vectorizing.
+    if len < ALIGNMENT * 3 - 1 {
+        return slow_search(bytes, 0, len, separator, on_match);
+    }
+
+    // We should only use 32-byte (256-bit) aligned reads w/ AVX2 intrinsics.
+    // Search unaligned bytes via slow method so subsequent haystack reads are always aligned.
+    let mut remaining = len;
+    // Regardless of whether or not the base pointer is aligned to a 32-byte address, we are
+    // reading from an arbitrary offset (determined by the length of the lines) and so we must
+    // first calculate a safe place to begin using SIMD operations from.
+    let align_offset = unsafe { ptr.add(len) }.align_offset(ALIGNMENT);
+    if align_offset != 0 {
+        let aligned_index = len + align_offset - ALIGNMENT;
+        debug_assert!(aligned_index < len && aligned_index > 0);
+        debug_assert!((ptr as usize + aligned_index).is_multiple_of(ALIGNMENT));
+
+        // eprintln!("Unoptimized search from {} to {}", aligned_index, len);
+        slow_search(bytes, aligned_index, len, separator, on_match)?;
+        remaining = aligned_index;
+    } else {
+        // `bytes` end in an aligned block, no need to offset
+        debug_assert!((ptr as usize + len).is_multiple_of(ALIGNMENT));
+    }
+
+    let pattern256 = unsafe { _mm256_set1_epi8(separator as i8) };
+    while remaining >= SIZE as usize {
+        let window_end_offset = remaining;
+        unsafe {
+            remaining -= 32;
+            let search256 = _mm256_load_si256(ptr.add(remaining) as *const __m256i);
+            let result256 = _mm256_cmpeq_epi8(search256, pattern256);
+            let part = _mm256_movemask_epi8(result256) as u32;
+            let mut matches;
+
+            // For 32-bit x86 architecture only one part can be loaded. 64-bit x86_64 can load another part
+            // to find the matches.
+            #[cfg(target_arch = "x86")]
+            {
+                matches = part;
+            }
+            #[cfg(target_arch = "x86_64")]
+            {
+                remaining -= 32;
+                let search256 = _mm256_load_si256(ptr.add(remaining) as *const __m256i);
+                let result256 = _mm256_cmpeq_epi8(search256, pattern256);
+                matches = ((part as u64) << 32) | _mm256_movemask_epi8(result256) as u32 as u64;
+            }
+
+            while matches != 0 {
+                // We would count *trailing* zeroes to find new lines in reverse order, but the
+                // result mask is in little endian (reversed) order, so we do the very
+                // opposite.
+                // core::intrinsics::ctlz() is not stabilized, but `u64::leading_zeros()` will
+                // use it directly if the lzcnt or bmi1 features are enabled.
+                let leading = matches.leading_zeros();
+                let offset = window_end_offset - leading as usize;
+
+                on_match(offset)?;
+
+                // Clear this match from the matches bitset.
+                #[cfg(target_arch = "x86")]
+                {
+                    matches = _bzhi_u32(matches, SIZE - 1 - leading);
+                }
+                #[cfg(target_arch = "x86_64")]
+                {
+                    matches = _bzhi_u64(matches, SIZE - 1 - leading);
+                }
+            }
+        }
+    }
+
+    if remaining != 0 {
+        // eprintln!("Unoptimized end search from {} to {}", 0, index);
+        slow_search(bytes, 0, remaining, separator, on_match)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "avx512", target_arch = "x86_64"))]
+#[target_feature(enable = "avx512f")]
+#[target_feature(enable = "avx512bw")]
+#[target_feature(enable = "lzcnt")]
+/// An AVX-512-optimized version of [`search256`], which compares a 64-byte (512-bit) window at a
+/// time straight into a mask register. Like it, this must only be called once AVX-512BW support
+/// has been checked for.
+unsafe fn search512(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    use core::arch::x86_64::*;
+
+    const SIZE: usize = 64;
+
+    let ptr = bytes.as_ptr();
+    let len = bytes.len();
+
+    // Aligned loads do not cross cache lines, so search the unaligned end of the haystack slowly.
+    // Too short to hold two aligned windows once the end is aligned: not worth vectorizing.
+    if len < SIZE * 3 - 1 {
+        return slow_search(bytes, 0, len, separator, on_match);
+    }
+
+    let mut remaining = len;
+    let align_offset = unsafe { ptr.add(len) }.align_offset(SIZE);
+    if align_offset != 0 {
+        let aligned_index = len + align_offset - SIZE;
+        slow_search(bytes, aligned_index, len, separator, on_match)?;
+        remaining = aligned_index;
+    }
+
+    let pattern512 = _mm512_set1_epi8(separator as i8);
+    while remaining >= SIZE {
+        let window_end_offset = remaining;
+        remaining -= SIZE;
+        let search512 = unsafe { _mm512_load_si512(ptr.add(remaining) as *const _) };
+        let mut matches = _mm512_cmpeq_epi8_mask(search512, pattern512);
+
+        while matches != 0 {
+            // The mask is in little endian order, so the last match is the highest bit.
+            let leading = matches.leading_zeros();
+            on_match(window_end_offset - leading as usize)?;
+            matches &= !(1 << (SIZE as u32 - 1 - leading));
+        }
+    }
+
+    if remaining != 0 {
+        slow_search(bytes, 0, remaining, separator, on_match)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+#[allow(unused_unsafe)]
+/// An SSE2-optimized newline search function for x86 CPUs without AVX2, e.g. older ones or virtual
+/// machines masking it, which searches four 16-byte (128-bit) windows at a time like [`search128`]
+/// does on ARM. This must only be called once SSE2 support has been checked for, which only
+/// matters on 32-bit x86.
+unsafe fn search128_x86(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    const SIZE: usize = 64;
+    const ALIGNMENT: usize = core::mem::align_of::<__m128i>();
+
+    let ptr = bytes.as_ptr();
+    let len = bytes.len();
+
+    // Search the unaligned end of the haystack slowly, so that every load is aligned.
+    // Too short to hold an aligned window once the end is aligned: not worth vectorizing.
+    if len < SIZE + ALIGNMENT - 1 {
+        return slow_search(bytes, 0, len, separator, on_match);
+    }
+
+    let mut remaining = len;
+    let align_offset = unsafe { ptr.add(len) }.align_offset(ALIGNMENT);
+    if align_offset != 0 {
+        let aligned_index = len + align_offset - ALIGNMENT;
+        slow_search(bytes, aligned_index, len, separator, on_match)?;
+        remaining = aligned_index;
+    }
+
+    let pattern128 = unsafe { _mm_set1_epi8(separator as i8) };
+    while remaining >= SIZE {
+        let window_end_offset = remaining;
+        remaining -= SIZE;
+        // Gather the 16-bit masks of the four windows into one, the first window lowest.
+        let mut matches = 0;
+        for window in (0..SIZE / 16).rev() {
+            let mask = unsafe {
+                let search128 = _mm_load_si128(ptr.add(remaining + window * 16) as *const __m128i);
+                _mm_movemask_epi8(_mm_cmpeq_epi8(search128, pattern128))
+            };
+            matches = matches << 16 | mask as u16 as u64;
+        }
+
+        while matches != 0 {
+            // The mask is in little endian order, so the last match is the highest bit.
+            let leading = matches.leading_zeros();
+            on_match(window_end_offset - leading as usize)?;
+            matches &= !(1 << (SIZE as u32 - 1 - leading));
+        }
+    }
+
+    if remaining != 0 {
+        slow_search(bytes, 0, remaining, separator, on_match)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+#[allow(unused_unsafe)]
+/// This is a NEON/AdvSIMD-optimized newline search function that searches a 16-byte (128-bit) window
+/// instead of scanning character-by-character (once aligned).
+unsafe fn search128(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    use core::arch::aarch64::*;
+
+    const SIZE: usize = 64;
+    const ALIGNMENT: usize = 16;
+
+    let ptr = bytes.as_ptr();
+    let len = bytes.len();
+
+    // Too short to hold an aligned window once the end is aligned: not worth vectorizing.
+    if len < SIZE + ALIGNMENT - 1 {
+        return slow_search(bytes, 0, len, separator, on_match);
+    }
+
+    // ARMv8 loads do not have alignment *requirements*, but there can be performance penalties
+    // (e.g. seems to be about 2% slowdown on Cortex-A72 with a 500MB file) so let's align.
+    // Search unaligned bytes via slow method so subsequent haystack reads are always aligned.
+    let mut remaining = len;
+    let align_offset = unsafe { ptr.add(len) }.align_offset(ALIGNMENT);
+    if align_offset != 0 {
+        let aligned_index = len + align_offset - ALIGNMENT;
+        slow_search(bytes, aligned_index, len, separator, on_match)?;
+        remaining = aligned_index;
+    }
+
+    let pattern128 = unsafe { vdupq_n_u8(separator) };
+    while remaining >= SIZE {
+        let window_end_offset = remaining;
+        unsafe {
+            remaining -= 16;
+            let window = ptr.add(remaining);
+            let search128 = vld1q_u8(window);
+            let result128_0 = vceqq_u8(search128, pattern128);
+
+            remaining -= 16;
+            let window = ptr.add(remaining);
+            let search128 = vld1q_u8(window);
+            let result128_1 = vceqq_u8(search128, pattern128);
+
+            remaining -= 16;
+            let window = ptr.add(remaining);
+            let search128 = vld1q_u8(window);
+            let result128_2 = vceqq_u8(search128, pattern128);
+
+            remaining -= 16;
+            let window = ptr.add(remaining);
+            let search128 = vld1q_u8(window);
+            let result128_3 = vceqq_u8(search128, pattern128);
+
+            // Bulk movemask as described in
+            // https://branchfree.org/2019/04/01/fitting-my-head-through-the-arm-holes/
+            let mut matches = {
+                let bit_mask: uint8x16_t = core::mem::transmute([
+                    0x01u8, 0x02, 0x4, 0x8, 0x10, 0x20, 0x40, 0x80, 0x01, 0x02, 0x4, 0x8, 0x10, 0x20, 0x40, 0x80,
+                ]);
+                let t0 = vandq_u8(result128_3, bit_mask);
+                let t1 = vandq_u8(result128_2, bit_mask);
+                let t2 = vandq_u8(result128_1, bit_mask);
+                let t3 = vandq_u8(result128_0, bit_mask);
+                let sum0 = vpaddq_u8(t0, t1);
+                let sum1 = vpaddq_u8(t2, t3);
+                let sum0 = vpaddq_u8(sum0, sum1);
+                let sum0 = vpaddq_u8(sum0, sum0);
+                vgetq_lane_u64(vreinterpretq_u64_u8(sum0), 0)
+            };
+
+            while matches != 0 {
+                // We would count *trailing* zeroes to find new lines in reverse order, but the
+                // result mask is in little endian (reversed) order, so we do the very
+                // opposite.
+                let leading = matches.leading_zeros();
+                let offset = window_end_offset - leading as usize;
+
+                on_match(offset)?;
+
+                // Clear this match from the matches bitset.
+                matches &= !(1 << (64 - leading - 1));
+            }
+        }
+    }
+
+    if remaining != 0 {
+        slow_search(bytes, 0, remaining, separator, on_match)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(target_arch = "riscv64", target_os = "linux", feature = "std"))]
+/// Whether the CPU and the kernel support the RISC-V vector extension, as reported by the
+/// `riscv_hwprobe` system call. Kernels without it (before Linux 6.4) do not support vectors in
+/// user space either.
+fn rvv_detected() -> bool {
+    static DETECTED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+    *DETECTED.get_or_init(|| {
+        // `struct riscv_hwprobe` and the constants of `<asm/hwprobe.h>`, which `libc` lacks.
+        #[repr(C)]
+        struct Pair {
+            key: i64,
+            value: u64,
+        }
+        const SYS_RISCV_HWPROBE: libc::c_long = 258;
+        const KEY_IMA_EXT_0: i64 = 4;
+        const IMA_V: u64 = 1 << 2;
+
+        let mut pair = Pair {
+            key: KEY_IMA_EXT_0,
+            value: 0,
+        };
+        // No CPU set: the extensions supported by every CPU.
+        let result = unsafe {
+            libc::syscall(
+                SYS_RISCV_HWPROBE,
+                &mut pair,
+                1usize,
+                0usize,
+                core::ptr::null::<u8>(),
+                0u32,
+            )
+        };
+        result == 0 && pair.key == KEY_IMA_EXT_0 && pair.value & IMA_V != 0
+    })
+}
+
+/// Without `std`, whether the vector extension is enabled at compile time.
+#[cfg(all(target_arch = "riscv64", target_os = "linux", not(feature = "std")))]
+fn rvv_detected() -> bool {
+    cfg!(target_feature = "v")
+}
+
+#[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+/// A RISC-V vector extension (RVV) newline search function. The vector length is only known at run
+/// time, so each step compares as many bytes as fit in a group of four vector registers, up to 64,
+/// and stores the comparison mask into a `u64` scanned like the other SIMD implementations do. This
+/// is a *safe* function, but must be adorned with `unsafe` to guarantee it's not called without
+/// first checking for RVV support.
+///
+/// Vector intrinsics are not available on stable Rust, hence the inline assembly.
+unsafe fn search_rvv(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    // The vector length adapts to inputs shorter than a window, down to none at all.
+    let mut remaining = bytes.len();
+    while remaining != 0 {
+        let len: usize;
+        let mut matches = 0u64;
+        // Compare the (up to) 64 bytes ending at `remaining`, element `i` of the mask being bit `i`.
+        core::arch::asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetvli {len}, {max_len}, e8, m4, ta, ma",
+            "sub {start}, {end}, {len}",
+            "vle8.v v8, ({start})",
+            "vmseq.vx v0, v8, {separator}",
+            "vsm.v v0, ({matches})",
+            ".option pop",
+            len = out(reg) len,
+            max_len = in(reg) remaining.min(64),
+            start = out(reg) _,
+            end = in(reg) bytes.as_ptr().add(remaining),
+            separator = in(reg) separator as usize,
+            matches = in(reg) &mut matches as *mut u64,
+            out("v0") _,
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            options(nostack),
+        );
+        // The mask bits past the last element are unspecified.
+        if len < 64 {
+            matches &= (1 << len) - 1;
+        }
+
+        remaining -= len;
+        while matches != 0 {
+            // The last match is the highest bit, as in the other SIMD implementations.
+            let leading = matches.leading_zeros();
+            on_match(remaining + 64 - leading as usize)?;
+            matches &= !(1 << (64 - leading - 1));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+/// A WebAssembly SIMD128-optimized newline search function, which searches four 16-byte (128-bit)
+/// windows at a time like [`search128`] does on ARM. SIMD128 is enabled at compile time, so unlike
+/// the other SIMD implementations this one is safe to call.
+fn search128_wasm(bytes: &[u8], separator: u8, on_match: &mut OnMatch) -> Result<(), Error> {
+    use core::arch::wasm32::*;
+
+    const SIZE: usize = 64;
+
+    let ptr = bytes.as_ptr();
+    let len = bytes.len();
+
+    // Too short to hold a window: not worth vectorizing.
+    if len < SIZE {
+        return slow_search(bytes, 0, len, separator, on_match);
+    }
+
+    // WebAssembly loads need no alignment, so the windows simply end where the haystack does.
+    let mut remaining = len;
+    let pattern128 = u8x16_splat(separator);
+    while remaining >= SIZE {
+        let window_end_offset = remaining;
+        remaining -= SIZE;
+        // Gather the 16-bit masks of the four windows into one, the first window lowest.
+        let mut matches = 0;
+        for window in (0..SIZE / 16).rev() {
+            // Within `bytes`, since `remaining + SIZE` is at most its length.
+            let search128 = unsafe { v128_load(ptr.add(remaining + window * 16) as *const v128) };
+            matches = matches << 16 | u8x16_bitmask(u8x16_eq(search128, pattern128)) as u64;
+        }
+
+        while matches != 0 {
+            // The mask is in little endian order, so the last match is the highest bit.
+            let leading = matches.leading_zeros();
+            on_match(window_end_offset - leading as usize)?;
+            matches &= !(1 << (SIZE as u32 - 1 - leading));
+        }
+    }
+
+    if remaining != 0 {
+        slow_search(bytes, 0, remaining, separator, on_match)?;
+    }
+
+    Ok(())
+}
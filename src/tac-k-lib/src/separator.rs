@@ -0,0 +1,220 @@
+use std::io::Result;
+use std::ops::Range;
+
+/// What separates the records of an input, see [`ReverseOptions::separator_bytes`] and
+/// [`ReverseOptions::separator_regex`].
+///
+/// [`ReverseOptions::separator_bytes`]: crate::ReverseOptions::separator_bytes
+/// [`ReverseOptions::separator_regex`]: crate::ReverseOptions::separator_regex
+#[derive(Debug, Clone)]
+pub(crate) enum Separator {
+    /// A fixed, non-empty byte string.
+    Bytes(Vec<u8>),
+    /// Any match of a regular expression, other than an empty one.
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::Regex),
+}
+
+impl Separator {
+    /// Report every separator in `bytes` to `on_match`, from the end of `bytes` to its start.
+    /// Returning an error aborts the search.
+    pub(crate) fn search(&self, bytes: &[u8], on_match: &mut dyn FnMut(Range<usize>) -> Result<()>) -> Result<()> {
+        match self {
+            Separator::Bytes(separator) => crate::search::search_separator(bytes, separator, &mut |offset| {
+                on_match(offset - separator.len()..offset)
+            }),
+            // Regular expressions can only be matched forwards, so collect the matches first.
+            #[cfg(feature = "regex")]
+            Separator::Regex(regex) => {
+                let found: Vec<Range<usize>> = matches(regex, bytes).collect();
+                found.into_iter().rev().try_for_each(on_match)
+            }
+        }
+    }
+
+    /// The separator if it is a fixed byte string.
+    pub(crate) fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            Separator::Bytes(separator) => Some(separator),
+            #[cfg(feature = "regex")]
+            Separator::Regex(_) => None,
+        }
+    }
+
+    /// Find the first separator in `bytes`.
+    pub(crate) fn find(&self, bytes: &[u8]) -> Option<Range<usize>> {
+        match self {
+            Separator::Bytes(separator) => bytes
+                .windows(separator.len())
+                .position(|window| window == &separator[..])
+                .map(|index| index..index + separator.len()),
+            #[cfg(feature = "regex")]
+            Separator::Regex(regex) => matches(regex, bytes).next(),
+        }
+    }
+
+    /// Find the last separator in `bytes`.
+    pub(crate) fn rfind(&self, bytes: &[u8]) -> Option<Range<usize>> {
+        match self {
+            Separator::Bytes(separator) => crate::rfind(bytes, separator).map(|index| index..index + separator.len()),
+            #[cfg(feature = "regex")]
+            Separator::Regex(regex) => matches(regex, bytes).last(),
+        }
+    }
+
+    /// The content of `record` without its separator, if it has one: a leading one if `before`,
+    /// see [`ReverseOptions::before`](crate::ReverseOptions::before), and a trailing one otherwise.
+    pub(crate) fn strip<'r>(&self, record: &'r [u8], before: bool) -> Option<&'r [u8]> {
+        match self {
+            Separator::Bytes(separator) if before => record.strip_prefix(&separator[..]),
+            Separator::Bytes(separator) => record.strip_suffix(&separator[..]),
+            #[cfg(feature = "regex")]
+            Separator::Regex(regex) if before => match matches(regex, record).next() {
+                Some(range) if range.start == 0 => Some(&record[range.end..]),
+                _ => None,
+            },
+            #[cfg(feature = "regex")]
+            Separator::Regex(regex) => match matches(regex, record).last() {
+                Some(range) if range.end == record.len() => Some(&record[..range.start]),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Counts the records of an input fed to it from its start, as they are reversed, so that they can be
+/// numbered without reading the input twice, see [`ReverseOptions::number`].
+///
+/// Only fixed separators that cannot overlap themselves are counted this way, since their
+/// occurrences are then the same whether they are searched for forwards or backwards: `--` is found
+/// at the start of `---` by the former, and at its end by the latter.
+///
+/// [`ReverseOptions::number`]: crate::ReverseOptions::number
+#[cfg(any(feature = "lz4", feature = "encrypt"))]
+pub(crate) struct RecordCounter {
+    separator: Vec<u8>,
+    before: bool,
+    /// Number of separators found so far.
+    separators: u64,
+    /// Length of the input fed so far.
+    len: u64,
+    /// Offsets of the start of the first separator found and of the end of the last one.
+    first: Option<u64>,
+    last: u64,
+    /// The last bytes fed, fewer than a separator, in which one may start.
+    tail: Vec<u8>,
+}
+
+#[cfg(any(feature = "lz4", feature = "encrypt"))]
+impl RecordCounter {
+    /// Create a counter for records separated by `separator`, unless it cannot count them.
+    pub(crate) fn new(separator: &Separator, before: bool) -> Option<Self> {
+        let separator = separator.bytes()?;
+        let overlaps = (1..separator.len()).any(|len| separator.ends_with(&separator[..len]));
+        (!overlaps).then(|| RecordCounter {
+            separator: separator.to_vec(),
+            before,
+            separators: 0,
+            len: 0,
+            first: None,
+            last: 0,
+            tail: Vec::new(),
+        })
+    }
+
+    /// Count the separators in `bytes`, which follow all bytes fed so far.
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        // Separators straddling the bytes fed before and these, which neither holds all of.
+        let head = &bytes[..bytes.len().min(self.separator.len() - 1)];
+        let mut boundary = std::mem::take(&mut self.tail);
+        let base = self.len - boundary.len() as u64;
+        boundary.extend_from_slice(head);
+        self.find(&boundary, base);
+        self.find(bytes, self.len);
+        self.len += bytes.len() as u64;
+
+        let keep = self.separator.len() - 1;
+        if head.len() == bytes.len() {
+            boundary.drain(..boundary.len().saturating_sub(keep));
+        } else {
+            boundary.clear();
+            boundary.extend_from_slice(&bytes[bytes.len() - keep..]);
+        }
+        self.tail = boundary;
+    }
+
+    fn find(&mut self, bytes: &[u8], base: u64) {
+        let separator = &self.separator[..];
+        let (separators, first, last) = (&mut self.separators, &mut self.first, &mut self.last);
+        // Counting cannot fail.
+        let _ = crate::search::search_separator(bytes, separator, &mut |end| {
+            let end = base + end as u64;
+            let start = end - separator.len() as u64;
+            *separators += 1;
+            *first = Some(first.map_or(start, |first| first.min(start)));
+            *last = (*last).max(end);
+            Ok(())
+        });
+    }
+
+    /// Number of records of the input fed so far, if it is complete.
+    pub(crate) fn records(&self) -> u64 {
+        let unseparated = match self.before {
+            // Whatever precedes the first separator is a record of its own.
+            true => self.first.unwrap_or(self.len) != 0,
+            // And so is whatever follows the last one.
+            false => self.last != self.len,
+        };
+        self.separators + u64::from(unseparated)
+    }
+}
+
+/// Resolve the escape sequences in a separator given as text, e.g. on a command line: `\0`, `\t`,
+/// `\n`, `\r`, `\\` and `\xNN` for any byte in hexadecimal. Other characters are taken as they are.
+///
+/// This lets front-ends accept bytes a shell cannot easily pass, like a tab or `0xff`. An unknown
+/// or truncated escape sequence fails with [`std::io::ErrorKind::InvalidInput`].
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::unescape;
+///
+/// assert_eq!(unescape(r"\t").unwrap(), b"\t");
+/// assert_eq!(unescape(r"\xff\0").unwrap(), b"\xff\0");
+/// assert!(unescape(r"\q").is_err());
+/// ```
+pub fn unescape(value: &str) -> Result<Vec<u8>> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let mut unescaped = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        unescaped.push(match bytes.next() {
+            Some(b'0') => b'\0',
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b'\\') => b'\\',
+            Some(b'x') => match [bytes.next(), bytes.next()].map(|digit| char::from(digit?).to_digit(16)) {
+                [Some(high), Some(low)] => (high << 4 | low) as u8,
+                _ => return Err(invalid("'\\x' must be followed by two hexadecimal digits".into())),
+            },
+            Some(other) => return Err(invalid(format!("unknown escape sequence '\\{}'", char::from(other)))),
+            None => return Err(invalid("trailing '\\' (use '\\\\' for a backslash)".into())),
+        });
+    }
+    Ok(unescaped)
+}
+
+/// The non-empty matches of `regex` in `bytes`.
+#[cfg(feature = "regex")]
+fn matches<'a>(regex: &'a regex::bytes::Regex, bytes: &'a [u8]) -> impl Iterator<Item = Range<usize>> + 'a {
+    regex
+        .find_iter(bytes)
+        .map(|m| m.range())
+        .filter(|range| !range.is_empty())
+}
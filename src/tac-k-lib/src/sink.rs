@@ -0,0 +1,68 @@
+/// A destination for the records of [`reverse_into`], which needs neither `std` nor an allocator.
+///
+/// ## Example
+///
+/// ```
+/// use tac_k_lib::{reverse_into, Sink};
+///
+/// /// Copies records into a fixed buffer, failing once it is full.
+/// struct Buffer<'a> {
+///     buf: &'a mut [u8],
+///     len: usize,
+/// }
+///
+/// impl Sink for Buffer<'_> {
+///     type Error = ();
+///
+///     fn record(&mut self, record: &[u8]) -> Result<(), ()> {
+///         let end = self.len + record.len();
+///         self.buf.get_mut(self.len..end).ok_or(())?.copy_from_slice(record);
+///         self.len = end;
+///         Ok(())
+///     }
+/// }
+///
+/// let mut buf = [0; 8];
+/// let mut sink = Buffer { buf: &mut buf, len: 0 };
+/// reverse_into(b"one\ntwo\n", b'\n', &mut sink).unwrap();
+/// assert_eq!(&buf, b"two\none\n");
+/// ```
+pub trait Sink {
+    /// Error returned by [`record`](Sink::record), which aborts the reversal.
+    type Error;
+
+    /// Take the next record, including its trailing separator if any.
+    fn record(&mut self, record: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Pass the records of `bytes` separated by `separator` to `sink`, last record first, with the
+/// same SIMD-accelerated backwards scan as [`reverse_file`](crate::reverse_file).
+///
+/// Records are passed exactly as [`rev_records`](crate::rev_records) yields them, but `bytes` is
+/// scanned in one uninterrupted pass. This is all the library offers without the `std` feature,
+/// e.g. to reverse a buffer in firmware. An error returned by `sink` stops the scan immediately and
+/// is returned as is.
+pub fn reverse_into<S: Sink + ?Sized>(bytes: &[u8], separator: u8, sink: &mut S) -> Result<(), S::Error> {
+    // End of the part of the input not passed on yet.
+    let mut end = bytes.len();
+    let mut error = None;
+    let _ = crate::search::search_auto(bytes, separator, &mut |offset| {
+        // A separator at the very end terminates the last record instead of starting an empty one.
+        if offset == end {
+            return Ok(());
+        }
+        if let Err(e) = sink.record(&bytes[offset..end]) {
+            error = Some(e);
+            return Err(crate::search::abort());
+        }
+        end = offset;
+        Ok(())
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    if end != 0 {
+        sink.record(&bytes[..end])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::fs::{File, OpenOptions};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// A file that buffered `stdin` is spilled into once it outgrows the in-memory buffer.
+///
+/// The whole input is written to `file` and then memory-mapped, so the handle must be opened for
+/// both reading and writing, and whatever ends up on disk must be the plain input.
+#[derive(Debug)]
+pub struct SpillFile {
+    /// Handle to write the input into and map it from afterwards.
+    pub file: File,
+    /// Path removed once the input has been reversed, if any.
+    pub path: Option<PathBuf>,
+}
+
+/// Hook deciding where and how a [`SpillFile`] is created, see [`ReverseOptions::spill_file`].
+///
+/// [`ReverseOptions::spill_file`]: crate::ReverseOptions::spill_file
+pub type SpillHook = dyn Fn() -> Result<SpillFile> + Send + Sync;
+
+/// What happens to buffered `stdin` once it outgrows the in-memory buffer, see
+/// [`ReverseOptions::spill_policy`].
+///
+/// Without a policy, the input is kept in memory up to 4 MiB and spilled into the file created by
+/// [`ReverseOptions::spill_file`] beyond that.
+///
+/// [`ReverseOptions::spill_policy`]: crate::ReverseOptions::spill_policy
+/// [`ReverseOptions::spill_file`]: crate::ReverseOptions::spill_file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpillPolicy {
+    /// Keep the whole input in memory, and fail if it is longer than the given number of bytes.
+    InMemoryUpTo(usize),
+    /// Spill into a temporary file in the given directory, see [`SpillFile::in_dir`].
+    TempFileIn(PathBuf),
+    /// Never spill, and fail if the input outgrows the in-memory buffer.
+    Fail,
+}
+
+impl SpillFile {
+    /// Create a spill file in [`std::env::temp_dir()`], i.e. `$TMPDIR` if set on Unix, see
+    /// [`in_dir`](Self::in_dir). This is the default when no hook is configured.
+    pub fn in_temp_dir() -> Result<SpillFile> {
+        // There is no file system on `wasm32-unknown-unknown`, where `temp_dir` panics.
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        return Err(std::io::ErrorKind::Unsupported.into());
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        Self::in_dir(&std::env::temp_dir())
+    }
+
+    /// Create a spill file named `.tac-<pid>-<random>` in `dir`.
+    ///
+    /// The file must not exist yet (`O_EXCL`), so neither a file left behind by another process nor
+    /// a symlink planted under a guessed name is ever opened. On Unix, it is only accessible to its
+    /// owner.
+    pub fn in_dir(dir: &Path) -> Result<SpillFile> {
+        use std::hash::{BuildHasher, Hasher};
+
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut attempts = 0;
+        loop {
+            // `RandomState` is seeded from the OS, which is plenty for a name that is not guessed.
+            let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+            let path = dir.join(format!(".tac-{}-{random:016x}", process_id()));
+            match options.open(&path) {
+                Ok(file) => return Ok(SpillFile { file, path: Some(path) }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 16 => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Create an anonymous, memory-backed spill file with `memfd_create(2)`.
+    ///
+    /// It never touches the filesystem and disappears with its last handle, so there is nothing to
+    /// clean up. Like `tmpfs`, its content lives in memory and swap rather than on disk.
+    #[cfg(target_os = "linux")]
+    pub fn memfd() -> Result<SpillFile> {
+        use std::os::fd::FromRawFd;
+
+        let fd = unsafe { libc::memfd_create(c"tac-spill".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        Ok(SpillFile { file, path: None })
+    }
+}
+
+/// Overwrite the content of the file at `path` with zeroes and sync it to disk.
+pub(crate) fn overwrite(path: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let zeroes = vec![0; 1024 * 1024];
+    while remaining > 0 {
+        let len = remaining.min(zeroes.len() as u64) as usize;
+        file.write_all(&zeroes[..len])?;
+        remaining -= len as u64;
+    }
+    file.sync_all()
+}
+
+/// Spill file holding the input as a sequence of independently transformed (compressed and/or
+/// encrypted) blocks, so it can be read back block by block from its end during the reverse pass.
+#[cfg(any(feature = "lz4", feature = "encrypt"))]
+pub(crate) struct BlockSpill {
+    file: File,
+    /// Offset and stored length of every block, in input order.
+    blocks: Vec<(u64, usize)>,
+    end: u64,
+    /// Length of the input stored so far.
+    len: u64,
+    /// Counts the records stored so far, if they are to be numbered.
+    counter: Option<crate::separator::RecordCounter>,
+    /// The start of the input, up to its first separator once it has been stored, if the leading
+    /// fragment is written first, see [`ReverseOptions::leading_fragment`](crate::ReverseOptions::leading_fragment).
+    head: Vec<u8>,
+    /// The separator ending `head`, until it has been stored.
+    heading: Option<crate::separator::Separator>,
+    #[cfg(feature = "lz4")]
+    compress: bool,
+    #[cfg(feature = "encrypt")]
+    cipher: Option<chacha20::XChaCha20>,
+}
+
+#[cfg(any(feature = "lz4", feature = "encrypt"))]
+impl BlockSpill {
+    pub(crate) fn new(file: File, options: &crate::ReverseOptions) -> Result<Self> {
+        Ok(BlockSpill {
+            file,
+            blocks: Vec::new(),
+            end: 0,
+            len: 0,
+            counter: options
+                .number
+                .then(|| crate::separator::RecordCounter::new(&options.separator, options.before))
+                .flatten(),
+            head: Vec::new(),
+            heading: (options.before && options.leading_fragment == crate::Fragment::First)
+                .then(|| options.separator.clone()),
+            #[cfg(feature = "lz4")]
+            compress: options.compress_spill,
+            #[cfg(feature = "encrypt")]
+            cipher: if options.encrypt_spill {
+                Some(ephemeral_cipher()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Whether `options` require the spill to be stored as blocks rather than as the plain input.
+    pub(crate) fn required(options: &crate::ReverseOptions) -> bool {
+        #[cfg(feature = "lz4")]
+        if options.compress_spill {
+            return true;
+        }
+        #[cfg(feature = "encrypt")]
+        if options.encrypt_spill {
+            return true;
+        }
+        let _ = options;
+        false
+    }
+
+    /// Store the next block of the input.
+    pub(crate) fn push(&mut self, block: &[u8]) -> Result<()> {
+        self.len += block.len() as u64;
+        if let Some(counter) = self.counter.as_mut() {
+            counter.feed(block);
+        }
+        if let Some(separator) = &self.heading {
+            // A fixed separator may straddle the blocks, a regular expression match anything.
+            let searched = match separator.bytes() {
+                Some(separator) => self.head.len().saturating_sub(separator.len() - 1),
+                None => 0,
+            };
+            crate::try_reserve(&mut self.head, block.len())?;
+            self.head.extend_from_slice(block);
+            if separator.find(&self.head[searched..]).is_some() {
+                self.heading = None;
+            }
+        }
+
+        #[cfg(feature = "lz4")]
+        let compressed;
+        #[cfg(feature = "lz4")]
+        let block = if self.compress {
+            compressed = lz4_flex::block::compress_prepend_size(block);
+            &compressed[..]
+        } else {
+            block
+        };
+        #[cfg(feature = "encrypt")]
+        if let Some(cipher) = self.cipher.as_mut() {
+            use chacha20::cipher::{StreamCipher, StreamCipherSeek};
+
+            let mut block = block.to_vec();
+            cipher.seek(self.end);
+            cipher.apply_keystream(&mut block);
+            return self.append(&block);
+        }
+        self.append(block)
+    }
+
+    fn append(&mut self, block: &[u8]) -> Result<()> {
+        use std::io::Write;
+
+        self.file.write_all(block)?;
+        self.blocks.push((self.end, block.len()));
+        self.end += block.len() as u64;
+        Ok(())
+    }
+
+    /// Length of the input stored so far.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Number of records of the input stored so far, if it was counted while storing it, see
+    /// [`ReverseOptions::number`](crate::ReverseOptions::number).
+    pub(crate) fn records(&self) -> Option<u64> {
+        self.counter.as_ref().map(crate::separator::RecordCounter::records)
+    }
+
+    /// Emit the reversed input, restoring one block at a time.
+    pub(crate) fn reverse(
+        &mut self,
+        separator: &crate::separator::Separator,
+        emitter: &mut crate::emit::Emitter,
+    ) -> Result<()> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        emitter.lead(&self.head)?;
+        let mut reverser = crate::chunked::ChunkedReverser::new(separator, self.len);
+        let mut block = Vec::new();
+        #[cfg(feature = "lz4")]
+        let mut decompressed = Vec::new();
+        for &(offset, len) in self.blocks.iter().rev() {
+            block.resize(len, 0);
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.read_exact(&mut block)?;
+
+            #[cfg(feature = "encrypt")]
+            if let Some(cipher) = self.cipher.as_mut() {
+                use chacha20::cipher::{StreamCipher, StreamCipherSeek};
+
+                cipher.seek(offset);
+                cipher.apply_keystream(&mut block);
+            }
+            #[cfg(feature = "lz4")]
+            if self.compress {
+                let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+                let (len, compressed) = lz4_flex::block::uncompressed_size(&block).map_err(invalid)?;
+                decompressed.resize(len, 0);
+                lz4_flex::block::decompress_into(compressed, &mut decompressed).map_err(invalid)?;
+                reverser.feed(&decompressed, emitter)?;
+                continue;
+            }
+
+            reverser.feed(&block, emitter)?;
+        }
+        reverser.finish(emitter)
+    }
+}
+
+/// Create an XChaCha20 cipher with a random key and nonce that only ever live in memory.
+#[cfg(feature = "encrypt")]
+fn ephemeral_cipher() -> Result<chacha20::XChaCha20> {
+    use chacha20::cipher::KeyIvInit;
+
+    let mut key = [0; 32];
+    let mut nonce = [0; 24];
+    getrandom::getrandom(&mut key).map_err(std::io::Error::from)?;
+    getrandom::getrandom(&mut nonce).map_err(std::io::Error::from)?;
+    Ok(chacha20::XChaCha20::new(&key.into(), &nonce.into()))
+}
+
+/// The id of the current process, or 0 on `wasm32-unknown-unknown`, where `std::process::id` panics.
+fn process_id() -> u32 {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return 0;
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    std::process::id()
+}
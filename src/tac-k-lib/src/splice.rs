@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::io::{IoSlice, Result, Write};
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::path::Path;
+
+use crate::{Error, Input, Output, OutputFormat, ReverseOptions};
+
+pub(crate) fn run(options: &ReverseOptions, pipe: BorrowedFd, path: Option<&Path>) -> std::result::Result<(), Error> {
+    let mut output = File::from(pipe.try_clone_to_owned()?);
+    // Anything but records written as they are goes through buffers that do not outlive the write.
+    let plain = options.output_format == OutputFormat::Raw && options.characters.is_none() && options.label.is_none();
+    if !plain || !is_pipe(&output) {
+        return options.run(&mut output, path);
+    }
+    let contents = options.open(path)?;
+    if !contents.is_mapped() {
+        return options.run_bytes(&mut output, &contents);
+    }
+    options.trace(|| "splicing the mapped input into the pipe".into());
+    let input = match path {
+        Some(path) => Input::Range(path, &contents, 0),
+        None => Input::Bytes(&contents),
+    };
+    crate::reverse(
+        &mut [Output::new(&mut Vmsplice { pipe: output })],
+        input,
+        options,
+        &mut options.new_buffer(),
+    )
+    .map(drop)
+    .map_err(Error::from)
+}
+
+/// Whether `file` is a pipe, which `vmsplice` requires.
+fn is_pipe(file: &File) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file.metadata().is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+/// Hands the pages of the records over to the pipe with `vmsplice` instead of copying them.
+///
+/// The pipe keeps referencing the pages after the write returns, so this relies on vectored writes
+/// only ever being passed slices of the mapped input, as the emitter of a plain output does, see
+/// `Emitter::records`. Other writes, e.g. of a fragment held back, are copied as usual.
+struct Vmsplice {
+    pipe: File,
+}
+
+impl Write for Vmsplice {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.pipe.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        // `IoSlice` is guaranteed to be ABI compatible with `iovec` on Unix.
+        let count = bufs.len().min(libc::UIO_MAXIOV as usize);
+        let written = unsafe { libc::vmsplice(self.pipe.as_raw_fd(), bufs.as_ptr().cast(), count, 0) };
+        if written >= 0 {
+            return Ok(written as usize);
+        }
+        match std::io::Error::last_os_error() {
+            // Not supported for this pipe after all, copy instead.
+            e if e.raw_os_error() == Some(libc::EINVAL) || e.raw_os_error() == Some(libc::ENOSYS) => {
+                self.pipe.write_vectored(bufs)
+            }
+            e => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
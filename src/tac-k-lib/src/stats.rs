@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::Engine;
+
+/// Figures describing how an input was reversed, see [`ReverseOptions::on_stats`] and
+/// [`ReverseOptions::run_with_stats`].
+///
+/// [`ReverseOptions::on_stats`]: crate::ReverseOptions::on_stats
+/// [`ReverseOptions::run_with_stats`]: crate::ReverseOptions::run_with_stats
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Path of the input, `None` for `stdin`.
+    pub path: Option<PathBuf>,
+    /// Length of the input in bytes.
+    pub bytes: u64,
+    /// Number of bytes written into the outputs, which differs from `bytes` if records were left
+    /// out or formatted, e.g. by [`max_records`](crate::ReverseOptions::max_records).
+    pub bytes_written: u64,
+    /// Number of records written.
+    pub records: u64,
+    /// The search implementation that scanned the input, never [`Engine::Auto`].
+    pub engine: Engine,
+    /// Whether the input had to be spilled to a temporary file.
+    pub spilled: bool,
+    /// Time spent opening the input, and reading or spilling it if it had to be buffered.
+    pub input_time: Duration,
+    /// Time spent scanning the input and writing the records.
+    pub reverse_time: Duration,
+}
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// Outcome of reversing several inputs in turn, see [`ReverseOptions::run_all`].
+///
+/// A failing input does not prevent the next ones from being reversed, unless it is the writer
+/// that failed: the remaining inputs are then skipped, as nothing could be written for them anyway.
+///
+/// [`ReverseOptions::run_all`]: crate::ReverseOptions::run_all
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Path of every input reversed, `None` for `stdin`, with its result, in order.
+    pub inputs: Vec<(Option<PathBuf>, Result<(), Error>)>,
+    /// Number of inputs left out after the writer failed.
+    pub skipped: usize,
+}
+
+impl Summary {
+    /// Whether every input was reversed successfully.
+    pub fn is_success(&self) -> bool {
+        self.skipped == 0 && self.failed() == 0
+    }
+
+    /// Number of inputs reversed successfully.
+    pub fn succeeded(&self) -> usize {
+        self.inputs.iter().filter(|(_, result)| result.is_ok()).count()
+    }
+
+    /// Number of inputs that failed, not counting the skipped ones.
+    pub fn failed(&self) -> usize {
+        self.inputs.len() - self.succeeded()
+    }
+
+    /// The path and error of every input that failed, in order.
+    pub fn errors(&self) -> impl Iterator<Item = (Option<&Path>, &Error)> {
+        self.inputs
+            .iter()
+            .filter_map(|(path, result)| Some((path.as_deref(), result.as_ref().err()?)))
+    }
+}
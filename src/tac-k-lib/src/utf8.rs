@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{Read, Result};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::separator::Separator;
+use crate::{ReverseOptions, StdinReader};
+
+/// Size of the chunks inputs that cannot be mapped are validated in.
+const CHUNK_SIZE: usize = 1024 * 1024; // 1 MiB
+
+/// Location of the first invalid UTF-8 sequence of an input, see [`ReverseOptions::check_utf8`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// Offset of the first byte of the invalid sequence.
+    pub offset: u64,
+    /// Offset of the record containing it.
+    pub record_offset: u64,
+}
+
+pub(crate) fn check(path: Option<&Path>, options: &ReverseOptions) -> Result<Option<InvalidUtf8>> {
+    let mut reader: Box<dyn Read> = match path {
+        None => {
+            if options.mmap {
+                if let Ok(mmap) = crate::map_stdin() {
+                    return Ok(check_bytes(&mmap, &options.separator, options.before));
+                }
+            }
+            Box::new(StdinReader::new(options.stdin_timeout)?)
+        }
+        Some(path) => {
+            #[cfg(feature = "zip")]
+            if let Some((archive_path, name)) = crate::archive::split_member(path) {
+                let mut archive = crate::archive::open(archive_path)?;
+                let mut member = crate::archive::member(&mut archive, archive_path, name)?;
+                return check_reader(&mut member, &options.separator, options.before);
+            }
+            #[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+            if let Some(mut decoder) = crate::decompress::open(path)? {
+                return check_reader(&mut decoder, &options.separator, options.before);
+            }
+            let file = File::open(path)?;
+            let metadata = file.metadata()?;
+            if options.mmap
+                && metadata.is_file()
+                && metadata.len() >= options.mmap_threshold
+                && crate::network_fs(&file).is_none()
+            {
+                // A file too large to be mapped is validated chunk by chunk instead.
+                if let Ok(mmap) = unsafe { Mmap::map(&file) } {
+                    return Ok(check_bytes(&mmap, &options.separator, options.before));
+                }
+            }
+            Box::new(file)
+        }
+    };
+    check_reader(&mut reader, &options.separator, options.before)
+}
+
+/// Find the first invalid UTF-8 sequence of the input read from `reader`. `before` tells whether
+/// records start with their separator, see [`ReverseOptions::before`].
+pub(crate) fn check_reader(reader: &mut dyn Read, separator: &Separator, before: bool) -> Result<Option<InvalidUtf8>> {
+    // Validate chunk by chunk, carrying over a sequence cut in two by the end of a chunk.
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut carry = 0;
+    // Offset of `buf` in the input, and of the start of the last record seen so far.
+    let mut base = 0;
+    let mut record_offset = 0;
+    loop {
+        let bytes_read = reader.read(&mut buf[carry..])?;
+        if bytes_read == 0 {
+            // Whatever is carried over is a truncated sequence.
+            return Ok((carry != 0).then_some(InvalidUtf8 {
+                offset: base,
+                record_offset,
+            }));
+        }
+        let chunk = &buf[..carry + bytes_read];
+        let (valid, invalid) = match std::str::from_utf8(chunk) {
+            Ok(_) => (chunk.len(), false),
+            // Without an error length the chunk merely ends in the middle of a sequence.
+            Err(e) => (e.valid_up_to(), e.error_len().is_some()),
+        };
+        if let Some(found) = separator.rfind(&chunk[..valid]) {
+            record_offset = base + if before { found.start } else { found.end } as u64;
+        }
+        if invalid {
+            return Ok(Some(InvalidUtf8 {
+                offset: base + valid as u64,
+                record_offset,
+            }));
+        }
+        carry = chunk.len() - valid;
+        buf.copy_within(valid..valid + carry, 0);
+        base += valid as u64;
+    }
+}
+
+/// Find the first invalid UTF-8 sequence of the whole input `bytes`.
+pub(crate) fn check_bytes(bytes: &[u8], separator: &Separator, before: bool) -> Option<InvalidUtf8> {
+    let offset = std::str::from_utf8(bytes).err()?.valid_up_to();
+    let record_offset = separator
+        .rfind(&bytes[..offset])
+        .map_or(0, |found| if before { found.start } else { found.end });
+    Some(InvalidUtf8 {
+        offset: offset as u64,
+        record_offset: record_offset as u64,
+    })
+}
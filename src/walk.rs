@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+
+use std::path::Path;
+
+/// Which of the files found under a directory are reversed, see `--include` and `--exclude`.
+pub struct Filter<'a> {
+    pub include: Vec<&'a str>,
+    pub exclude: Vec<&'a str>,
+}
+
+impl Filter<'_> {
+    /// Whether the file named `name` is reversed: it matches one of the `include` patterns if
+    /// there are any, and none of the `exclude` ones.
+    fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, name)))
+            && !self.exclude.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// Replace every directory among `files` with the regular files under it, recursively, see
+/// `--recursive`.
+///
+/// The entries of a directory are sorted by name so that the order does not depend on the
+/// filesystem. Symbolic links to directories are not followed below the command line, to avoid
+/// walking in circles.
+pub fn expand(files: Vec<String>, filter: &Filter) -> Result<Vec<String>> {
+    let mut expanded = Vec::with_capacity(files.len());
+    for file in files {
+        if file != "-" && Path::new(&file).is_dir() {
+            walk(Path::new(&file), filter, &mut expanded)?;
+        } else {
+            expanded.push(file);
+        }
+    }
+    Ok(expanded)
+}
+
+fn walk(dir: &Path, filter: &Filter, files: &mut Vec<String>) -> Result<()> {
+    let read = || -> std::io::Result<_> {
+        let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        Ok(entries)
+    };
+    let entries = read().with_context(|| format!("failed to read directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if file_type.is_dir() {
+            walk(&path, filter, files)?;
+            continue;
+        }
+        // Links to files are reversed like the files themselves, anything else is skipped.
+        if !(file_type.is_file() || file_type.is_symlink() && path.is_file()) {
+            continue;
+        }
+        let Some(file) = path.to_str() else {
+            eprintln!("tac: skipping {}: not valid UTF-8", path.display());
+            continue;
+        };
+        if entry.file_name().to_str().is_some_and(|name| filter.matches(name)) {
+            files.push(file.to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` matches the shell-style `pattern`, in which `*` matches any number of characters,
+/// `?` any single character and `[...]` any of the characters listed, ranges like `a-z` included,
+/// or any character but those if it starts with `!`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`, if matching what follows it fails: the pattern just past
+    // it, and the name one character further than last time.
+    let mut resume = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                resume = Some((p, n));
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            Some('[') => {
+                if let Some((matched, len)) = match_class(&pattern[p + 1..], name[n]) {
+                    if matched {
+                        p += 1 + len;
+                        n += 1;
+                        continue;
+                    }
+                } else if name[n] == '[' {
+                    // An unterminated class is a literal `[`.
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+            }
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match resume {
+            Some((star_p, star_n)) => {
+                resume = Some((star_p, star_n + 1));
+                p = star_p;
+                n = star_n + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the class at the start of `class`, which follows its opening `[`. Returns
+/// whether it matched and the length of the class including the closing `]`, or `None` if it is
+/// not terminated.
+fn match_class(class: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = class.first() == Some(&'!');
+    let start = usize::from(negated);
+    // A `]` right at the start is part of the class.
+    let end = start + 1 + class.get(start + 1..)?.iter().position(|&c| c == ']')?;
+    let members = &class[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < members.len() {
+        if members.get(i + 1) == Some(&'-') && i + 2 < members.len() {
+            matched |= (members[i]..=members[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= members[i] == c;
+            i += 1;
+        }
+    }
+    Some((matched != negated, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        for (pattern, name, expected) in [
+            ("*.log", "app.log", true),
+            ("*.log", "app.log.1", false),
+            ("*.log", ".log", true),
+            ("*", "", true),
+            ("", "", true),
+            ("", "a", false),
+            // `*` backtracks past earlier partial matches.
+            ("*ab*ab", "aabxabab", true),
+            ("a*b*c", "abcbc", true),
+            ("a*b*c", "abcbd", false),
+            ("**a", "bba", true),
+            ("?", "a", true),
+            ("?", "", false),
+            ("??.txt", "ab.txt", true),
+            ("??.txt", "a.txt", false),
+            ("é?", "éé", true),
+            ("[abc].rs", "b.rs", true),
+            ("[abc].rs", "d.rs", false),
+            ("[a-c]x", "bx", true),
+            ("[a-c]x", "dx", false),
+            ("[!a-c]x", "dx", true),
+            ("[!a-c]x", "ax", false),
+            ("[0-9][0-9]", "42", true),
+            // A `]` first in a class is part of it, and a trailing `-` is literal.
+            ("[]]", "]", true),
+            ("[]a]", "a", true),
+            ("[!]]", "]", false),
+            ("[!]]", "a", true),
+            ("[a-]", "-", true),
+            // An unterminated `[` is a literal.
+            ("[ab", "[ab", true),
+            ("[ab", "a", false),
+            ("x[", "x[", true),
+            ("[]", "[]", true),
+            ("*[", "ab[", true),
+        ] {
+            assert_eq!(glob_match(pattern, name), expected, "{pattern:?} against {name:?}");
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = |include: &[&'static str], exclude: &[&'static str]| Filter {
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+        };
+        assert!(filter(&[], &[]).matches("any"));
+        assert!(filter(&["*.log", "*.txt"], &[]).matches("a.txt"));
+        assert!(!filter(&["*.log"], &[]).matches("a.txt"));
+        assert!(!filter(&[], &["*.gz"]).matches("a.log.gz"));
+        // Excluding wins over including, whichever is given first.
+        assert!(!filter(&["*.log*"], &["*.gz"]).matches("a.log.gz"));
+        assert!(filter(&["*.log*"], &["*.gz"]).matches("a.log.1"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = std::env::temp_dir().join(format!("tac-k-test-walk-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("b/c")).unwrap();
+        for file in ["b/c/z.log", "b/a.log", "b/a.gz", "a.log", "c.txt"] {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        let root = dir.to_str().unwrap().to_owned();
+        let expanded = expand(
+            vec!["-".to_owned(), root.clone()],
+            &Filter {
+                include: vec!["*.log", "*.gz"],
+                exclude: vec!["*.gz"],
+            },
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let files: Vec<_> = ["a.log", "b/a.log", "b/c/z.log"]
+            .iter()
+            .map(|file| dir.join(file).to_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(expanded, [&["-".to_owned()][..], &files].concat());
+    }
+}